@@ -0,0 +1,188 @@
+use std::path::PathBuf;
+
+use crate::{
+    export::{ExportFormat, SCALE_RANGE},
+    renderer::Renderer,
+};
+
+const USAGE: &str = "\
+Usage: image-annotator [OPTIONS] [IMAGE]...
+
+Options:
+    --renderer <wgpu|glow|software>  Rendering backend (default: auto)
+    --format <png|jpeg|webp|bmp|tiff|svg|pdf>
+                                     Format used by Save (default: last used)
+    --quality <1-100>                JPEG and WebP quality; makes WebP lossy
+    --scale <factor>                 Export scale, e.g. 0.5, 2 or 1.5x
+    -h, --help                       Print help
+
+Environment:
+    ANNOTATOR_RENDERER               Same as --renderer";
+
+/// 命令行参数
+#[derive(Debug, Clone, Default)]
+pub struct Args {
+    /// 启动时打开的图片，每张一个标签页
+    pub paths: Vec<PathBuf>,
+    /// 指定的渲染后端，未指定时自动选择
+    pub renderer: Option<Renderer>,
+    /// 本次运行保存时使用的格式
+    pub format: Option<ExportFormat>,
+    /// 本次运行保存时使用的质量
+    pub quality: Option<u8>,
+    /// 本次运行导出时使用的倍率
+    pub scale: Option<f32>,
+}
+
+impl Args {
+    /// 解析命令行参数，参数错误时打印用法并退出
+    pub fn parse() -> Self {
+        match Self::try_parse(std::env::args().skip(1)) {
+            Ok(args) => args,
+            Err(err) => {
+                if !err.is_empty() {
+                    eprintln!("error: {err}\n");
+                }
+                eprintln!("{USAGE}");
+                std::process::exit(if err.is_empty() { 0 } else { 2 });
+            }
+        }
+    }
+
+    /// 返回 `Err("")` 表示请求显示帮助
+    fn try_parse(mut iter: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut args = Args::default();
+        while let Some(arg) = iter.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_owned(), Some(value.to_owned()))
+                }
+                _ => (arg.clone(), None),
+            };
+            match flag.as_str() {
+                "-h" | "--help" => return Err(String::new()),
+                "--renderer" => {
+                    let value = inline_value
+                        .or_else(|| iter.next())
+                        .ok_or("--renderer requires a value")?;
+                    let renderer = Renderer::parse(&value)
+                        .ok_or_else(|| format!("unknown renderer `{value}`"))?;
+                    args.renderer = Some(renderer);
+                }
+                "--format" => {
+                    let value = inline_value
+                        .or_else(|| iter.next())
+                        .ok_or("--format requires a value")?;
+                    let format = ExportFormat::parse(&value)
+                        .ok_or_else(|| format!("unknown format `{value}`"))?;
+                    args.format = Some(format);
+                }
+                "--quality" => {
+                    let value = inline_value
+                        .or_else(|| iter.next())
+                        .ok_or("--quality requires a value")?;
+                    let quality = value
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|q| (1..=100).contains(q))
+                        .ok_or_else(|| format!("invalid quality `{value}`, expected 1-100"))?;
+                    args.quality = Some(quality);
+                }
+                "--scale" => {
+                    let value = inline_value
+                        .or_else(|| iter.next())
+                        .ok_or("--scale requires a value")?;
+                    let scale = value
+                        .strip_suffix(['x', 'X'])
+                        .unwrap_or(&value)
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|s| SCALE_RANGE.contains(s))
+                        .ok_or_else(|| {
+                            format!(
+                                "invalid scale `{value}`, expected {}-{}",
+                                SCALE_RANGE.start(),
+                                SCALE_RANGE.end()
+                            )
+                        })?;
+                    args.scale = Some(scale);
+                }
+                _ if flag.starts_with('-') && flag.len() > 1 => {
+                    return Err(format!("unknown option `{flag}`"));
+                }
+                _ => args.paths.push(PathBuf::from(arg)),
+            }
+        }
+
+        if args.renderer.is_none()
+            && let Ok(value) = std::env::var("ANNOTATOR_RENDERER")
+        {
+            let renderer = Renderer::parse(&value)
+                .ok_or_else(|| format!("unknown renderer `{value}` in ANNOTATOR_RENDERER"))?;
+            args.renderer = Some(renderer);
+        }
+        Ok(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::try_parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn scale_accepts_factors_and_x_suffix() {
+        for (value, expected) in [("0.5", 0.5), ("2", 2.0), ("1.5x", 1.5), ("3X", 3.0)] {
+            assert_eq!(parse(&["--scale", value]).unwrap().scale, Some(expected), "{value}");
+        }
+        assert_eq!(parse(&["--scale=0.1"]).unwrap().scale, Some(0.1));
+        assert_eq!(parse(&["--scale=8x"]).unwrap().scale, Some(8.0));
+    }
+
+    #[test]
+    fn scale_rejects_invalid_values() {
+        for value in ["0", "0.05", "8.5", "-1", "2xx", "x", "abc", "NaN", "inf", ""] {
+            let err = parse(&["--scale", value]).unwrap_err();
+            assert!(err.starts_with("invalid scale"), "{value}: {err}");
+        }
+        assert_eq!(parse(&["--scale"]).unwrap_err(), "--scale requires a value");
+    }
+
+    #[test]
+    fn quality_range() {
+        assert_eq!(parse(&["--quality", "1"]).unwrap().quality, Some(1));
+        assert_eq!(parse(&["--quality=100"]).unwrap().quality, Some(100));
+        for value in ["0", "101", "256", "-5", "80.5", "high"] {
+            let err = parse(&["--quality", value]).unwrap_err();
+            assert!(err.starts_with("invalid quality"), "{value}: {err}");
+        }
+    }
+
+    #[test]
+    fn format_names() {
+        for (value, expected) in [
+            ("png", ExportFormat::Png),
+            ("JPG", ExportFormat::Jpeg),
+            ("jpeg", ExportFormat::Jpeg),
+            ("webp", ExportFormat::WebP),
+            ("bmp", ExportFormat::Bmp),
+            ("tif", ExportFormat::Tiff),
+            ("svg", ExportFormat::Svg),
+            ("pdf", ExportFormat::Pdf),
+        ] {
+            assert_eq!(parse(&["--format", value]).unwrap().format, Some(expected), "{value}");
+        }
+        assert_eq!(parse(&["--format", "gif"]).unwrap_err(), "unknown format `gif`");
+    }
+
+    #[test]
+    fn paths_and_unknown_options() {
+        let args = parse(&["a.png", "--format=png", "b.jpg"]).unwrap();
+        assert_eq!(args.paths, [PathBuf::from("a.png"), PathBuf::from("b.jpg")]);
+        assert_eq!(parse(&["--bogus"]).unwrap_err(), "unknown option `--bogus`");
+        assert_eq!(parse(&["--help"]).unwrap_err(), "");
+    }
+}
@@ -1,20 +1,71 @@
 use egui::{Color32, Response, StrokeKind, Ui, Vec2};
 
+use crate::{
+    file_dialog::FileDialog,
+    palette::{PALETTE_EXTENSIONS, Palette},
+    settings::HexColor,
+};
+
+/// 最近使用的颜色最多保留的数量
+const MAX_RECENT_COLORS: usize = 10;
+
+/// 预设颜色
+pub const PRESET_COLORS: &[Color32] = &[
+    Color32::BLACK,
+    Color32::WHITE,
+    Color32::from_rgb(255, 0, 0),     // 红
+    Color32::from_rgb(0, 255, 0),     // 绿
+    Color32::from_rgb(0, 0, 255),     // 蓝
+    Color32::from_rgb(255, 255, 0),   // 黄
+    Color32::from_rgb(255, 165, 0),   // 橙
+    Color32::from_rgb(128, 0, 128),   // 紫
+    Color32::from_rgb(255, 192, 203), // 粉
+    Color32::GRAY,
+    Color32::from_rgb(139, 69, 19), // 棕
+    Color32::from_rgb(0, 255, 255), // 青
+    Color32::from_rgb(255, 0, 255), // 洋红
+    Color32::LIGHT_GRAY,
+    Color32::DARK_GRAY,
+];
+
 /// 颜色选择器按钮组件
 #[derive(Default)]
 pub struct ColorPickerButton {
     /// 当前选中的颜色
     current_color: Color32,
-    /// 临时颜色（用户正在选择但未确认的颜色）
-    temp_color: Color32,
+    /// 临时颜色（用户正在选择但未确认的颜色），非预乘的 sRGBA
+    ///
+    /// 编辑时保持非预乘值，透明度调到 0 再调回来不会丢失 RGB，确认时才转换为 `Color32`
+    temp_rgba: [u8; 4],
     /// 是否显示选择器窗口
     show_picker: bool,
     /// 是否展开自定义颜色选择器
     show_custom: bool,
     /// 唯一ID（用于窗口）
     id: String,
+    /// 窗口标题
+    title: String,
     /// 刚刚打开标志（用于防止立即关闭）
     just_opened: bool,
+    /// 用户自定义调色板
+    custom_colors: Vec<Color32>,
+    /// 最近确认过的颜色，最新的在前
+    recent_colors: Vec<Color32>,
+    /// 命名调色板
+    palettes: Vec<Palette>,
+    /// 当前显示的命名调色板
+    active_palette: usize,
+    /// 新建调色板的名称输入
+    new_palette_name: String,
+    /// 导入、导出调色板的文件窗口
+    import_dialog: FileDialog,
+    export_dialog: FileDialog,
+    /// 导入、导出失败时的错误信息
+    palette_error: Option<String>,
+    /// 十六进制输入框内容
+    hex_input: String,
+    /// 上次编辑的 HSV 值及对应的 RGB，避免灰色时色相丢失
+    hsv_cache: Option<([u8; 3], [f32; 3])>,
 }
 
 impl ColorPickerButton {
@@ -22,19 +73,100 @@ impl ColorPickerButton {
     pub fn new(id: impl Into<String>, initial_color: Color32) -> Self {
         Self {
             current_color: initial_color,
-            temp_color: initial_color,
+            temp_rgba: initial_color.to_srgba_unmultiplied(),
             show_picker: false,
             show_custom: false,
             id: id.into(),
+            title: "Color Picker".to_owned(),
             just_opened: false,
+            custom_colors: Vec::new(),
+            recent_colors: Vec::new(),
+            palettes: Vec::new(),
+            active_palette: 0,
+            new_palette_name: String::new(),
+            import_dialog: FileDialog::new("Import Palette", PALETTE_EXTENSIONS),
+            export_dialog: FileDialog::new("Export Palette", PALETTE_EXTENSIONS).saving(),
+            palette_error: None,
+            hex_input: String::new(),
+            hsv_cache: None,
         }
     }
 
+    /// 设置窗口标题
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
     /// 获取当前颜色
     pub fn color(&self) -> Color32 {
         self.current_color
     }
 
+    /// 选择器窗口是否打开
+    pub fn is_open(&self) -> bool {
+        self.show_picker
+    }
+
+    /// 把自定义颜色、最近颜色和命名调色板同步给另一个选择器
+    pub fn share_library(&self, other: &mut ColorPickerButton) {
+        other.custom_colors.clone_from(&self.custom_colors);
+        other.recent_colors.clone_from(&self.recent_colors);
+        other.palettes.clone_from(&self.palettes);
+        other.active_palette = other.active_palette.min(self.palettes.len().saturating_sub(1));
+    }
+
+    /// 自定义调色板
+    pub fn custom_colors(&self) -> &[Color32] {
+        &self.custom_colors
+    }
+
+    /// 设置自定义调色板（如从设置文件恢复）
+    pub fn set_custom_colors(&mut self, colors: Vec<Color32>) {
+        self.custom_colors = colors;
+    }
+
+    /// 最近使用的颜色
+    pub fn recent_colors(&self) -> &[Color32] {
+        &self.recent_colors
+    }
+
+    /// 设置最近使用的颜色（如从设置文件恢复）
+    pub fn set_recent_colors(&mut self, colors: Vec<Color32>) {
+        self.recent_colors = colors;
+        self.recent_colors.truncate(MAX_RECENT_COLORS);
+    }
+
+    /// 命名调色板
+    pub fn palettes(&self) -> &[Palette] {
+        &self.palettes
+    }
+
+    /// 设置命名调色板（如从设置文件恢复）
+    pub fn set_palettes(&mut self, palettes: Vec<Palette>) {
+        self.palettes = palettes;
+        self.active_palette = 0;
+    }
+
+    /// 记录最近使用的颜色
+    fn push_recent(&mut self, color: Color32) {
+        self.recent_colors.retain(|&c| c != color);
+        self.recent_colors.insert(0, color);
+        self.recent_colors.truncate(MAX_RECENT_COLORS);
+    }
+
+    /// 设置当前颜色（如通过快捷键切换）
+    pub fn set_color(&mut self, color: Color32) {
+        self.current_color = color;
+        self.temp_rgba = color.to_srgba_unmultiplied();
+    }
+
+    /// 正在选择的颜色
+    fn temp_color(&self) -> Color32 {
+        let [r, g, b, a] = self.temp_rgba;
+        Color32::from_rgba_unmultiplied(r, g, b, a)
+    }
+
     /// 显示颜色选择器按钮
     /// 返回：(Response, Option<Color32>)
     /// - Response: 按钮的响应
@@ -45,7 +177,7 @@ impl ColorPickerButton {
         if button_response.clicked() {
             self.show_picker = true;
             self.just_opened = true;
-            self.temp_color = self.current_color;
+            self.temp_rgba = self.current_color.to_srgba_unmultiplied();
         }
 
         let mut selected_color = None;
@@ -53,7 +185,7 @@ impl ColorPickerButton {
         if self.show_picker {
             let window_id = egui::Id::new(&self.id);
 
-            let window_response = egui::Window::new("Color Picker")
+            let window_response = egui::Window::new(self.title.clone())
                 .id(window_id)
                 .collapsible(false)
                 .resizable(false)
@@ -65,6 +197,40 @@ impl ColorPickerButton {
 
                     self.draw_preset_colors(ui);
 
+                    // 最近使用的颜色
+                    if !self.recent_colors.is_empty() {
+                        ui.add_space(10.0);
+                        ui.label("Recent");
+                        ui.horizontal_wrapped(|ui| {
+                            ui.spacing_mut().item_spacing = egui::vec2(2.0, 2.0);
+                            for color in self.recent_colors.clone() {
+                                if self.draw_small_tile(ui, color).clicked() {
+                                    self.temp_rgba = color.to_srgba_unmultiplied();
+                                }
+                            }
+                        });
+                    }
+
+                    ui.add_space(10.0);
+                    ui.separator();
+                    ui.add_space(5.0);
+
+                    // 自定义调色板
+                    ui.heading("My Colors");
+                    ui.add_space(5.0);
+
+                    self.draw_custom_colors(ui);
+
+                    ui.add_space(10.0);
+                    ui.separator();
+                    ui.add_space(5.0);
+
+                    // 命名调色板
+                    ui.heading("Palettes");
+                    ui.add_space(5.0);
+
+                    self.draw_palettes(ui);
+
                     ui.add_space(10.0);
                     ui.separator();
                     ui.add_space(5.0);
@@ -82,9 +248,18 @@ impl ColorPickerButton {
 
                     if self.show_custom {
                         ui.add_space(5.0);
-                        ui.color_edit_button_srgba(&mut self.temp_color);
+                        ui.color_edit_button_srgba_unmultiplied(&mut self.temp_rgba);
+                        ui.add_space(5.0);
+                        self.draw_value_fields(ui);
                     }
 
+                    // 不透明度
+                    ui.add_space(5.0);
+                    ui.horizontal(|ui| {
+                        ui.label("Alpha:");
+                        alpha_slider(ui, &mut self.temp_rgba);
+                    });
+
                     ui.add_space(10.0);
                     ui.separator();
                     ui.add_space(5.0);
@@ -95,7 +270,7 @@ impl ColorPickerButton {
 
                     ui.horizontal(|ui| {
                         ui.label("Current:");
-                        self.draw_color_preview(ui, self.temp_color);
+                        self.draw_color_preview(ui, self.temp_rgba);
 
                         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                             if ui.button("Confirm").clicked() {
@@ -116,22 +291,26 @@ impl ColorPickerButton {
             if let Some(inner_response) = window_response {
                 if let Some((close_window, confirm)) = inner_response.inner {
                     if confirm {
-                        self.current_color = self.temp_color;
+                        self.current_color = self.temp_color();
+                        self.push_recent(self.current_color);
                         selected_color = Some(self.current_color);
                     }
                     if close_window {
                         self.show_picker = false;
                         if !confirm {
-                            self.temp_color = self.current_color;
+                            self.temp_rgba = self.current_color.to_srgba_unmultiplied();
                         }
                     }
                 }
 
                 // 检测窗口是否被关闭（点击外部区域）
-                // 但忽略刚打开时的点击
-                if inner_response.response.clicked_elsewhere() && !self.just_opened {
+                // 但忽略刚打开时的点击，以及在弹出菜单、导入导出窗口中的点击
+                let dialog_open = self.import_dialog.is_visible()
+                    || self.export_dialog.is_visible()
+                    || egui::Popup::is_any_open(ui.ctx());
+                if inner_response.response.clicked_elsewhere() && !self.just_opened && !dialog_open {
                     self.show_picker = false;
-                    self.temp_color = self.current_color;
+                    self.temp_rgba = self.current_color.to_srgba_unmultiplied();
                 }
             }
 
@@ -141,6 +320,8 @@ impl ColorPickerButton {
             }
         }
 
+        self.palette_dialogs(ui.ctx());
+
         (button_response, selected_color)
     }
 
@@ -165,6 +346,7 @@ impl ColorPickerButton {
             let color_rect =
                 egui::Rect::from_min_size(rect.min + egui::vec2(5.0, 5.0), Vec2::new(20.0, 20.0));
 
+            paint_checkerboard(ui.painter(), color_rect);
             ui.painter()
                 .rect_filled(color_rect, 2.0, self.current_color);
             ui.painter().rect_stroke(
@@ -193,30 +375,12 @@ impl ColorPickerButton {
 
     /// 绘制预设颜色网格
     fn draw_preset_colors(&mut self, ui: &mut Ui) {
-        const PRESET_COLORS: &[Color32] = &[
-            Color32::BLACK,
-            Color32::WHITE,
-            Color32::from_rgb(255, 0, 0),     // 红
-            Color32::from_rgb(0, 255, 0),     // 绿
-            Color32::from_rgb(0, 0, 255),     // 蓝
-            Color32::from_rgb(255, 255, 0),   // 黄
-            Color32::from_rgb(255, 165, 0),   // 橙
-            Color32::from_rgb(128, 0, 128),   // 紫
-            Color32::from_rgb(255, 192, 203), // 粉
-            Color32::GRAY,
-            Color32::from_rgb(139, 69, 19), // 棕
-            Color32::from_rgb(0, 255, 255), // 青
-            Color32::from_rgb(255, 0, 255), // 洋红
-            Color32::LIGHT_GRAY,
-            Color32::DARK_GRAY,
-        ];
-
         egui::Grid::new(format!("{}_preset_grid", self.id))
             .spacing([5.0, 5.0])
             .show(ui, |ui| {
                 for (i, &color) in PRESET_COLORS.iter().enumerate() {
                     if self.draw_color_tile(ui, color) {
-                        self.temp_color = color;
+                        self.temp_rgba = color.to_srgba_unmultiplied();
                     }
 
                     if (i + 1) % 5 == 0 {
@@ -226,8 +390,166 @@ impl ColorPickerButton {
             });
     }
 
+    /// 绘制自定义调色板，右键删除颜色
+    fn draw_custom_colors(&mut self, ui: &mut Ui) {
+        let mut remove = None;
+        egui::Grid::new(format!("{}_custom_grid", self.id))
+            .spacing([5.0, 5.0])
+            .show(ui, |ui| {
+                for (i, &color) in self.custom_colors.iter().enumerate() {
+                    let (clicked, secondary_clicked) = self.draw_color_tile_response(ui, color);
+                    if clicked {
+                        self.temp_rgba = color.to_srgba_unmultiplied();
+                    }
+                    if secondary_clicked {
+                        remove = Some(i);
+                    }
+
+                    if (i + 1) % 5 == 0 {
+                        ui.end_row();
+                    }
+                }
+
+                if ui
+                    .add_sized([40.0, 40.0], egui::Button::new("+"))
+                    .on_hover_text("Add current color")
+                    .clicked()
+                    && !self.custom_colors.contains(&self.temp_color())
+                {
+                    self.custom_colors.push(self.temp_color());
+                }
+            });
+
+        if let Some(i) = remove {
+            self.custom_colors.remove(i);
+        }
+    }
+
+    /// 绘制命名调色板：选择、编辑、新建、删除、导入和导出
+    fn draw_palettes(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let selected = self
+                .palettes
+                .get(self.active_palette)
+                .map_or("(none)", |p| p.name.as_str());
+            egui::ComboBox::from_id_salt(format!("{}_palettes", self.id))
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for (i, palette) in self.palettes.iter().enumerate() {
+                        ui.selectable_value(&mut self.active_palette, i, &palette.name);
+                    }
+                });
+            if ui
+                .add_enabled(self.active_palette < self.palettes.len(), egui::Button::new("🗑"))
+                .on_hover_text("Delete palette")
+                .clicked()
+            {
+                self.palettes.remove(self.active_palette);
+                self.active_palette = self.active_palette.saturating_sub(1);
+            }
+        });
+
+        if let Some(palette) = self.palettes.get(self.active_palette) {
+            let mut remove = None;
+            let colors: Vec<Color32> = palette.colors().collect();
+            ui.horizontal_wrapped(|ui| {
+                ui.spacing_mut().item_spacing = egui::vec2(2.0, 2.0);
+                for (i, &color) in colors.iter().enumerate() {
+                    let response = self.draw_small_tile(ui, color);
+                    if response.clicked() {
+                        self.temp_rgba = color.to_srgba_unmultiplied();
+                    }
+                    if response.secondary_clicked() {
+                        remove = Some(i);
+                    }
+                }
+                if ui
+                    .add_sized([20.0, 20.0], egui::Button::new("+"))
+                    .on_hover_text("Add current color")
+                    .clicked()
+                    && !colors.contains(&self.temp_color())
+                {
+                    let color = self.temp_color();
+                    self.palettes[self.active_palette].colors.push(HexColor(color));
+                }
+            });
+            if let Some(i) = remove {
+                self.palettes[self.active_palette].colors.remove(i);
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.new_palette_name)
+                    .hint_text("New palette name")
+                    .desired_width(140.0),
+            );
+            let name = self.new_palette_name.trim().to_owned();
+            if ui.add_enabled(!name.is_empty(), egui::Button::new("New")).clicked() {
+                self.palettes.push(Palette::new(name));
+                self.active_palette = self.palettes.len() - 1;
+                self.new_palette_name.clear();
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Import...").on_hover_text("GIMP .gpl or hex list").clicked() {
+                self.palette_error = None;
+                self.import_dialog.open();
+            }
+            if let Some(palette) = self.palettes.get(self.active_palette)
+                && ui.button("Export...").clicked()
+            {
+                self.palette_error = None;
+                self.export_dialog.set_file_name(format!("{}.gpl", palette.name));
+                self.export_dialog.open();
+            }
+        });
+        if let Some(error) = &self.palette_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+    }
+
+    /// 显示导入、导出调色板的文件窗口
+    fn palette_dialogs(&mut self, ctx: &egui::Context) {
+        if let Some(path) = self.import_dialog.show(ctx) {
+            match Palette::load(&path) {
+                Ok(palette) => {
+                    self.palettes.push(palette);
+                    self.active_palette = self.palettes.len() - 1;
+                }
+                Err(err) => self.palette_error = Some(format!("{}: {err}", path.display())),
+            }
+        }
+        if let Some(path) = self.export_dialog.show(ctx)
+            && let Some(palette) = self.palettes.get(self.active_palette)
+            && let Err(err) = palette.save(&path)
+        {
+            self.palette_error = Some(format!("{}: {err}", path.display()));
+        }
+    }
+
+    /// 绘制小号颜色方块（最近使用、命名调色板）
+    fn draw_small_tile(&self, ui: &mut Ui, color: Color32) -> Response {
+        let (rect, response) = ui.allocate_exact_size(Vec2::splat(20.0), egui::Sense::click());
+        if ui.is_rect_visible(rect) {
+            ui.painter().rect_filled(rect, 2.0, color);
+            let stroke = if self.temp_color() == color {
+                egui::Stroke::new(2.0, Color32::from_rgb(100, 150, 255))
+            } else {
+                egui::Stroke::new(1.0, Color32::GRAY)
+            };
+            ui.painter().rect_stroke(rect, 2.0, stroke, StrokeKind::Middle);
+        }
+        response.on_hover_text(color_to_hex(color))
+    }
+
     /// 绘制单个颜色方块
     fn draw_color_tile(&self, ui: &mut Ui, color: Color32) -> bool {
+        self.draw_color_tile_response(ui, color).0
+    }
+
+    /// 绘制单个颜色方块，返回 (左键点击, 右键点击)
+    fn draw_color_tile_response(&self, ui: &mut Ui, color: Color32) -> (bool, bool) {
         let size = Vec2::new(40.0, 40.0);
         let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
 
@@ -238,7 +560,7 @@ impl ColorPickerButton {
             ui.painter().rect_filled(inner_rect, 2.0, color);
 
             // 绘制边框（选中状态）
-            let is_selected = self.temp_color == color;
+            let is_selected = self.temp_color() == color;
             let stroke = if is_selected {
                 egui::Stroke::new(3.0, Color32::from_rgb(100, 150, 255))
             } else if response.hovered() {
@@ -251,14 +573,97 @@ impl ColorPickerButton {
                 .rect_stroke(inner_rect, 2.0, stroke, StrokeKind::Middle);
         }
 
-        response.clicked()
+        (response.clicked(), response.secondary_clicked())
+    }
+
+    /// 十六进制、RGB 和 HSV 输入
+    fn draw_value_fields(&mut self, ui: &mut Ui) {
+        egui::Grid::new(format!("{}_values", self.id))
+            .num_columns(2)
+            .spacing([8.0, 4.0])
+            .show(ui, |ui| {
+                // 十六进制，输入合法时立即生效
+                ui.label("Hex");
+                let id = ui.make_persistent_id("hex_input");
+                if !ui.memory(|m| m.has_focus(id)) {
+                    self.hex_input = rgba_to_hex(self.temp_rgba);
+                }
+                let parsed = hex_to_rgba(&self.hex_input);
+                let text_color = if parsed.is_some() {
+                    ui.visuals().text_color()
+                } else {
+                    ui.visuals().error_fg_color
+                };
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.hex_input)
+                        .id(id)
+                        .font(egui::TextStyle::Monospace)
+                        .text_color(text_color)
+                        .desired_width(90.0),
+                );
+                if response.changed()
+                    && let Some(rgba) = parsed
+                {
+                    self.temp_rgba = rgba;
+                }
+                if parsed.is_none() {
+                    response.on_hover_text("Expected #RRGGBB or #RRGGBBAA");
+                }
+                ui.end_row();
+
+                // RGB
+                ui.label("RGB");
+                let [mut r, mut g, mut b, a] = self.temp_rgba;
+                let changed = ui
+                    .horizontal(|ui| {
+                        ui.add(egui::DragValue::new(&mut r).prefix("R ")).changed()
+                            | ui.add(egui::DragValue::new(&mut g).prefix("G ")).changed()
+                            | ui.add(egui::DragValue::new(&mut b).prefix("B ")).changed()
+                    })
+                    .inner;
+                if changed {
+                    self.temp_rgba = [r, g, b, a];
+                }
+                ui.end_row();
+
+                // HSV
+                ui.label("HSV");
+                let mut hsv = match self.hsv_cache {
+                    Some((rgb, hsv)) if rgb == [r, g, b] => hsv,
+                    _ => rgb_to_hsv([r, g, b]),
+                };
+                let changed = ui
+                    .horizontal(|ui| {
+                        let fields = [("H ", "°", 360.0), ("S ", "%", 100.0), ("V ", "%", 100.0)];
+                        let mut changed = false;
+                        for (value, (prefix, suffix, max)) in hsv.iter_mut().zip(fields) {
+                            let drag = egui::DragValue::new(value)
+                                .range(0.0..=max)
+                                .max_decimals(0)
+                                .prefix(prefix)
+                                .suffix(suffix);
+                            changed |= ui.add(drag).changed();
+                        }
+                        changed
+                    })
+                    .inner;
+                if changed {
+                    let [r, g, b] = hsv_to_rgb(hsv);
+                    self.temp_rgba = [r, g, b, a];
+                    self.hsv_cache = Some(([r, g, b], hsv));
+                }
+                ui.end_row();
+            });
     }
 
     /// 绘制颜色预览
-    fn draw_color_preview(&self, ui: &mut Ui, color: Color32) {
+    fn draw_color_preview(&self, ui: &mut Ui, rgba: [u8; 4]) {
+        let [r, g, b, a] = rgba;
+        let color = Color32::from_rgba_unmultiplied(r, g, b, a);
         let size = Vec2::new(60.0, 20.0);
         let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
 
+        paint_checkerboard(ui.painter(), rect);
         ui.painter().rect_filled(rect, 2.0, color);
         ui.painter().rect_stroke(
             rect,
@@ -267,15 +672,176 @@ impl ColorPickerButton {
             StrokeKind::Middle,
         );
 
-        let (r, g, b, _) = color.to_tuple();
-        ui.label(format!("({},{},{})", r, g, b));
+        ui.label(format!("({r},{g},{b},{a})"));
     }
 }
 
-impl ColorPickerButton {
-    /// 简化版本：只显示按钮，返回是否有颜色被选中
-    pub fn ui(&mut self, ui: &mut Ui) -> bool {
-        let (_, changed) = self.show(ui);
-        changed.is_some()
+/// 不透明度滑块：棋盘格背景上从透明到不透明的渐变
+fn alpha_slider(ui: &mut Ui, rgba: &mut [u8; 4]) -> Response {
+    let [r, g, b, a] = *rgba;
+    let (rect, mut response) = ui.allocate_exact_size(Vec2::new(160.0, 18.0), egui::Sense::click_and_drag());
+
+    if let Some(pos) = response.interact_pointer_pos() {
+        let t = ((pos.x - rect.left()) / rect.width()).clamp(0.0, 1.0);
+        let new_alpha = (t * 255.0).round() as u8;
+        if new_alpha != a {
+            rgba[3] = new_alpha;
+            response.mark_changed();
+        }
+    }
+
+    if ui.is_rect_visible(rect) {
+        let painter = ui.painter();
+        paint_checkerboard(painter, rect);
+
+        // 左右两端颜色不同的矩形网格，绘制渐变
+        let mut mesh = egui::Mesh::default();
+        let transparent = Color32::from_rgba_unmultiplied(r, g, b, 0);
+        let opaque = Color32::from_rgb(r, g, b);
+        mesh.colored_vertex(rect.left_top(), transparent);
+        mesh.colored_vertex(rect.right_top(), opaque);
+        mesh.colored_vertex(rect.right_bottom(), opaque);
+        mesh.colored_vertex(rect.left_bottom(), transparent);
+        mesh.add_triangle(0, 1, 2);
+        mesh.add_triangle(0, 2, 3);
+        painter.add(mesh);
+        painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, Color32::GRAY), StrokeKind::Middle);
+
+        // 当前值
+        let x = rect.left() + rect.width() * rgba[3] as f32 / 255.0;
+        let marker = egui::Rect::from_center_size(egui::pos2(x, rect.center().y), Vec2::new(4.0, rect.height() + 4.0));
+        painter.rect(marker, 1.0, Color32::WHITE, egui::Stroke::new(1.0, Color32::BLACK), StrokeKind::Middle);
+    }
+
+    if ui.add(egui::DragValue::new(&mut rgba[3])).changed() {
+        response.mark_changed();
+    }
+    response
+}
+
+/// 绘制棋盘格，用于显示半透明颜色
+fn paint_checkerboard(painter: &egui::Painter, rect: egui::Rect) {
+    const CELL: f32 = 5.0;
+    painter.rect_filled(rect, 0.0, Color32::WHITE);
+    let cols = (rect.width() / CELL).ceil() as usize;
+    let rows = (rect.height() / CELL).ceil() as usize;
+    for row in 0..rows {
+        for col in (row % 2..cols).step_by(2) {
+            let min = rect.min + Vec2::new(col as f32, row as f32) * CELL;
+            let cell = egui::Rect::from_min_size(min, Vec2::splat(CELL)).intersect(rect);
+            painter.rect_filled(cell, 0.0, Color32::LIGHT_GRAY);
+        }
+    }
+}
+
+/// sRGB 转 HSV，色相为角度，饱和度和明度为百分比
+fn rgb_to_hsv([r, g, b]: [u8; 3]) -> [f32; 3] {
+    let [r, g, b] = [r, g, b].map(|c| c as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    [hue, saturation * 100.0, max * 100.0]
+}
+
+/// HSV 转 sRGB，参数含义同 [`rgb_to_hsv`]
+fn hsv_to_rgb([h, s, v]: [f32; 3]) -> [u8; 3] {
+    let (s, v) = (s / 100.0, v / 100.0);
+    let c = v * s;
+    let h = (h.rem_euclid(360.0)) / 60.0;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    [r, g, b].map(|channel| ((channel + m) * 255.0).round() as u8)
+}
+
+/// 颜色转换为 `#RRGGBBAA`
+pub fn color_to_hex(color: Color32) -> String {
+    rgba_to_hex(color.to_srgba_unmultiplied())
+}
+
+/// 非预乘的 sRGBA 转换为 `#RRGGBBAA`
+fn rgba_to_hex([r, g, b, a]: [u8; 4]) -> String {
+    format!("#{r:02X}{g:02X}{b:02X}{a:02X}")
+}
+
+/// 解析 `#RRGGBB` 或 `#RRGGBBAA`（`#` 可省略）
+pub fn hex_to_color(text: &str) -> Option<Color32> {
+    let [r, g, b, a] = hex_to_rgba(text)?;
+    Some(Color32::from_rgba_unmultiplied(r, g, b, a))
+}
+
+/// 解析为非预乘的 sRGBA，格式同 [`hex_to_color`]
+fn hex_to_rgba(text: &str) -> Option<[u8; 4]> {
+    let hex = text.trim().trim_start_matches('#');
+    // from_str_radix 接受正负号，逐字符检查
+    if !matches!(hex.len(), 6 | 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let a = if hex.len() == 8 { channel(6)? } else { 255 };
+    Some([channel(0)?, channel(2)?, channel(4)?, a])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_round_trip() {
+        for rgba in [[0, 0, 0, 0], [255, 255, 255, 255], [0x12, 0xAB, 0x7F, 0x80], [1, 2, 3, 254]] {
+            assert_eq!(hex_to_rgba(&rgba_to_hex(rgba)), Some(rgba));
+        }
+        assert_eq!(hex_to_rgba("#ff8000"), Some([255, 128, 0, 255]));
+        assert_eq!(hex_to_rgba("  FF800040 "), Some([255, 128, 0, 64]));
+        assert_eq!(hex_to_color("#FF000080"), Some(Color32::from_rgba_unmultiplied(255, 0, 0, 128)));
+    }
+
+    #[test]
+    fn hex_rejects_malformed_input() {
+        for text in ["", "#", "#FFF", "#FFFFF", "#FFFFFFF", "#FFFFFFFFF", "#GG0000", "#+12345", "#-1FFFF", "#ÿÿÿ", "##FFFFFF0"] {
+            assert_eq!(hex_to_rgba(text), None, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn hsv_known_values() {
+        assert_eq!(rgb_to_hsv([255, 0, 0]), [0.0, 100.0, 100.0]);
+        assert_eq!(rgb_to_hsv([0, 255, 0]), [120.0, 100.0, 100.0]);
+        assert_eq!(rgb_to_hsv([0, 0, 255]), [240.0, 100.0, 100.0]);
+        assert_eq!(rgb_to_hsv([0, 0, 0]), [0.0, 0.0, 0.0]);
+        assert_eq!(rgb_to_hsv([255, 255, 255]), [0.0, 0.0, 100.0]);
+        assert_eq!(hsv_to_rgb([60.0, 100.0, 100.0]), [255, 255, 0]);
+        // 色相超出范围时取模
+        assert_eq!(hsv_to_rgb([360.0, 100.0, 100.0]), [255, 0, 0]);
+        assert_eq!(hsv_to_rgb([-120.0, 100.0, 100.0]), [0, 0, 255]);
+    }
+
+    #[test]
+    fn hsv_round_trip() {
+        for r in (0..=255).step_by(15) {
+            for g in (0..=255).step_by(17) {
+                for b in (0..=255).step_by(51) {
+                    let rgb = [r as u8, g as u8, b as u8];
+                    assert_eq!(hsv_to_rgb(rgb_to_hsv(rgb)), rgb);
+                }
+            }
+        }
     }
 }
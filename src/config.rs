@@ -0,0 +1,11 @@
+use std::path::PathBuf;
+
+/// 配置目录：`$XDG_CONFIG_HOME/image-annotator`
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("image-annotator"))
+}
+
+/// 配置目录下的文件路径
+pub fn config_file(name: &str) -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(name))
+}
@@ -0,0 +1,504 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use egui::{Pos2, Rect};
+use image::GrayImage;
+use serde::Serialize;
+use tiny_skia::{FillRule, Paint, Pixmap, Transform};
+
+use crate::{
+    document::Document,
+    drawable::polygon_path,
+    operators::{Operator, ToolType},
+};
+
+/// 没有设置标签的标注导出时使用的类别
+pub const DEFAULT_LABEL: &str = "object";
+
+/// 数据集标注格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DatasetFormat {
+    /// 所有图片写入一个 JSON
+    #[default]
+    Coco,
+    /// 每张图片一个 XML，有多边形时另有 `<原文件名>_mask.png` 类别掩码
+    Voc,
+    /// 每张图片一个 txt，另有 classes.txt 列出类别；多边形按 YOLO 分割格式写出顶点
+    Yolo,
+}
+
+impl DatasetFormat {
+    pub const ALL: [DatasetFormat; 3] = [DatasetFormat::Coco, DatasetFormat::Voc, DatasetFormat::Yolo];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DatasetFormat::Coco => "COCO JSON",
+            DatasetFormat::Voc => "Pascal VOC XML",
+            DatasetFormat::Yolo => "YOLO txt",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DatasetFormat::Coco => "json",
+            DatasetFormat::Voc => "xml",
+            DatasetFormat::Yolo => "txt",
+        }
+    }
+
+    /// 是否每张图片单独一个文件
+    pub fn per_image(&self) -> bool {
+        !matches!(self, DatasetFormat::Coco)
+    }
+}
+
+/// 一个导出的目标：类别名、图片坐标中的外接框，多边形另有分割轮廓
+struct Object<'a> {
+    label: &'a str,
+    bbox: Rect,
+    polygon: Option<Vec<Pos2>>,
+}
+
+/// 可导出的标注：矩形，椭圆取外接矩形，多边形的顶点限制在图片内；超出图片的部分被裁掉
+fn shape(op: &Operator, bounds: Rect) -> Option<(Rect, Option<Vec<Pos2>>)> {
+    let (rect, polygon) = match &op.tool {
+        ToolType::Rect(rect) => (*rect, None),
+        ToolType::Ellipse(ellipse) => (Rect::from_center_size(ellipse.center, ellipse.radius * 2.0), None),
+        ToolType::Polygon(points) => {
+            let points: Vec<Pos2> = points.iter().map(|p| p.clamp(bounds.min, bounds.max)).collect();
+            (Rect::from_points(&points), Some(points))
+        }
+        _ => return None,
+    };
+    let rect = rect.intersect(bounds);
+    rect.is_positive().then_some((rect, polygon))
+}
+
+fn objects(doc: &Document) -> Vec<Object<'_>> {
+    let (width, height) = doc.original_image.dimensions();
+    let bounds = Rect::from_min_max(Pos2::ZERO, Pos2::new(width as f32, height as f32));
+    doc.operators
+        .iter()
+        .filter_map(|op| {
+            let (bbox, polygon) = shape(op, bounds)?;
+            Some(Object {
+                label: op.label.as_deref().map(str::trim).filter(|l| !l.is_empty()).unwrap_or(DEFAULT_LABEL),
+                bbox,
+                polygon,
+            })
+        })
+        .collect()
+}
+
+/// 数据集的类别：类别文件中的类别在前，其余按在标签页中首次出现的顺序
+pub fn class_names(docs: &[&Document], labels: &[String]) -> Vec<String> {
+    let mut names = labels.to_vec();
+    for doc in docs {
+        for object in objects(doc) {
+            if !names.iter().any(|n| n == object.label) {
+                names.push(object.label.to_owned());
+            }
+        }
+    }
+    names
+}
+
+/// 标注文件中记录的图片文件名
+fn image_file_name(doc: &Document) -> String {
+    doc.path
+        .as_deref()
+        .and_then(Path::file_name)
+        .map_or_else(|| format!("{}.png", doc.title), |name| name.to_string_lossy().into_owned())
+}
+
+/// 每张图片单独保存时的文件名：`<原文件名>.<扩展名>`
+pub fn annotation_file_name(doc: &Document, format: DatasetFormat) -> String {
+    format!("{}.{}", annotation_stem(doc), format.extension())
+}
+
+fn annotation_stem(doc: &Document) -> String {
+    doc.path
+        .as_deref()
+        .and_then(Path::file_stem)
+        .map_or_else(|| doc.title.clone(), |s| s.to_string_lossy().into_owned())
+}
+
+/// 导出数据集标注，返回写入的文件
+///
+/// COCO 把所有标签页写入 `path`；VOC 和 YOLO 当前标签页写入 `path`，
+/// 其余标签页按原文件名写在同一目录，YOLO 另写 `classes.txt`；
+/// 不同标签页的原文件名相同时依次加上序号 `-2`、`-3`……，同一次导出的文件不互相覆盖
+pub fn export(
+    docs: &[&Document],
+    labels: &[String],
+    active: usize,
+    format: DatasetFormat,
+    path: &Path,
+) -> Result<Vec<PathBuf>, String> {
+    let classes = class_names(docs, labels);
+    let write = |path: PathBuf, data: String| fs::write(&path, data).map(|_| path).map_err(|e| e.to_string());
+
+    if !format.per_image() {
+        return Ok(vec![write(path.to_path_buf(), coco(docs, &classes)?)?]);
+    }
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let classes_file = dir.join("classes.txt");
+    let mut used = HashSet::from([path.to_path_buf()]);
+    if format == DatasetFormat::Yolo {
+        used.insert(classes_file.clone());
+    }
+    let mut written = Vec::new();
+    for (i, doc) in docs.iter().enumerate() {
+        let file = if i == active {
+            path.to_path_buf()
+        } else {
+            let stem = annotation_stem(doc);
+            let mut file = dir.join(format!("{stem}.{}", format.extension()));
+            let mut n = 1;
+            while !used.insert(file.clone()) {
+                n += 1;
+                file = dir.join(format!("{stem}-{n}.{}", format.extension()));
+            }
+            file
+        };
+        let data = match format {
+            DatasetFormat::Voc => voc(doc),
+            _ => yolo(doc, &classes),
+        };
+        if format == DatasetFormat::Voc
+            && let Some(mask) = class_mask(doc, &classes)
+        {
+            let stem = file.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+            let mask_file = file.with_file_name(format!("{stem}_mask.png"));
+            mask.save(&mask_file).map_err(|e| e.to_string())?;
+            written.push(mask_file);
+        }
+        written.push(write(file, data)?);
+    }
+    if format == DatasetFormat::Yolo {
+        written.push(write(classes_file, classes.join("\n") + "\n")?);
+    }
+    Ok(written)
+}
+
+#[derive(Serialize)]
+struct CocoDataset {
+    images: Vec<CocoImage>,
+    annotations: Vec<CocoAnnotation>,
+    categories: Vec<CocoCategory>,
+}
+
+#[derive(Serialize)]
+struct CocoImage {
+    id: usize,
+    file_name: String,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Serialize)]
+struct CocoAnnotation {
+    id: usize,
+    image_id: usize,
+    category_id: usize,
+    /// [x, y, 宽, 高]
+    bbox: [f32; 4],
+    area: f32,
+    iscrowd: u8,
+    segmentation: Vec<Vec<f32>>,
+}
+
+#[derive(Serialize)]
+struct CocoCategory {
+    id: usize,
+    name: String,
+    supercategory: String,
+}
+
+/// COCO 的 id 从 1 开始
+fn coco(docs: &[&Document], classes: &[String]) -> Result<String, String> {
+    let mut dataset = CocoDataset {
+        images: Vec::new(),
+        annotations: Vec::new(),
+        categories: classes
+            .iter()
+            .enumerate()
+            .map(|(i, name)| CocoCategory {
+                id: i + 1,
+                name: name.clone(),
+                supercategory: "none".to_owned(),
+            })
+            .collect(),
+    };
+    for (i, doc) in docs.iter().enumerate() {
+        let (width, height) = doc.original_image.dimensions();
+        dataset.images.push(CocoImage {
+            id: i + 1,
+            file_name: image_file_name(doc),
+            width,
+            height,
+        });
+        for object in objects(doc) {
+            let bbox = object.bbox;
+            let (segmentation, area) = match &object.polygon {
+                Some(points) => (vec![points.iter().flat_map(|p| [p.x, p.y]).collect()], polygon_area(points)),
+                None => (Vec::new(), bbox.area()),
+            };
+            dataset.annotations.push(CocoAnnotation {
+                id: dataset.annotations.len() + 1,
+                image_id: i + 1,
+                category_id: class_index(classes, object.label) + 1,
+                bbox: [bbox.left(), bbox.top(), bbox.width(), bbox.height()],
+                area,
+                iscrowd: 0,
+                segmentation,
+            });
+        }
+    }
+    serde_json::to_string_pretty(&dataset).map_err(|e| e.to_string())
+}
+
+/// Pascal VOC：坐标取整数像素
+fn voc(doc: &Document) -> String {
+    let (width, height) = doc.original_image.dimensions();
+    let mut xml = String::new();
+    let _ = writeln!(xml, "<annotation>");
+    let _ = writeln!(xml, "  <filename>{}</filename>", escape(&image_file_name(doc)));
+    if let Some(path) = &doc.path {
+        let _ = writeln!(xml, "  <path>{}</path>", escape(&path.to_string_lossy()));
+    }
+    let _ = writeln!(xml, "  <source>\n    <database>Unknown</database>\n  </source>");
+    let _ = writeln!(
+        xml,
+        "  <size>\n    <width>{width}</width>\n    <height>{height}</height>\n    <depth>3</depth>\n  </size>"
+    );
+    let objects = objects(doc);
+    let segmented = objects.iter().any(|o| o.polygon.is_some());
+    let _ = writeln!(xml, "  <segmented>{}</segmented>", u8::from(segmented));
+    for object in objects {
+        let bbox = object.bbox;
+        let _ = writeln!(xml, "  <object>");
+        let _ = writeln!(xml, "    <name>{}</name>", escape(object.label));
+        let _ = writeln!(xml, "    <pose>Unspecified</pose>\n    <truncated>0</truncated>\n    <difficult>0</difficult>");
+        let _ = writeln!(
+            xml,
+            "    <bndbox>\n      <xmin>{}</xmin>\n      <ymin>{}</ymin>\n      <xmax>{}</xmax>\n      <ymax>{}</ymax>\n    </bndbox>",
+            bbox.left().round(),
+            bbox.top().round(),
+            bbox.right().round(),
+            bbox.bottom().round(),
+        );
+        let _ = writeln!(xml, "  </object>");
+    }
+    let _ = writeln!(xml, "</annotation>");
+    xml
+}
+
+/// YOLO：每行 `类别序号 中心x 中心y 宽 高`，多边形为 `类别序号 x1 y1 x2 y2 ...`，坐标按图片尺寸归一化
+fn yolo(doc: &Document, classes: &[String]) -> String {
+    let (width, height) = doc.original_image.dimensions();
+    let (width, height) = (width as f32, height as f32);
+    let mut txt = String::new();
+    for object in objects(doc) {
+        if let Some(points) = &object.polygon {
+            let _ = write!(txt, "{}", class_index(classes, object.label));
+            for p in points {
+                let _ = write!(txt, " {:.6} {:.6}", p.x / width, p.y / height);
+            }
+            txt.push('\n');
+            continue;
+        }
+        let bbox = object.bbox;
+        let center = bbox.center();
+        let _ = writeln!(
+            txt,
+            "{} {:.6} {:.6} {:.6} {:.6}",
+            class_index(classes, object.label),
+            center.x / width,
+            center.y / height,
+            bbox.width() / width,
+            bbox.height() / height,
+        );
+    }
+    txt
+}
+
+/// 多边形的类别掩码：背景为 0，多边形内为类别序号 + 1，后画的覆盖先画的；没有多边形时为 None
+fn class_mask(doc: &Document, classes: &[String]) -> Option<GrayImage> {
+    let objects = objects(doc);
+    if objects.iter().all(|o| o.polygon.is_none()) {
+        return None;
+    }
+    let (width, height) = doc.original_image.dimensions();
+    let mut mask = GrayImage::new(width, height);
+    let paint = Paint {
+        anti_alias: false,
+        ..Default::default()
+    };
+    for object in &objects {
+        let Some(path) = object.polygon.as_deref().and_then(polygon_path) else {
+            continue;
+        };
+        let mut pixmap = Pixmap::new(width, height)?;
+        pixmap.fill_path(&path, &paint, FillRule::Winding, Transform::identity(), None);
+        let value = (class_index(classes, object.label) + 1).min(255) as u8;
+        for (pixel, covered) in mask.iter_mut().zip(pixmap.pixels()) {
+            if covered.alpha() > 0 {
+                *pixel = value;
+            }
+        }
+    }
+    Some(mask)
+}
+
+/// 多边形面积（鞋带公式）
+fn polygon_area(points: &[Pos2]) -> f32 {
+    let twice: f32 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.x * b.y - b.x * a.y)
+        .sum();
+    twice.abs() / 2.0
+}
+
+fn class_index(classes: &[String], label: &str) -> usize {
+    classes.iter().position(|c| c == label).unwrap_or_default()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Color32, Context, Vec2, epaint::EllipseShape};
+    use image::RgbaImage;
+
+    use super::*;
+    use crate::{loader::LoadedImage, toolbar::StrokeWidth};
+
+    fn document(path: &str, width: u32, height: u32, operators: Vec<Operator>) -> Document {
+        let mut doc = Document::new(
+            &Context::default(),
+            LoadedImage {
+                image: RgbaImage::new(width, height),
+                size: Vec2::new(width as f32, height as f32),
+                name: path.to_owned(),
+                path: Some(PathBuf::from(path)),
+            },
+        );
+        doc.operators = operators;
+        doc
+    }
+
+    fn labelled(tool: ToolType, label: Option<&str>) -> Operator {
+        let mut op = Operator::new(tool, StrokeWidth::THREE, Color32::RED, None);
+        op.label = label.map(str::to_owned);
+        op
+    }
+
+    fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> ToolType {
+        ToolType::Rect(Rect::from_min_max(Pos2::new(x0, y0), Pos2::new(x1, y1)))
+    }
+
+    #[test]
+    fn yolo_normalises_by_image_size() {
+        let doc = document("a.png", 100, 50, vec![labelled(rect(10.0, 10.0, 30.0, 20.0), Some("cat"))]);
+        let classes = class_names(&[&doc], &["dog".to_owned()]);
+        assert_eq!(classes, ["dog", "cat"]);
+        assert_eq!(yolo(&doc, &classes), "1 0.200000 0.300000 0.200000 0.200000\n");
+    }
+
+    #[test]
+    fn shapes_are_clamped_to_image() {
+        let doc = document(
+            "a.png",
+            100,
+            50,
+            vec![
+                labelled(rect(-10.0, -10.0, 20.0, 20.0), None),
+                labelled(rect(120.0, 0.0, 150.0, 20.0), None),
+                labelled(
+                    ToolType::Ellipse(EllipseShape::stroke(Pos2::new(90.0, 25.0), Vec2::new(20.0, 10.0), (1.0, Color32::RED))),
+                    None,
+                ),
+                labelled(
+                    ToolType::Polygon(vec![Pos2::new(50.0, 10.0), Pos2::new(150.0, 25.0), Pos2::new(50.0, 60.0)]),
+                    None,
+                ),
+                labelled(ToolType::Line(Pos2::ZERO, Pos2::new(10.0, 10.0)), None),
+            ],
+        );
+        let objects = objects(&doc);
+        assert_eq!(objects.len(), 3, "shapes outside the image and non-shapes are skipped");
+        assert!(objects.iter().all(|o| o.label == DEFAULT_LABEL));
+        assert_eq!(objects[0].bbox, Rect::from_min_max(Pos2::ZERO, Pos2::new(20.0, 20.0)));
+        assert_eq!(objects[1].bbox, Rect::from_min_max(Pos2::new(70.0, 15.0), Pos2::new(100.0, 35.0)));
+        assert_eq!(
+            objects[2].polygon.as_deref(),
+            Some(&[Pos2::new(50.0, 10.0), Pos2::new(100.0, 25.0), Pos2::new(50.0, 50.0)][..])
+        );
+        assert_eq!(objects[2].bbox, Rect::from_min_max(Pos2::new(50.0, 10.0), Pos2::new(100.0, 50.0)));
+    }
+
+    #[test]
+    fn polygon_area_ignores_winding() {
+        let square = [Pos2::new(0.0, 0.0), Pos2::new(10.0, 0.0), Pos2::new(10.0, 10.0), Pos2::new(0.0, 10.0)];
+        assert_eq!(polygon_area(&square), 100.0);
+        let reversed: Vec<Pos2> = square.iter().rev().copied().collect();
+        assert_eq!(polygon_area(&reversed), 100.0);
+        assert_eq!(polygon_area(&[Pos2::new(0.0, 0.0), Pos2::new(4.0, 0.0), Pos2::new(0.0, 3.0)]), 6.0);
+    }
+
+    #[test]
+    fn coco_uses_polygon_area_and_one_based_ids() {
+        let triangle = vec![Pos2::new(0.0, 0.0), Pos2::new(4.0, 0.0), Pos2::new(0.0, 3.0)];
+        let doc = document(
+            "dir/a.png",
+            10,
+            10,
+            vec![labelled(rect(1.0, 1.0, 3.0, 4.0), Some("cat")), labelled(ToolType::Polygon(triangle), Some("dog"))],
+        );
+        let classes = class_names(&[&doc], &[]);
+        let json: serde_json::Value = serde_json::from_str(&coco(&[&doc], &classes).unwrap()).unwrap();
+        assert_eq!(json["images"][0]["file_name"], "a.png");
+        assert_eq!(json["categories"][1]["name"], "dog");
+        let annotations = json["annotations"].as_array().unwrap();
+        assert_eq!(annotations[0]["bbox"], serde_json::json!([1.0, 1.0, 2.0, 3.0]));
+        assert_eq!(annotations[0]["area"], 6.0);
+        assert_eq!(annotations[1]["category_id"], 2);
+        assert_eq!(annotations[1]["area"], 6.0);
+        assert_eq!(annotations[1]["segmentation"], serde_json::json!([[0.0, 0.0, 4.0, 0.0, 0.0, 3.0]]));
+    }
+
+    #[test]
+    fn voc_rounds_to_pixels_and_escapes_names() {
+        let doc = document("a.png", 10, 10, vec![labelled(rect(1.4, 1.6, 3.5, 4.0), Some("a&b"))]);
+        let xml = voc(&doc);
+        assert!(xml.contains("<name>a&amp;b</name>"));
+        assert!(xml.contains("<xmin>1</xmin>"));
+        assert!(xml.contains("<ymin>2</ymin>"));
+        assert!(xml.contains("<xmax>4</xmax>"));
+        assert!(xml.contains("<segmented>0</segmented>"));
+    }
+
+    #[test]
+    fn export_does_not_overwrite_tabs_with_the_same_name() {
+        let dir = std::env::temp_dir().join(format!("annotator-dataset-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let docs = [
+            document("one/a.png", 10, 10, vec![labelled(rect(1.0, 1.0, 3.0, 3.0), None)]),
+            document("two/a.png", 10, 10, vec![labelled(rect(2.0, 2.0, 4.0, 4.0), None)]),
+            document("three/a.png", 10, 10, Vec::new()),
+        ];
+        let docs: Vec<&Document> = docs.iter().collect();
+        let written = export(&docs, &[], 0, DatasetFormat::Yolo, &dir.join("a.txt")).unwrap();
+        let names: Vec<_> = written.iter().map(|p| p.file_name().unwrap().to_string_lossy().into_owned()).collect();
+        assert_eq!(names, ["a.txt", "a-2.txt", "a-3.txt", "classes.txt"]);
+        assert_ne!(fs::read_to_string(&written[0]).unwrap(), fs::read_to_string(&written[1]).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+use egui::{Context, Painter, Pos2, Rect, Vec2};
+use image::{RgbaImage, imageops::{self, FilterType}};
+use tiny_skia::Transform;
+
+use crate::{
+    drawable::{self, DrawImage, Layer, MosaicCache},
+    font::FontSet,
+    loader::LoadedImage,
+    operators::{DragHandle, Operator, ToolType},
+    tiles::{self, ImageTile},
+};
+
+/// 按倍率缩放后的尺寸，至少 1 像素
+pub fn scaled_size(width: u32, height: u32, scale: f32) -> (u32, u32) {
+    let scale = |v: u32| ((v as f32 * scale).round() as u32).max(1);
+    (scale(width), scale(height))
+}
+
+/// 一个标签页：一张图片及其标注、视图状态
+pub struct Document {
+    /// 标签页标题
+    pub title: String,
+    /// 图片来源路径（剪贴板等来源为 None）
+    pub path: Option<PathBuf>,
+    // 显示
+    pub tiles: Vec<ImageTile>,
+    pub last_image_rect: Option<Rect>,
+    pub zoom: f32,
+    pub pan: Vec2,
+    // 图片相关
+    pub image_size: Vec2,
+    pub original_image: RgbaImage,
+    // 进行过的操作
+    pub operators: Vec<Operator>,
+    /// 下一个数字标注的编号
+    pub number: u8,
+    /// 选择工具选中的标注
+    pub selected: Option<usize>,
+    /// 拖动选中标注时移动的部分
+    pub drag_handle: DragHandle,
+    /// 上次保存到的文件，再次保存同一格式时覆盖它
+    pub saved_path: Option<PathBuf>,
+    /// 画布上马赛克色块的缓存
+    pub mosaic_cache: MosaicCache,
+}
+
+impl Document {
+    pub fn new(ctx: &Context, loaded: LoadedImage) -> Self {
+        Self {
+            title: loaded.name,
+            path: loaded.path,
+            tiles: tiles::upload_tiles(ctx, &loaded.image),
+            last_image_rect: None,
+            zoom: 1.0,
+            pan: Vec2::ZERO,
+            image_size: loaded.size,
+            original_image: loaded.image,
+            operators: Vec::new(),
+            number: 0,
+            selected: None,
+            drag_handle: DragHandle::Body,
+            saved_path: None,
+            mosaic_cache: MosaicCache::default(),
+        }
+    }
+
+    /// 将标注绘制到原图副本上：按导出倍率先重采样原图，再在目标分辨率上栅格化标注，
+    /// 而不是缩放渲染好的位图，缩小时细线不会消失
+    ///
+    /// `Layer::Overlay` 时画在同样大小的透明图上，只导出标注层
+    pub fn render(&self, fonts: &FontSet, scale: f32, layer: Layer) -> RgbaImage {
+        let (width, height) = self.original_image.dimensions();
+        let (scaled_width, scaled_height) = scaled_size(width, height, scale);
+        let img = match layer {
+            Layer::Overlay => RgbaImage::new(scaled_width, scaled_height),
+            Layer::Composite if (scaled_width, scaled_height) == (width, height) => self.original_image.clone(),
+            Layer::Composite => {
+                imageops::resize(&self.original_image, scaled_width, scaled_height, FilterType::Lanczos3)
+            }
+        };
+        let transform = Transform::from_scale(
+            scaled_width as f32 / width as f32,
+            scaled_height as f32 / height as f32,
+        );
+        // tiny-skia 使用预乘透明度，画完再转回非预乘
+        let Some(mut pixmap) = drawable::to_pixmap(&img) else {
+            return img;
+        };
+        for op in &self.operators {
+            op.draw_on_image(&mut pixmap, fonts, transform, layer);
+        }
+        drawable::to_image(&pixmap)
+    }
+
+    /// SVG、PDF 嵌入的原图：马赛克区域在像素上打码，导出文件中不留被遮住的内容
+    pub fn redacted_image(&self) -> Cow<'_, RgbaImage> {
+        let mosaics: Vec<_> = self
+            .operators
+            .iter()
+            .filter_map(|op| match &op.tool {
+                ToolType::Mosaic(rect) => Some((op, rect)),
+                _ => None,
+            })
+            .collect();
+        if mosaics.is_empty() {
+            return Cow::Borrowed(&self.original_image);
+        }
+        let Some(mut pixmap) = drawable::to_pixmap(&self.original_image) else {
+            return Cow::Borrowed(&self.original_image);
+        };
+        for (op, rect) in mosaics {
+            drawable::draw_mosaic_on_image(op, &mut pixmap, rect, Transform::identity());
+        }
+        Cow::Owned(drawable::to_image(&pixmap))
+    }
+
+    /// 导出文件路径：`<导出目录>/<原文件名>_annotated.<扩展名>`
+    ///
+    /// 未设置导出目录时放在原图所在目录，没有来源路径时为当前目录；
+    /// 文件已存在时依次尝试 `_annotated-2`、`_annotated-3`……，不覆盖已有文件
+    pub fn output_path(&self, dir: Option<&Path>, extension: &str) -> PathBuf {
+        let stem = self
+            .path
+            .as_deref()
+            .and_then(Path::file_stem)
+            .map_or_else(|| self.title.clone(), |s| s.to_string_lossy().into_owned());
+        let dir = dir
+            .or_else(|| self.path.as_deref().and_then(Path::parent))
+            .unwrap_or(Path::new(""));
+        unique_path(dir, &format!("{stem}_annotated"), extension)
+    }
+
+    /// 撤销最后一次操作
+    pub fn undo(&mut self) {
+        self.selected = None;
+        let opt = self.operators.pop();
+        if opt.is_some_and(|op| matches!(op.tool, ToolType::Number(..))) {
+            self.number -= 1;
+        }
+    }
+
+    /// 查找位于 `pos`（图片坐标）的最上层标注
+    pub fn hit_test(&self, pos: Pos2, painter: &Painter, tolerance: f32) -> Option<usize> {
+        self.operators
+            .iter()
+            .rposition(|op| op.bounding_rect(painter).expand(tolerance).contains(pos))
+    }
+
+    /// 选中位于 `pos`（图片坐标）的标注，并确定拖动时移动的部分
+    pub fn select_at(&mut self, pos: Pos2, painter: &Painter, tolerance: f32) {
+        self.selected = self.hit_test(pos, painter, tolerance);
+        self.drag_handle = self
+            .selected
+            .map(|i| self.operators[i].handle_at(pos, tolerance))
+            .unwrap_or_default();
+    }
+
+    /// 拖动选中的标注
+    pub fn drag_selected(&mut self, delta: Vec2) {
+        if let Some(op) = self.selected.and_then(|i| self.operators.get_mut(i)) {
+            op.drag(self.drag_handle, delta);
+        }
+    }
+
+    /// 平移选中的标注
+    pub fn nudge_selected(&mut self, delta: Vec2) {
+        if let Some(op) = self.selected.and_then(|i| self.operators.get_mut(i)) {
+            op.translate(delta);
+        }
+    }
+
+    /// 缩放并居中，使图片完整显示在 `available_rect` 中
+    pub fn reset_view(&mut self, available_rect: Rect) {
+        let image_size = self.image_size;
+        let panel_size = available_rect.size();
+
+        let scale_x = panel_size.x / image_size.x;
+        let scale_y = panel_size.y / image_size.y;
+
+        self.zoom = scale_x.min(scale_y);
+
+        let new_size = image_size * self.zoom;
+
+        self.pan = (panel_size - new_size) / 2.0;
+    }
+}
+
+/// `<dir>/<stem>.<extension>`，文件已存在时加上序号 `-2`、`-3`……
+pub fn unique_path(dir: &Path, stem: &str, extension: &str) -> PathBuf {
+    let mut path = dir.join(format!("{stem}.{extension}"));
+    let mut n = 1;
+    while path.exists() {
+        n += 1;
+        path = dir.join(format!("{stem}-{n}.{extension}"));
+    }
+    path
+}
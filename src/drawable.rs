@@ -1,25 +1,64 @@
-use ab_glyph::{Font, PxScale};
-use egui::Vec2;
+use std::collections::{HashMap, HashSet};
+
+use ab_glyph::{Font, PxScale, ScaleFont, point};
+use egui::{Color32, Vec2};
 use egui::{Pos2, Rect, epaint::EllipseShape};
 use image::RgbaImage;
-use imageproc::drawing::draw_text_mut;
-use tiny_skia::{FillRule, Paint, Path, PathBuilder, PixmapMut, Stroke, Transform};
+use tiny_skia::{ColorU8, FillRule, Paint, Path, PathBuilder, Pixmap, PremultipliedColorU8, Stroke, Transform};
 use tiny_skia::Rect as SkiaRect;
 
-use crate::operators::{Operator, ToolType};
+use crate::font::FontSet;
+use crate::operators::{
+    CALLOUT_FILL, MASK_COLOR, Operator, TextAlign, TextStyle, ToolType, callout_tail, mosaic_block,
+    number_text_color,
+};
+
+/// 标注绘制到的图层
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Layer {
+    /// 画在原图上，马赛克按下面的像素打码
+    #[default]
+    Composite,
+    /// 只有标注的透明图层，马赛克区域画成不透明遮罩
+    Overlay,
+}
 
 pub trait DrawImage {
-    fn draw_on_image<F: Font>(&self, img: &mut RgbaImage, font: &F);
+    /// 经 `transform`（图片坐标到像素）绘制，导出缩放时直接按目标分辨率栅格化
+    ///
+    /// `img` 与 tiny-skia 一致为预乘透明度，用 [`to_pixmap`]、[`to_image`] 与 `RgbaImage` 互相转换
+    fn draw_on_image(&self, img: &mut Pixmap, fonts: &FontSet, transform: Transform, layer: Layer);
+}
+
+/// 非预乘的图片转换为预乘透明度的 `Pixmap`，图片为空时返回 None
+pub fn to_pixmap(img: &RgbaImage) -> Option<Pixmap> {
+    let mut pixmap = Pixmap::new(img.width(), img.height())?;
+    for (dst, src) in pixmap.pixels_mut().iter_mut().zip(img.pixels()) {
+        let [r, g, b, a] = src.0;
+        *dst = ColorU8::from_rgba(r, g, b, a).premultiply();
+    }
+    Some(pixmap)
+}
+
+/// 预乘透明度的 `Pixmap` 转回非预乘的图片，半透明像素在别处合成时不会变暗
+pub fn to_image(pixmap: &Pixmap) -> RgbaImage {
+    let mut img = RgbaImage::new(pixmap.width(), pixmap.height());
+    for (dst, src) in img.pixels_mut().zip(pixmap.pixels()) {
+        let c = src.demultiply();
+        *dst = image::Rgba([c.red(), c.green(), c.blue(), c.alpha()]);
+    }
+    img
 }
 
 impl DrawImage for Operator {
-    fn draw_on_image<F: Font>(&self, img: &mut RgbaImage, font: &F) {
+    fn draw_on_image(&self, img: &mut Pixmap, fonts: &FontSet, transform: Transform, layer: Layer) {
         match &self.tool {
-            ToolType::Rect(rect) => draw_rect_on_image(self, img, &rect),
-            ToolType::Ellipse(ellipse) => draw_ellipse_on_image(self, img, &ellipse),
-            ToolType::Arrow(arrow) => draw_points_on_image(self, img, &arrow.points, true),
-            ToolType::Line(s, e) => draw_line_on_image(self, img, s, e),
-            ToolType::Pencil(points) => draw_points_on_image(self, img, points, false),
+            ToolType::Rect(rect) => draw_rect_on_image(self, img, rect, transform),
+            ToolType::Ellipse(ellipse) => draw_ellipse_on_image(self, img, ellipse, transform),
+            ToolType::Arrow(arrow) => draw_points_on_image(self, img, &arrow.points, true, transform),
+            ToolType::Line(s, e) => draw_line_on_image(self, img, s, e, transform),
+            ToolType::Pencil(points) => draw_points_on_image(self, img, points, false, transform),
+            ToolType::Polygon(points) => draw_points_on_image(self, img, points, true, transform),
             ToolType::Number(c, n) => {
                 let ellipse = EllipseShape {
                     radius: Vec2::new(c.radius, c.radius),
@@ -27,23 +66,133 @@ impl DrawImage for Operator {
                     fill: c.fill,
                     stroke: c.stroke,
                 };
-                draw_ellipse_on_image(self, img, &ellipse);
-                draw_text(img, c.center, &n.to_string(), c.radius, font, true);
+                draw_ellipse_on_image(self, img, &ellipse, transform);
+                let style = TextStyle {
+                    size: c.radius,
+                    align: TextAlign::Center,
+                    ..Default::default()
+                };
+                let color = number_text_color(c.fill, self.color);
+                draw_text(img, c.center, &(n + 1).to_string(), &style, fonts, color, true, transform);
+            },
+            ToolType::Text { pos, content, style } => {
+                draw_text(img, *pos, content, style, fonts, self.color, false, transform);
+            }
+            ToolType::Callout { target, pos, content, style } => {
+                draw_callout(self, img, *target, *pos, content, style, fonts, transform);
+            }
+            ToolType::Mosaic(rect) => match layer {
+                Layer::Composite => draw_mosaic_on_image(self, img, rect, transform),
+                Layer::Overlay => {
+                    if let Some(skia_rect) = SkiaRect::from_ltrb(rect.left(), rect.top(), rect.right(), rect.bottom()) {
+                        fill_skia_path(img, &PathBuilder::from_rect(skia_rect), MASK_COLOR, transform);
+                    }
+                }
             },
-            ToolType::Text { .. } => todo!(),
         }
     }
 }
 
-fn draw_rect_on_image(op: &Operator, img: &mut RgbaImage, rect: &Rect) {
+/// 把区域内已经画好的像素替换成色块
+pub fn draw_mosaic_on_image(op: &Operator, img: &mut Pixmap, rect: &Rect, transform: Transform) {
+    let mut min = tiny_skia::Point::from_xy(rect.min.x, rect.min.y);
+    let mut max = tiny_skia::Point::from_xy(rect.max.x, rect.max.y);
+    transform.map_point(&mut min);
+    transform.map_point(&mut max);
+    let pixel_rect = Rect::from_two_pos(Pos2::new(min.x, min.y), Pos2::new(max.x, max.y));
+    let block = mosaic_block(op.stroke_width.into()) * transform.sx;
+
+    // 预乘值的平均仍是合法的预乘颜色，透明像素不会把色块染黑
+    let width = img.width();
+    let cells = average_cells((width, img.height()), pixel_rect, block, |x, y| {
+        let p = img.pixels()[(y * width + x) as usize];
+        [p.red(), p.green(), p.blue(), p.alpha()]
+    });
+    let pixels = img.pixels_mut();
+    for (cell, [r, g, b, a]) in cells {
+        let pixel = PremultipliedColorU8::from_rgba(r, g, b, a).unwrap_or(PremultipliedColorU8::TRANSPARENT);
+        for y in cell.min.y as u32..cell.max.y as u32 {
+            for x in cell.min.x as u32..cell.max.x as u32 {
+                pixels[(y * width + x) as usize] = pixel;
+            }
+        }
+    }
+}
+
+/// 马赛克色块：把区域按 `block` 像素分格，每格取平均色，格子对齐到整像素并裁剪到图片内
+pub fn mosaic_cells(img: &RgbaImage, rect: Rect, block: f32) -> Vec<(Rect, Color32)> {
+    average_cells(img.dimensions(), rect, block, |x, y| img.get_pixel(x, y).0)
+        .into_iter()
+        .map(|(cell, [r, g, b, a])| (cell, Color32::from_rgba_unmultiplied(r, g, b, a)))
+        .collect()
+}
+
+/// 屏幕上马赛克色块的缓存：区域和格子大小不变时沿用上次的平均色，不必每帧重新计算
+#[derive(Default)]
+pub struct MosaicCache {
+    cells: HashMap<[u32; 5], Vec<(Rect, Color32)>>,
+    /// 本帧用到的区域
+    used: HashSet<[u32; 5]>,
+}
+
+impl MosaicCache {
+    /// 区域的色块，没有缓存时计算
+    pub fn cells(&mut self, img: &RgbaImage, rect: Rect, block: f32) -> &[(Rect, Color32)] {
+        let key = [rect.min.x, rect.min.y, rect.max.x, rect.max.y, block].map(f32::to_bits);
+        self.used.insert(key);
+        self.cells.entry(key).or_insert_with(|| mosaic_cells(img, rect, block))
+    }
+
+    /// 一帧画完后丢掉没有用到的区域（已移动、删除的马赛克）
+    pub fn end_frame(&mut self) {
+        let used = std::mem::take(&mut self.used);
+        self.cells.retain(|key, _| used.contains(key));
+    }
+}
+
+/// 按 `block` 像素分格求每格各通道的平均值，`pixel` 读取 (x, y) 处的像素
+fn average_cells(
+    (width, height): (u32, u32),
+    rect: Rect,
+    block: f32,
+    pixel: impl Fn(u32, u32) -> [u8; 4],
+) -> Vec<(Rect, [u8; 4])> {
+    let x0 = rect.min.x.floor().max(0.0) as u32;
+    let y0 = rect.min.y.floor().max(0.0) as u32;
+    let x1 = (rect.max.x.ceil().max(0.0) as u32).min(width);
+    let y1 = (rect.max.y.ceil().max(0.0) as u32).min(height);
+    let block = block.round().max(1.0) as u32;
+
+    let mut cells = Vec::new();
+    for y in (y0..y1).step_by(block as usize) {
+        for x in (x0..x1).step_by(block as usize) {
+            let (w, h) = (block.min(x1 - x), block.min(y1 - y));
+            let mut sum = [0u64; 4];
+            for py in y..y + h {
+                for px in x..x + w {
+                    for (s, c) in sum.iter_mut().zip(pixel(px, py)) {
+                        *s += u64::from(c);
+                    }
+                }
+            }
+            let n = u64::from(w * h);
+            let [r, g, b, a] = sum.map(|s| ((s + n / 2) / n) as u8);
+            let cell = Rect::from_min_size(Pos2::new(x as f32, y as f32), Vec2::new(w as f32, h as f32));
+            cells.push((cell, [r, g, b, a]));
+        }
+    }
+    cells
+}
+
+fn draw_rect_on_image(op: &Operator, img: &mut Pixmap, rect: &Rect, transform: Transform) {
     let skia_rect = SkiaRect::from_xywh(rect.left(), rect.top(), rect.width(), rect.height()).unwrap();
 
     let path = PathBuilder::from_rect(skia_rect);
 
-    draw_skia_image(op, img, &path);
+    draw_skia_image(op, img, &path, transform);
 }
 
-fn draw_ellipse_on_image(op: &Operator, img: &mut RgbaImage, ellipse: &EllipseShape) {
+fn draw_ellipse_on_image(op: &Operator, img: &mut Pixmap, ellipse: &EllipseShape, transform: Transform) {
     let rect = SkiaRect::from_xywh(
         ellipse.center.x - ellipse.radius.x,
         ellipse.center.y - ellipse.radius.y,
@@ -54,20 +203,20 @@ fn draw_ellipse_on_image(op: &Operator, img: &mut RgbaImage, ellipse: &EllipseSh
 
     let path = PathBuilder::from_oval(rect).unwrap();
 
-    draw_skia_image(op, img, &path);
+    draw_skia_image(op, img, &path, transform);
 }
 
-fn draw_line_on_image(op: &Operator, img: &mut RgbaImage, start: &Pos2, end: &Pos2) {
+fn draw_line_on_image(op: &Operator, img: &mut Pixmap, start: &Pos2, end: &Pos2, transform: Transform) {
     let mut pb = PathBuilder::new();
     pb.move_to(start.x, start.y);
     pb.line_to(end.x, end.y);
 
     if let Some(path) = pb.finish() {
-        draw_skia_image(op, img, &path);
+        draw_skia_image(op, img, &path, transform);
     }
 }
 
-fn draw_points_on_image(op: &Operator, img: &mut RgbaImage, points: &Vec<Pos2>, close: bool) {
+fn draw_points_on_image(op: &Operator, img: &mut Pixmap, points: &[Pos2], close: bool, transform: Transform) {
     if points.is_empty() {
         return;
     }
@@ -81,47 +230,298 @@ fn draw_points_on_image(op: &Operator, img: &mut RgbaImage, points: &Vec<Pos2>,
     }
 
     if let Some(path) = pb.finish() {
-        draw_skia_image(op, img, &path);
+        draw_skia_image(op, img, &path, transform);
     }
 }
 
-fn draw_skia_image(op: &Operator, img: &mut RgbaImage, path: &Path) {
-    let (width, height) = img.dimensions();
+fn draw_skia_image(op: &Operator, img: &mut Pixmap, path: &Path, transform: Transform) {
+    let mut pixmap = img.as_mut();
 
-    // 直接用 RgbaImage 的 buffer 构造 PixmapMut，零拷贝
-    let mut pixmap = PixmapMut::from_bytes(img.as_mut(), width, height).unwrap();
-
-    let color = op.color;
+    // Color32 是预乘透明度的，tiny-skia 需要未预乘的颜色
+    let [r, g, b, a] = op.color.to_srgba_unmultiplied();
     let mut paint = Paint::default();
-    paint.set_color_rgba8(color.r(), color.g(), color.b(), color.a());
+    paint.set_color_rgba8(r, g, b, a);
     paint.anti_alias = true;
 
-    let mut stroke = Stroke::default();
-    stroke.width = op.stroke_width.into();
-    pixmap.stroke_path(path, &paint, &stroke, Transform::identity(), None);
-
+    // 先填充再描边，描边不会被半透明的填充色盖住
     if let Some(fill_color) = op.fill_color {
         let mut fill_paint = Paint::default();
-        fill_paint.set_color_rgba8(fill_color.r(), fill_color.g(), fill_color.b(), fill_color.a());
+        let [r, g, b, a] = fill_color.to_srgba_unmultiplied();
+        fill_paint.set_color_rgba8(r, g, b, a);
         fill_paint.anti_alias = true;
-        pixmap.fill_path(path, &fill_paint, FillRule::Winding, Transform::identity(), None);
+        pixmap.fill_path(path, &fill_paint, FillRule::Winding, transform, None);
     }
 
+    let stroke = Stroke {
+        width: min_stroke_width(op.stroke_width.into(), transform),
+        ..Default::default()
+    };
+    pixmap.stroke_path(path, &paint, &stroke, transform, None);
+}
+
+/// 缩小导出时线宽至少保留 1 像素，避免细线消失
+fn min_stroke_width(width: f32, transform: Transform) -> f32 {
+    width.max(1.0 / transform.sx)
+}
+
+/// 斜体的倾斜比例
+pub const ITALIC_SHEAR: f32 = 0.2;
+
+/// 排好版的文字
+pub struct TextLayout {
+    pub scale: PxScale,
+    /// 每行的文字和起点（基线处）
+    pub lines: Vec<(String, Pos2)>,
+    /// 文字区域，粗体时包含错开绘制的部分
+    pub rect: Rect,
 }
 
-fn draw_text<F: Font>(img: &mut RgbaImage, pos: Pos2, text: &str, size: f32, font: &F, center: bool) {
-    let scale = PxScale::from(size);
-    let color = image::Rgba([255, 255, 255, 255]);
-    
-    let (text_x, text_y) = if center {
-        // 计算居中位置
-        let (tw, th) = crate::font::measure_text(&font, scale, &text);
-        let text_x = pos.x - tw / 2.0;
-        let text_y = pos.y - th / 2.0;
-        (text_x as i32, text_y as i32)
+/// 逐行排版文字
+///
+/// `pos` 为锚点，每行水平方向按 `style.align` 对齐；`middle` 为 true 时垂直居中，否则为顶部
+pub fn layout_text(pos: Pos2, text: &str, style: &TextStyle, fonts: &FontSet, middle: bool) -> Option<TextLayout> {
+    let primary = fonts.primary()?;
+    // egui 的字号是 em 大小，换算成 ab_glyph 的行高缩放，与预览保持一致
+    let scale = primary.pt_to_px_scale(style.size).unwrap_or(PxScale::from(style.size));
+
+    let lines = fonts.wrap_lines(scale, text, style.wrap_width);
+    let line_height = fonts.line_height(scale);
+    let top = if middle {
+        let (_, th) = fonts.measure_text(scale, text);
+        pos.y - (th + line_height * (lines.len() - 1) as f32) / 2.0
     } else {
-        (pos.x as i32, pos.y as i32)
+        pos.y
+    };
+    let ascent = primary.as_scaled(scale).ascent();
+
+    let mut rect = Rect::NOTHING;
+    let mut placed = Vec::with_capacity(lines.len());
+    for (i, line) in lines.into_iter().enumerate() {
+        let (width, _) = fonts.measure_text(scale, line.trim_end());
+        let dx = match style.align {
+            TextAlign::Left => 0.0,
+            TextAlign::Center => width / 2.0,
+            TextAlign::Right => width,
+        };
+        let line_top = top + line_height * i as f32;
+        rect = rect.union(Rect::from_min_size(
+            Pos2::new(pos.x - dx, line_top),
+            Vec2::new(width, line_height),
+        ));
+        placed.push((line, Pos2::new(pos.x - dx, line_top + ascent)));
+    }
+    if style.bold {
+        rect.max.x += style.bold_offset();
+    }
+    Some(TextLayout { scale, lines: placed, rect })
+}
+
+/// 绘制文字及其背景框，每个字符使用字体链中第一个包含该字形的字体
+#[allow(clippy::too_many_arguments)]
+fn draw_text(
+    img: &mut Pixmap,
+    pos: Pos2,
+    text: &str,
+    style: &TextStyle,
+    fonts: &FontSet,
+    color: Color32,
+    middle: bool,
+    transform: Transform,
+) {
+    let Some(layout) = layout_text(pos, text, style, fonts, middle) else {
+        return;
+    };
+
+    if let Some(background) = style.background {
+        let box_rect = layout.rect.expand(style.padding);
+        if let Some(shadow) = style.shadow
+            && let Some(path) = rounded_rect_path(box_rect.translate(style.shadow_offset()), style.corner_radius)
+        {
+            fill_skia_path(img, &path, shadow, transform);
+        }
+        if let Some(path) = rounded_rect_path(box_rect, style.corner_radius) {
+            fill_skia_path(img, &path, background, transform);
+        }
+    }
+    draw_glyphs(img, &layout, style, fonts, color, transform);
+}
+
+/// 绘制标注气泡：带尾巴的圆角文字框
+#[allow(clippy::too_many_arguments)]
+fn draw_callout(
+    op: &Operator,
+    img: &mut Pixmap,
+    target: Pos2,
+    pos: Pos2,
+    text: &str,
+    style: &TextStyle,
+    fonts: &FontSet,
+    transform: Transform,
+) {
+    let Some(layout) = layout_text(pos, text, style, fonts, false) else {
+        return;
+    };
+    let box_rect = layout.rect.expand(style.padding);
+    let Some(bubble) = rounded_rect_path(box_rect, style.corner_radius) else {
+        return;
+    };
+    let tail = callout_tail(box_rect, target).and_then(|points| polygon_path(&points));
+    let fill = style.background.unwrap_or(CALLOUT_FILL);
+
+    if let Some(shadow) = style.shadow {
+        let offset = style.shadow_offset();
+        let shift = Transform::from_translate(offset.x, offset.y);
+        for path in tail.iter().chain([&bubble]) {
+            if let Some(path) = path.clone().transform(shift) {
+                fill_skia_path(img, &path, shadow, transform);
+            }
+        }
+    }
+    // 先画两倍宽的边框再填充，填充盖住内侧一半，只留下外轮廓
+    let outline_width = f32::from(op.stroke_width) * 2.0;
+    for path in tail.iter().chain([&bubble]) {
+        stroke_skia_path(img, path, op.color, outline_width, transform);
+    }
+    for path in tail.iter().chain([&bubble]) {
+        fill_skia_path(img, path, fill, transform);
+    }
+
+    // 气泡已经是背景，文字不再画阴影
+    let style = TextStyle {
+        shadow: None,
+        ..style.clone()
+    };
+    draw_glyphs(img, &layout, &style, fonts, op.color, transform);
+}
+
+/// 按样式逐行绘制字形
+fn draw_glyphs(img: &mut Pixmap, layout: &TextLayout, style: &TextStyle, fonts: &FontSet, color: Color32, transform: Transform) {
+    // 字形不走 tiny-skia，按变换缩放字号和起点
+    let scale = PxScale {
+        x: layout.scale.x * transform.sx,
+        y: layout.scale.y * transform.sy,
+    };
+    let shear = if style.italic { ITALIC_SHEAR } else { 0.0 };
+    for (offset, pass_color) in style.glyph_passes(color) {
+        let rgba = pass_color.to_srgba_unmultiplied();
+        for (line, start) in &layout.lines {
+            let start = *start + offset;
+            let mut start = tiny_skia::Point::from_xy(start.x, start.y);
+            transform.map_point(&mut start);
+            draw_line_glyphs(img, fonts, scale, line, start.x, start.y, shear, rgba);
+        }
+    }
+}
+
+/// 闭合折线路径
+pub fn polygon_path(points: &[Pos2]) -> Option<Path> {
+    let (first, rest) = points.split_first()?;
+    let mut pb = PathBuilder::new();
+    pb.move_to(first.x, first.y);
+    for p in rest {
+        pb.line_to(p.x, p.y);
+    }
+    pb.close();
+    pb.finish()
+}
+
+/// 圆角矩形路径
+fn rounded_rect_path(rect: Rect, radius: f32) -> Option<Path> {
+    let r = radius.min(rect.width() / 2.0).min(rect.height() / 2.0).max(0.0);
+    if r <= 0.0 {
+        let skia_rect = SkiaRect::from_ltrb(rect.left(), rect.top(), rect.right(), rect.bottom())?;
+        return Some(PathBuilder::from_rect(skia_rect));
+    }
+    // 用三次贝塞尔曲线近似四分之一圆弧
+    let k = r * 0.552_284_8;
+    let (l, t, rt, b) = (rect.left(), rect.top(), rect.right(), rect.bottom());
+    let mut pb = PathBuilder::new();
+    pb.move_to(l + r, t);
+    pb.line_to(rt - r, t);
+    pb.cubic_to(rt - r + k, t, rt, t + r - k, rt, t + r);
+    pb.line_to(rt, b - r);
+    pb.cubic_to(rt, b - r + k, rt - r + k, b, rt - r, b);
+    pb.line_to(l + r, b);
+    pb.cubic_to(l + r - k, b, l, b - r + k, l, b - r);
+    pb.line_to(l, t + r);
+    pb.cubic_to(l, t + r - k, l + r - k, t, l + r, t);
+    pb.close();
+    pb.finish()
+}
+
+/// 用纯色描边路径
+fn stroke_skia_path(img: &mut Pixmap, path: &Path, color: Color32, width: f32, transform: Transform) {
+    let mut pixmap = img.as_mut();
+
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(r, g, b, a);
+    paint.anti_alias = true;
+    let stroke = Stroke {
+        width: min_stroke_width(width, transform),
+        ..Default::default()
     };
+    pixmap.stroke_path(path, &paint, &stroke, transform, None);
+}
 
-    draw_text_mut(img, color, text_x, text_y, scale, &font, text);
+/// 用纯色填充路径
+fn fill_skia_path(img: &mut Pixmap, path: &Path, color: Color32, transform: Transform) {
+    let mut pixmap = img.as_mut();
+
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(r, g, b, a);
+    paint.anti_alias = true;
+    pixmap.fill_path(path, &paint, FillRule::Winding, transform, None);
+}
+
+/// 从 (`x`, `baseline`) 开始绘制一行文字
+#[allow(clippy::too_many_arguments)]
+fn draw_line_glyphs(
+    img: &mut Pixmap,
+    fonts: &FontSet,
+    scale: PxScale,
+    line: &str,
+    mut x: f32,
+    baseline: f32,
+    shear: f32,
+    rgba: [u8; 4],
+) {
+    for c in line.chars() {
+        let Some(font) = fonts.font_for(c) else {
+            continue;
+        };
+        let scaled = font.as_scaled(scale);
+        let mut glyph = scaled.scaled_glyph(c);
+        glyph.position = point(x, baseline);
+        x += scaled.h_advance(glyph.id);
+
+        if let Some(outlined) = font.outline_glyph(glyph) {
+            let bounds = outlined.px_bounds();
+            outlined.draw(|gx, gy, coverage| {
+                let py = bounds.min.y + gy as f32;
+                // 斜体：基线以上向右倾斜
+                let px = bounds.min.x + gx as f32 + (baseline - py) * shear;
+                blend_pixel(img, px.round() as i32, py as i32, rgba, coverage);
+            });
+        }
+    }
+}
+
+/// 按覆盖率将非预乘的颜色混合到预乘透明度的像素上（source-over）
+fn blend_pixel(img: &mut Pixmap, x: i32, y: i32, color: [u8; 4], coverage: f32) {
+    if x < 0 || y < 0 || x >= img.width() as i32 || y >= img.height() as i32 {
+        return;
+    }
+    let alpha = coverage.clamp(0.0, 1.0) * color[3] as f32 / 255.0;
+    let index = (y as u32 * img.width() + x as u32) as usize;
+    let dst = img.pixels()[index];
+    let blend = |src: u8, dst: u8| (src as f32 * alpha + dst as f32 * (1.0 - alpha)).round() as u8;
+    let blended = PremultipliedColorU8::from_rgba(
+        blend(color[0], dst.red()),
+        blend(color[1], dst.green()),
+        blend(color[2], dst.blue()),
+        blend(255, dst.alpha()),
+    );
+    img.pixels_mut()[index] = blended.unwrap_or(dst);
 }
@@ -0,0 +1,193 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use egui::Color32;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{ImageFormat, RgbImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::{drawable::Layer, settings::HexColor};
+
+/// 可以导出的图片扩展名，第一个为默认
+pub const EXPORT_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp", "bmp", "tif", "tiff", "svg", "pdf"];
+
+/// 导出格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Png,
+    Jpeg,
+    WebP,
+    Bmp,
+    Tiff,
+    /// 矢量格式，原图嵌入、标注保持可编辑
+    Svg,
+    /// 原图为位图，标注为矢量路径和文字
+    Pdf,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 7] = [
+        ExportFormat::Png,
+        ExportFormat::Jpeg,
+        ExportFormat::WebP,
+        ExportFormat::Bmp,
+        ExportFormat::Tiff,
+        ExportFormat::Svg,
+        ExportFormat::Pdf,
+    ];
+
+    /// 按名称或扩展名解析（不区分大小写）
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(ExportFormat::Png),
+            "jpg" | "jpeg" => Some(ExportFormat::Jpeg),
+            "webp" => Some(ExportFormat::WebP),
+            "bmp" => Some(ExportFormat::Bmp),
+            "tif" | "tiff" => Some(ExportFormat::Tiff),
+            "svg" => Some(ExportFormat::Svg),
+            "pdf" => Some(ExportFormat::Pdf),
+            _ => None,
+        }
+    }
+
+    /// 根据文件扩展名确定格式
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension().and_then(|ext| Self::parse(&ext.to_string_lossy()))
+    }
+
+    /// 保存时使用的扩展名
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Png => "png",
+            ExportFormat::Jpeg => "jpg",
+            ExportFormat::WebP => "webp",
+            ExportFormat::Bmp => "bmp",
+            ExportFormat::Tiff => "tiff",
+            ExportFormat::Svg => "svg",
+            ExportFormat::Pdf => "pdf",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportFormat::Png => "PNG",
+            ExportFormat::Jpeg => "JPEG",
+            ExportFormat::WebP => "WebP",
+            ExportFormat::Bmp => "BMP",
+            ExportFormat::Tiff => "TIFF",
+            ExportFormat::Svg => "SVG",
+            ExportFormat::Pdf => "PDF",
+        }
+    }
+
+    /// 是否保留透明度，不保留时先铺上背景色
+    ///
+    /// BMP 虽然可以存 32 位，但多数软件会忽略其中的透明度
+    pub fn supports_alpha(&self) -> bool {
+        matches!(self, ExportFormat::Png | ExportFormat::WebP | ExportFormat::Tiff | ExportFormat::Svg | ExportFormat::Pdf)
+    }
+
+    /// 是否为位图格式，矢量格式不经过 `save`
+    pub fn is_raster(&self) -> bool {
+        !matches!(self, ExportFormat::Svg | ExportFormat::Pdf)
+    }
+}
+
+/// 导出选项
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportOptions {
+    /// 快速保存时使用的格式
+    pub format: ExportFormat,
+    /// JPEG 和有损 WebP 的质量（1-100）
+    pub quality: u8,
+    /// WebP 是否无损
+    pub webp_lossless: bool,
+    /// 不支持透明度的格式铺底用的颜色
+    pub background: HexColor,
+    /// 导出倍率，按目标分辨率重新渲染
+    pub scale: f32,
+    /// 只导出透明背景上的标注层
+    pub overlay: bool,
+}
+
+/// 导出菜单中的预设倍率
+pub const SCALE_PRESETS: [f32; 3] = [0.5, 1.0, 2.0];
+
+/// 允许的导出倍率范围
+pub const SCALE_RANGE: std::ops::RangeInclusive<f32> = 0.1..=8.0;
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            format: ExportFormat::Png,
+            quality: 90,
+            webp_lossless: true,
+            background: HexColor(Color32::WHITE),
+            scale: 1.0,
+            overlay: false,
+        }
+    }
+}
+
+impl ExportOptions {
+    /// 限制在允许范围内的导出倍率
+    pub fn scale_factor(&self) -> f32 {
+        self.scale.clamp(*SCALE_RANGE.start(), *SCALE_RANGE.end())
+    }
+
+    /// 导出的图层
+    pub fn layer(&self) -> Layer {
+        if self.overlay { Layer::Overlay } else { Layer::Composite }
+    }
+}
+
+/// 按指定的位图格式保存图片
+pub fn save(img: &RgbaImage, path: &Path, format: ExportFormat, options: &ExportOptions) -> Result<(), String> {
+    if !format.is_raster() {
+        return Err(format!("{} is not a raster format", format.label()));
+    }
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut writer = BufWriter::new(file);
+    let quality = options.quality.clamp(1, 100);
+    if !format.supports_alpha() {
+        let rgb = flatten(img, options.background.0);
+        let result = match format {
+            ExportFormat::Jpeg => JpegEncoder::new_with_quality(&mut writer, quality).encode_image(&rgb),
+            _ => rgb.write_to(&mut writer, ImageFormat::Bmp),
+        };
+        return result.map_err(|e| e.to_string());
+    }
+
+    let result = match format {
+        ExportFormat::Tiff => img.write_to(&mut writer, ImageFormat::Tiff),
+        ExportFormat::WebP if options.webp_lossless => WebPEncoder::new_lossless(&mut writer).encode(
+            img,
+            img.width(),
+            img.height(),
+            image::ExtendedColorType::Rgba8,
+        ),
+        // image 只带无损 WebP 编码器，有损编码用自己的 VP8 编码器
+        ExportFormat::WebP => {
+            let data = crate::webp::encode_lossy(img, quality)?;
+            return writer.write_all(&data).map_err(|e| e.to_string());
+        }
+        _ => img.write_to(&mut writer, ImageFormat::Png),
+    };
+    result.map_err(|e| e.to_string())
+}
+
+/// 把透明度混合到背景色上
+fn flatten(img: &RgbaImage, background: Color32) -> RgbImage {
+    let [br, bg, bb, _] = background.to_srgba_unmultiplied();
+    RgbImage::from_fn(img.width(), img.height(), |x, y| {
+        let [r, g, b, a] = img.get_pixel(x, y).0;
+        let a = u32::from(a);
+        let mix = |c: u8, back: u8| ((u32::from(c) * a + u32::from(back) * (255 - a) + 127) / 255) as u8;
+        image::Rgb([mix(r, br), mix(g, bg), mix(b, bb)])
+    })
+}
@@ -0,0 +1,122 @@
+use egui::{Color32, Id, LayerId, Order, Painter, Pos2, Rect, Stroke, StrokeKind, Vec2};
+use image::RgbaImage;
+
+use crate::{color_picker, toolbar::Tool};
+
+/// 放大镜显示的像素范围（中心像素两侧各几个）
+const LOUPE_HALF: i32 = 7;
+/// 放大镜中每个像素的大小（屏幕像素）
+const LOUPE_CELL: f32 = 8.0;
+/// 最大取样半径
+pub const MAX_RADIUS: u32 = 10;
+
+/// 取色器：从原图中取样颜色
+#[derive(Debug, Default)]
+pub struct Eyedropper {
+    /// 取样半径，取 (2r+1)×(2r+1) 个像素的平均色
+    pub radius: u32,
+    /// 取色完成后切换回的工具
+    pub return_to: Tool,
+}
+
+impl Eyedropper {
+    pub fn new(radius: u32) -> Self {
+        Self {
+            radius: radius.min(MAX_RADIUS),
+            ..Default::default()
+        }
+    }
+
+    /// `pos`（图片坐标）处的取样颜色，位于图片外时返回 None
+    pub fn sample(&self, img: &RgbaImage, pos: Pos2) -> Option<Color32> {
+        let (x, y) = pixel_at(img, pos)?;
+        let r = self.radius as i32;
+        let mut sum = [0u32; 4];
+        let mut count = 0;
+        for py in y - r..=y + r {
+            for px in x - r..=x + r {
+                let Some(pixel) = get_pixel(img, px, py) else {
+                    continue;
+                };
+                for (s, v) in sum.iter_mut().zip(pixel) {
+                    *s += v as u32;
+                }
+                count += 1;
+            }
+        }
+        let [r, g, b, a] = sum.map(|s| (s as f32 / count as f32).round() as u8);
+        Some(Color32::from_rgba_unmultiplied(r, g, b, a))
+    }
+
+    /// 在光标旁绘制放大镜：周围像素、取样范围和取样颜色
+    pub fn paint_loupe(&self, ctx: &egui::Context, img: &RgbaImage, cursor: Pos2, pos: Pos2) {
+        let Some((cx, cy)) = pixel_at(img, pos) else {
+            return;
+        };
+        let painter = Painter::new(
+            ctx.clone(),
+            LayerId::new(Order::Tooltip, Id::new("eyedropper_loupe")),
+            ctx.content_rect(),
+        );
+
+        let side = (LOUPE_HALF * 2 + 1) as f32 * LOUPE_CELL;
+        let mut origin = cursor + Vec2::splat(20.0);
+        // 靠近窗口右下边缘时放到光标另一侧
+        let screen = ctx.content_rect();
+        if origin.x + side > screen.right() {
+            origin.x = cursor.x - 20.0 - side;
+        }
+        if origin.y + side + 24.0 > screen.bottom() {
+            origin.y = cursor.y - 20.0 - side - 24.0;
+        }
+        let loupe = Rect::from_min_size(origin, Vec2::splat(side));
+
+        painter.rect_filled(loupe, 0.0, Color32::from_gray(64));
+        for dy in -LOUPE_HALF..=LOUPE_HALF {
+            for dx in -LOUPE_HALF..=LOUPE_HALF {
+                let Some([r, g, b, a]) = get_pixel(img, cx + dx, cy + dy) else {
+                    continue;
+                };
+                let cell = Rect::from_min_size(
+                    origin + Vec2::new((dx + LOUPE_HALF) as f32, (dy + LOUPE_HALF) as f32) * LOUPE_CELL,
+                    Vec2::splat(LOUPE_CELL),
+                );
+                painter.rect_filled(cell, 0.0, Color32::from_rgba_unmultiplied(r, g, b, a));
+            }
+        }
+
+        // 取样范围
+        let r = self.radius.min(LOUPE_HALF as u32) as f32;
+        let sample_rect = Rect::from_center_size(loupe.center(), Vec2::splat((2.0 * r + 1.0) * LOUPE_CELL));
+        painter.rect_stroke(sample_rect, 0.0, Stroke::new(1.0, Color32::BLACK), StrokeKind::Outside);
+        painter.rect_stroke(sample_rect.expand(1.0), 0.0, Stroke::new(1.0, Color32::WHITE), StrokeKind::Outside);
+        painter.rect_stroke(loupe, 0.0, Stroke::new(1.0, Color32::WHITE), StrokeKind::Outside);
+
+        // 取样颜色和十六进制值
+        if let Some(color) = self.sample(img, pos) {
+            let swatch = Rect::from_min_size(loupe.left_bottom() + Vec2::new(0.0, 4.0), Vec2::new(20.0, 20.0));
+            painter.rect_filled(swatch, 2.0, color);
+            painter.rect_stroke(swatch, 2.0, Stroke::new(1.0, Color32::WHITE), StrokeKind::Outside);
+            let label = swatch.right_center() + Vec2::new(6.0, 0.0);
+            let text = color_picker::color_to_hex(color);
+            let font = egui::FontId::monospace(12.0);
+            let galley = painter.layout_no_wrap(text, font, Color32::WHITE);
+            let bg = Rect::from_min_size(label - Vec2::new(2.0, galley.size().y / 2.0 + 2.0), galley.size() + Vec2::splat(4.0));
+            painter.rect_filled(bg, 2.0, Color32::from_black_alpha(180));
+            painter.galley(label - Vec2::new(0.0, galley.size().y / 2.0), galley, Color32::WHITE);
+        }
+    }
+}
+
+/// `pos`（图片坐标）所在的像素
+fn pixel_at(img: &RgbaImage, pos: Pos2) -> Option<(i32, i32)> {
+    let (x, y) = (pos.x.floor() as i32, pos.y.floor() as i32);
+    get_pixel(img, x, y).map(|_| (x, y))
+}
+
+fn get_pixel(img: &RgbaImage, x: i32, y: i32) -> Option<[u8; 4]> {
+    if x < 0 || y < 0 || x >= img.width() as i32 || y >= img.height() as i32 {
+        return None;
+    }
+    Some(img.get_pixel(x as u32, y as u32).0)
+}
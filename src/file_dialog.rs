@@ -0,0 +1,267 @@
+use std::path::{Path, PathBuf};
+
+use egui::{Context, Key, ScrollArea, Ui};
+
+/// 可以打开的图片扩展名
+pub const IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "bmp", "gif", "webp", "tif", "tiff", "ico", "tga", "pnm", "qoi",
+];
+
+/// 使用 egui 绘制的简易文件浏览窗口
+pub struct FileDialog {
+    /// 窗口标题
+    title: String,
+    /// 允许选择的扩展名（为空时不过滤）
+    extensions: &'static [&'static str],
+    /// 是否显示窗口
+    visible: bool,
+    /// 当前浏览的目录
+    current_dir: PathBuf,
+    /// 当前目录下的条目（目录在前，文件在后）
+    entries: Vec<DirEntry>,
+    /// 选中的文件
+    selected: Option<PathBuf>,
+    /// 地址栏内容
+    path_input: String,
+    /// 读取目录失败时的错误信息
+    error: Option<String>,
+    /// 保存模式：输入文件名，文件可以不存在
+    save_mode: bool,
+    /// 保存模式下的文件名
+    file_name: String,
+}
+
+struct DirEntry {
+    path: PathBuf,
+    name: String,
+    is_dir: bool,
+}
+
+impl Default for FileDialog {
+    fn default() -> Self {
+        Self::new("Open Image", IMAGE_EXTENSIONS)
+    }
+}
+
+impl FileDialog {
+    /// 创建文件浏览窗口，`extensions` 为允许选择的文件扩展名（小写）
+    pub fn new(title: impl Into<String>, extensions: &'static [&'static str]) -> Self {
+        let current_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+        Self {
+            title: title.into(),
+            extensions,
+            visible: false,
+            path_input: current_dir.display().to_string(),
+            current_dir,
+            entries: Vec::new(),
+            selected: None,
+            error: None,
+            save_mode: false,
+            file_name: String::new(),
+        }
+    }
+
+    /// 切换为保存模式
+    pub fn saving(mut self) -> Self {
+        self.save_mode = true;
+        self
+    }
+
+    /// 设置保存模式下默认的文件名
+    pub fn set_file_name(&mut self, name: impl Into<String>) {
+        self.file_name = name.into();
+    }
+
+    /// 设置保存模式下默认的文件：打开时进入其所在目录并填入文件名
+    pub fn set_path(&mut self, path: &Path) {
+        if let Some(dir) = path.parent().filter(|d| d.is_dir()) {
+            self.current_dir = dir.to_path_buf();
+        }
+        if let Some(name) = path.file_name() {
+            self.file_name = name.to_string_lossy().into_owned();
+        }
+    }
+
+    /// 打开窗口
+    pub fn open(&mut self) {
+        self.visible = true;
+        self.selected = None;
+        let dir = self.current_dir.clone();
+        self.change_dir(dir);
+    }
+
+    /// 窗口是否正在显示
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// 显示窗口，用户确认选择后返回文件路径
+    pub fn show(&mut self, ctx: &Context) -> Option<PathBuf> {
+        if !self.visible {
+            return None;
+        }
+
+        let mut picked = None;
+        let mut visible = self.visible;
+        egui::Window::new(self.title.clone())
+            .open(&mut visible)
+            .collapsible(false)
+            .default_size([480.0, 360.0])
+            .show(ctx, |ui| {
+                picked = self.contents(ui);
+            });
+
+        // 点击 Cancel 会在 contents 中把 visible 置为 false
+        self.visible &= visible && picked.is_none();
+        picked
+    }
+
+    fn contents(&mut self, ui: &mut Ui) -> Option<PathBuf> {
+        let mut picked = None;
+        let mut goto = None;
+
+        // 地址栏
+        ui.horizontal(|ui| {
+            if ui.button("⬆").on_hover_text("Parent directory").clicked()
+                && let Some(parent) = self.current_dir.parent()
+            {
+                goto = Some(parent.to_path_buf());
+            }
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.path_input).desired_width(f32::INFINITY),
+            );
+            if response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                let path = PathBuf::from(self.path_input.trim());
+                if path.is_dir() {
+                    goto = Some(path);
+                } else if self.save_mode || self.accepts(&path) {
+                    picked = Some(path);
+                }
+            }
+        });
+        ui.separator();
+
+        // 目录内容
+        let bottom_height = ui.spacing().interact_size.y * 2.0;
+        ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .max_height(ui.available_height() - bottom_height)
+            .show(ui, |ui| {
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                for entry in &self.entries {
+                    let label = if entry.is_dir {
+                        format!("📁 {}", entry.name)
+                    } else {
+                        format!("🖼 {}", entry.name)
+                    };
+                    let is_selected = self.selected.as_deref() == Some(entry.path.as_path());
+                    let response = ui.selectable_label(is_selected, label);
+                    if response.double_clicked() {
+                        if entry.is_dir {
+                            goto = Some(entry.path.clone());
+                        } else {
+                            picked = Some(entry.path.clone());
+                        }
+                    } else if response.clicked() && !entry.is_dir {
+                        self.selected = Some(entry.path.clone());
+                        if self.save_mode {
+                            self.file_name = entry.name.clone();
+                        }
+                    }
+                }
+            });
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            if self.save_mode {
+                ui.label("File name:");
+                ui.add(egui::TextEdit::singleline(&mut self.file_name).desired_width(200.0));
+            } else {
+                let name = self
+                    .selected
+                    .as_deref()
+                    .and_then(Path::file_name)
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                ui.label(name);
+            }
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("Cancel").clicked() {
+                    self.visible = false;
+                }
+                if self.save_mode {
+                    let name = self.file_name.trim();
+                    if ui.add_enabled(!name.is_empty(), egui::Button::new("Save")).clicked() {
+                        picked = Some(self.save_path(name));
+                    }
+                } else {
+                    let open = ui.add_enabled(self.selected.is_some(), egui::Button::new("Open"));
+                    if open.clicked() {
+                        picked = self.selected.clone();
+                    }
+                }
+            });
+        });
+
+        if let Some(dir) = goto {
+            self.change_dir(dir);
+        }
+        picked
+    }
+
+    /// 切换目录并刷新条目
+    fn change_dir(&mut self, dir: PathBuf) {
+        self.entries.clear();
+        self.selected = None;
+        self.error = None;
+
+        match std::fs::read_dir(&dir) {
+            Ok(read_dir) => {
+                for entry in read_dir.flatten() {
+                    let path = entry.path();
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    // 隐藏文件不显示
+                    if name.starts_with('.') {
+                        continue;
+                    }
+                    let is_dir = path.is_dir();
+                    if is_dir || self.accepts(&path) {
+                        self.entries.push(DirEntry { path, name, is_dir });
+                    }
+                }
+                self.entries.sort_by(|a, b| {
+                    b.is_dir
+                        .cmp(&a.is_dir)
+                        .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+                });
+            }
+            Err(err) => self.error = Some(format!("{}: {err}", dir.display())),
+        }
+
+        self.path_input = dir.display().to_string();
+        self.current_dir = dir;
+    }
+
+    /// 保存路径，文件名没有扩展名时加上第一个允许的扩展名
+    fn save_path(&self, name: &str) -> PathBuf {
+        let mut path = self.current_dir.join(name);
+        if path.extension().is_none()
+            && let Some(ext) = self.extensions.first()
+        {
+            path.set_extension(ext);
+        }
+        path
+    }
+
+    /// 文件扩展名是否符合过滤条件
+    fn accepts(&self, path: &Path) -> bool {
+        if self.extensions.is_empty() {
+            return path.is_file();
+        }
+        path.extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .is_some_and(|ext| self.extensions.contains(&ext.as_str()))
+    }
+}
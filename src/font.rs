@@ -1,9 +1,10 @@
-use std::sync::Arc;
+use std::sync::{Arc, mpsc::Sender};
 
-use ab_glyph::{Font, PxScale, ScaleFont};
-use font_kit::{family_name::FamilyName, properties::Properties, source::SystemSource};
+use ab_glyph::{Font, FontArc, FontVec, GlyphId, PxScale, ScaleFont};
+use font_kit::{family_name::FamilyName, handle::Handle, properties::Properties, source::SystemSource};
 
-const DEFAULT_FONT_NAMES: &[&str] = &[
+/// 默认的后备字体链
+pub const DEFAULT_FONT_NAMES: &[&str] = &[
     "HarmonyOS Sans SC",
     "Noto Sans CJK SC Regular",
     "Microsoft YaHei UI",
@@ -11,51 +12,260 @@ const DEFAULT_FONT_NAMES: &[&str] = &[
     "Arial Unicode MS",
 ];
 
-pub fn try_load_font_data_from_system() -> Option<(&'static [u8], String)> {
-    for &family_name in DEFAULT_FONT_NAMES {
-        let source = SystemSource::new();
-        let handle = source
-            .select_best_match(
-                &[FamilyName::Title(family_name.to_string())],
-                &Properties::new(),
-            )
-            .ok()?;
-    
-        let font = handle.load().ok()?;
-        let data = font.copy_font_data()?;
-        let data = Box::leak((*data).clone().into_boxed_slice());
-        return Some((data, family_name.to_owned()));
-    }
-    None
-}
-
-pub fn init_egui_fonts(cc: &eframe::CreationContext<'_>, font_data: Option<(&'static [u8], String)>) {
-    if let Some((data, font_name)) = font_data {
-        let mut fonts = egui::FontDefinitions::default();
-        fonts.font_data.insert(
-            font_name.clone(),
-            Arc::new(egui::FontData::from_static(data)),
-        );
-        
-        fonts.families
-            .entry(egui::FontFamily::Proportional)
-            .or_default()
-            .insert(0, font_name.clone());
-    
-        fonts.families
-            .entry(egui::FontFamily::Monospace)
-            .or_default()
-            .insert(0, font_name.clone());
-        
-        cc.egui_ctx.set_fonts(fonts);
-    }
-}
-
-pub fn measure_text<F: Font>(font: &F, scale: PxScale, text: &str) -> (f32, f32) {
-    let scaled = font.as_scaled(scale);
-    let width: f32 = text.chars()
-        .map(|c| scaled.h_advance(font.glyph_id(c)))
-        .sum();
-    let height = scaled.ascent() - scaled.descent();
-    (width, height)
-}
\ No newline at end of file
+/// 内置的后备字体（Ubuntu Light，Ubuntu Font Licence），系统字体都找不到时使用
+const BUNDLED_FONT: &[u8] = include_bytes!("../assets/fonts/Ubuntu-Light.ttf");
+const BUNDLED_FONT_NAME: &str = "Ubuntu Light (bundled)";
+const BUNDLED_FONT_POSTSCRIPT_NAME: &str = "Ubuntu-Light";
+
+/// 已加载的字体
+pub struct LoadedFont {
+    /// 字体族名称
+    pub name: String,
+    /// 字体的 PostScript 名称
+    pub postscript_name: Option<String>,
+    /// 字体文件数据和字体序号，直接注册到 egui，重新注册时不再复制
+    data: Arc<egui::FontData>,
+    font: FontArc,
+}
+
+impl LoadedFont {
+    /// 字体文件数据
+    pub fn data(&self) -> &[u8] {
+        &self.data.font
+    }
+
+    /// 字体在 `.ttc` 字体集合中的序号，单个字体文件为 0
+    pub fn index(&self) -> u32 {
+        self.data.index
+    }
+
+    pub fn font(&self) -> &FontArc {
+        &self.font
+    }
+}
+
+/// 标注使用的字体：主字体和按顺序排列的后备字体
+///
+/// 主字体缺少的字形（CJK、符号、Emoji 等）依次从后备字体中查找
+#[derive(Default)]
+pub struct FontSet {
+    fonts: Vec<LoadedFont>,
+}
+
+impl FontSet {
+    /// 加载主字体和后备字体，找不到的字体会被跳过
+    ///
+    /// 字体链末尾总是加上内置字体，保证至少有一个字体可用
+    pub fn load(primary: Option<&str>, fallbacks: &[String]) -> Self {
+        let mut fonts: Vec<LoadedFont> = Vec::new();
+        let names = primary
+            .into_iter()
+            .chain(fallbacks.iter().map(String::as_str));
+        for name in names {
+            if fonts.iter().any(|f| f.name == name) {
+                continue;
+            }
+            if let Some(font) = load_system_font(name) {
+                fonts.push(font);
+            }
+        }
+        fonts.push(bundled_font());
+        Self { fonts }
+    }
+
+    /// 已加载的字体，第一个为主字体
+    pub fn fonts(&self) -> &[LoadedFont] {
+        &self.fonts
+    }
+
+    /// 包含字符 `c` 的第一个字体，都不包含时返回主字体
+    pub fn font_for(&self, c: char) -> Option<&FontArc> {
+        self.index_for(c).map(|i| &self.fonts[i].font)
+    }
+
+    /// 包含字符 `c` 的第一个字体在字体链中的序号，都不包含时为主字体
+    pub fn index_for(&self, c: char) -> Option<usize> {
+        self.fonts
+            .iter()
+            .position(|f| f.font.glyph_id(c) != GlyphId(0))
+            .or_else(|| (!self.fonts.is_empty()).then_some(0))
+    }
+
+    /// 主字体，用于确定行高和基线
+    pub fn primary(&self) -> Option<&FontArc> {
+        self.fonts.first().map(|f| &f.font)
+    }
+
+    /// 测量单行文字的宽高
+    pub fn measure_text(&self, scale: PxScale, text: &str) -> (f32, f32) {
+        let Some(primary) = self.primary() else {
+            return (0.0, 0.0);
+        };
+        let width: f32 = text.chars().map(|c| self.advance(scale, c)).sum();
+        let scaled = primary.as_scaled(scale);
+        (width, scaled.ascent() - scaled.descent())
+    }
+
+    /// 字符的前进宽度
+    fn advance(&self, scale: PxScale, c: char) -> f32 {
+        self.font_for(c)
+            .map(|font| font.as_scaled(scale).h_advance(font.glyph_id(c)))
+            .unwrap_or(0.0)
+    }
+
+    /// 行距：主字体的上升、下降和行间距之和
+    pub fn line_height(&self, scale: PxScale) -> f32 {
+        self.primary()
+            .map(|font| {
+                let scaled = font.as_scaled(scale);
+                scaled.ascent() - scaled.descent() + scaled.line_gap()
+            })
+            .unwrap_or(scale.y)
+    }
+
+    /// 将文字拆分为行：换行符处换行，指定 `max_width` 时超宽自动换行
+    ///
+    /// 优先在空白处断开，没有空白（如中文）时在字符间断开
+    pub fn wrap_lines(&self, scale: PxScale, text: &str, max_width: Option<f32>) -> Vec<String> {
+        let mut lines = Vec::new();
+        for paragraph in text.split('\n') {
+            let Some(max_width) = max_width else {
+                lines.push(paragraph.to_owned());
+                continue;
+            };
+            let mut line = String::new();
+            let mut width = 0.0;
+            // 行内最后一个空白的位置
+            let mut last_space: Option<usize> = None;
+            for c in paragraph.chars() {
+                let advance = self.advance(scale, c);
+                if !line.is_empty() && !c.is_whitespace() && width + advance > max_width {
+                    match last_space {
+                        // 空白之前没有内容时断开会得到空行，按超长单词处理
+                        Some(idx) if !line[..idx].trim().is_empty() => {
+                            let rest = line.split_off(idx);
+                            lines.push(line);
+                            line = rest.trim_start().to_owned();
+                            width = line.chars().map(|c| self.advance(scale, c)).sum();
+                        }
+                        _ => {
+                            lines.push(std::mem::take(&mut line));
+                            width = 0.0;
+                        }
+                    }
+                    // 空白位置相对于旧行，新行里的内容都在空白之后
+                    last_space = None;
+                }
+                if c.is_whitespace() {
+                    last_space = Some(line.len());
+                }
+                line.push(c);
+                width += advance;
+            }
+            lines.push(line);
+        }
+        lines
+    }
+}
+
+/// 从系统中加载指定字体族
+fn load_system_font(family_name: &str) -> Option<LoadedFont> {
+    let handle = SystemSource::new()
+        .select_best_match(
+            &[FamilyName::Title(family_name.to_string())],
+            &Properties::new(),
+        )
+        .ok()?;
+    let index = match &handle {
+        Handle::Path { font_index, .. } | Handle::Memory { font_index, .. } => *font_index,
+    };
+    // ab_glyph 和 egui 都要求独占字体数据，各持有一份
+    let system_font = handle.load().ok()?;
+    let postscript_name = system_font.postscript_name();
+    let bytes = Arc::unwrap_or_clone(system_font.copy_font_data()?);
+    let font = FontVec::try_from_vec_and_index(bytes, index).ok()?;
+    let mut data = egui::FontData::from_owned(font.as_slice().to_vec());
+    data.index = index;
+    Some(LoadedFont {
+        name: family_name.to_owned(),
+        postscript_name,
+        data: Arc::new(data),
+        font: FontArc::new(font),
+    })
+}
+
+fn bundled_font() -> LoadedFont {
+    LoadedFont {
+        name: BUNDLED_FONT_NAME.to_owned(),
+        postscript_name: Some(BUNDLED_FONT_POSTSCRIPT_NAME.to_owned()),
+        data: Arc::new(egui::FontData::from_static(BUNDLED_FONT)),
+        font: FontArc::try_from_slice(BUNDLED_FONT).expect("bundled font is valid"),
+    }
+}
+
+/// 在后台线程中加载字体链，完成后发送到 `tx` 并请求重绘
+pub fn spawn_load(ctx: &egui::Context, tx: Sender<FontSet>, primary: Option<String>, fallbacks: Vec<String>) {
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        let _ = tx.send(FontSet::load(primary.as_deref(), &fallbacks));
+        ctx.request_repaint();
+    });
+}
+
+/// 在后台线程中读取系统字体族列表，完成后发送到 `tx` 并请求重绘
+pub fn spawn_system_families(ctx: &egui::Context, tx: Sender<Vec<String>>) {
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        let _ = tx.send(system_families());
+        ctx.request_repaint();
+    });
+}
+
+/// 系统中所有字体族名称（已排序）
+fn system_families() -> Vec<String> {
+    let mut families = SystemSource::new().all_families().unwrap_or_default();
+    families.sort_by_key(|name| name.to_lowercase());
+    families.dedup();
+    families
+}
+
+/// 将字体链注册到 egui，按顺序放在默认字体之前
+pub fn init_egui_fonts(ctx: &egui::Context, font_set: &FontSet) {
+    let mut fonts = egui::FontDefinitions::default();
+    for (i, loaded) in font_set.fonts().iter().enumerate() {
+        fonts.font_data.insert(loaded.name.clone(), loaded.data.clone());
+
+        for family in [egui::FontFamily::Proportional, egui::FontFamily::Monospace] {
+            fonts
+                .families
+                .entry(family)
+                .or_default()
+                .insert(i, loaded.name.clone());
+        }
+    }
+    ctx.set_fonts(fonts);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_lines_mixes_long_words_and_spaces() {
+        let fonts = FontSet { fonts: vec![bundled_font()] };
+        let scale = PxScale::from(20.0);
+        let max_width = fonts.measure_text(scale, "abcdef").0;
+        let text = "  abcdefghijklmno ab cd efghijklmnopqrstu v";
+        let lines = fonts.wrap_lines(scale, text, Some(max_width));
+
+        for line in &lines {
+            assert!(!line.trim().is_empty(), "{lines:?}");
+            assert!(fonts.measure_text(scale, line.trim_end()).0 <= max_width, "{line:?} in {lines:?}");
+        }
+        // 只在空白处或超长单词内部断开，不丢字符
+        let joined: String = lines.concat().split_whitespace().collect();
+        let expected: String = text.split_whitespace().collect();
+        assert_eq!(joined, expected);
+        assert!(lines.contains(&"ab cd".to_owned()), "{lines:?}");
+    }
+}
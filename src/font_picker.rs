@@ -0,0 +1,175 @@
+use std::sync::mpsc::{self, Receiver};
+
+use egui::{Context, Ui};
+
+use crate::font;
+
+/// 字体选择窗口：主字体和后备字体链
+#[derive(Default)]
+pub struct FontPicker {
+    /// 是否显示窗口
+    show_window: bool,
+    /// 系统字体族列表（首次打开时在后台读取）
+    families: Option<Vec<String>>,
+    /// 正在后台读取的字体族列表
+    families_receiver: Option<Receiver<Vec<String>>>,
+    /// 字体列表的过滤关键字
+    filter: String,
+    /// 主字体，None 表示直接使用后备字体链
+    pub primary: Option<String>,
+    /// 按顺序排列的后备字体
+    pub fallbacks: Vec<String>,
+}
+
+impl FontPicker {
+    pub fn new(primary: Option<String>, fallbacks: Vec<String>) -> Self {
+        Self {
+            primary,
+            fallbacks,
+            ..Default::default()
+        }
+    }
+
+    /// 打开窗口
+    pub fn open(&mut self) {
+        self.show_window = true;
+    }
+
+    /// 显示窗口，字体设置改变时返回 true
+    pub fn show(&mut self, ctx: &Context) -> bool {
+        if !self.show_window {
+            return false;
+        }
+        if self.families.is_none() {
+            match &self.families_receiver {
+                Some(rx) => self.families = rx.try_recv().ok(),
+                None => {
+                    let (tx, rx) = mpsc::channel();
+                    font::spawn_system_families(ctx, tx);
+                    self.families_receiver = Some(rx);
+                }
+            }
+        }
+
+        let mut changed = false;
+        let mut open = self.show_window;
+        egui::Window::new("Fonts")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                changed = self.contents(ui);
+            });
+        self.show_window = open;
+        changed
+    }
+
+    fn contents(&mut self, ui: &mut Ui) -> bool {
+        let mut changed = false;
+
+        // 主字体
+        ui.heading("Primary Font");
+        ui.add_space(5.0);
+        ui.horizontal(|ui| {
+            let label = self.primary.as_deref().unwrap_or("(first available fallback)");
+            ui.label(label);
+            if self.primary.is_some() && ui.small_button("×").on_hover_text("Clear").clicked() {
+                self.primary = None;
+                changed = true;
+            }
+        });
+
+        ui.add_space(10.0);
+        ui.separator();
+        ui.add_space(5.0);
+
+        // 后备字体链
+        ui.heading("Fallback Chain");
+        ui.label("Used in order for glyphs missing from the primary font.");
+        ui.add_space(5.0);
+        let mut action = None;
+        let len = self.fallbacks.len();
+        for (i, name) in self.fallbacks.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{}. {name}", i + 1));
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.small_button("×").on_hover_text("Remove").clicked() {
+                        action = Some(ChainAction::Remove(i));
+                    }
+                    if ui.add_enabled(i + 1 < len, egui::Button::new("⏷").small()).clicked() {
+                        action = Some(ChainAction::Swap(i, i + 1));
+                    }
+                    if ui.add_enabled(i > 0, egui::Button::new("⏶").small()).clicked() {
+                        action = Some(ChainAction::Swap(i - 1, i));
+                    }
+                });
+            });
+        }
+        match action {
+            Some(ChainAction::Remove(i)) => {
+                self.fallbacks.remove(i);
+                changed = true;
+            }
+            Some(ChainAction::Swap(a, b)) => {
+                self.fallbacks.swap(a, b);
+                changed = true;
+            }
+            None => {}
+        }
+        if ui.button("Reset to defaults").clicked() {
+            self.fallbacks = font::DEFAULT_FONT_NAMES.iter().map(|s| s.to_string()).collect();
+            changed = true;
+        }
+
+        ui.add_space(10.0);
+        ui.separator();
+        ui.add_space(5.0);
+
+        // 系统字体列表
+        ui.heading("System Fonts");
+        ui.add_space(5.0);
+        ui.add(egui::TextEdit::singleline(&mut self.filter).hint_text("Search"));
+        if self.families.is_none() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Loading system fonts…");
+            });
+        }
+        let filter = self.filter.to_lowercase();
+        egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+            for family in self.families.iter().flatten() {
+                if !family.to_lowercase().contains(&filter) {
+                    continue;
+                }
+                ui.horizontal(|ui| {
+                    ui.label(family);
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        let in_chain = self.fallbacks.contains(family);
+                        if ui
+                            .add_enabled(!in_chain, egui::Button::new("+ Fallback").small())
+                            .clicked()
+                        {
+                            self.fallbacks.push(family.clone());
+                            changed = true;
+                        }
+                        let is_primary = self.primary.as_ref() == Some(family);
+                        if ui
+                            .add_enabled(!is_primary, egui::Button::new("Use").small())
+                            .clicked()
+                        {
+                            self.primary = Some(family.clone());
+                            changed = true;
+                        }
+                    });
+                });
+            }
+        });
+
+        changed
+    }
+}
+
+enum ChainAction {
+    Remove(usize),
+    Swap(usize, usize),
+}
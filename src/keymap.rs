@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use egui::{Context, Key, KeyboardShortcut, Modifiers};
+
+use crate::{config, toolbar::Tool};
+
+/// 快捷键配置文件名
+const KEYMAP_FILE: &str = "keymap.toml";
+
+/// 可以绑定快捷键的动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// 切换到指定工具
+    Tool(Tool),
+    Undo,
+    SaveAll,
+    NextStrokeWidth,
+    PrevStrokeWidth,
+    NextColor,
+    PrevColor,
+    NudgeLeft,
+    NudgeRight,
+    NudgeUp,
+    NudgeDown,
+    /// 选择类别文件中的第 n 个类别（从 0 开始）
+    Label(usize),
+}
+
+impl Action {
+    /// 所有动作及其在配置文件中的名称、默认快捷键
+    ///
+    /// 尚未实现的工具（Emoji、Pin、Copy）不在表中，避免按键后创建标注时 panic
+    const ALL: &[(Action, &str, &str)] = &[
+        (Action::Tool(Tool::Select), "select", "V"),
+        (Action::Tool(Tool::Rectangle), "rectangle", "R"),
+        (Action::Tool(Tool::Circle), "circle", "E"),
+        (Action::Tool(Tool::Arrow), "arrow", "A"),
+        (Action::Tool(Tool::Line), "line", "L"),
+        (Action::Tool(Tool::Pencil), "pencil", "P"),
+        (Action::Tool(Tool::Number), "number", "N"),
+        (Action::Tool(Tool::Text), "text", "T"),
+        (Action::Tool(Tool::Callout), "callout", "B"),
+        (Action::Tool(Tool::Polygon), "polygon", "G"),
+        (Action::Tool(Tool::Masaic), "mosaic", "M"),
+        (Action::Tool(Tool::Save), "save", "Ctrl+S"),
+        (Action::Tool(Tool::Open), "open", "Ctrl+O"),
+        (Action::Tool(Tool::Eyedropper), "eyedropper", "I"),
+        (Action::Undo, "undo", "Ctrl+Z"),
+        (Action::SaveAll, "save_all", "Ctrl+Shift+S"),
+        (Action::NextStrokeWidth, "next_stroke_width", "]"),
+        (Action::PrevStrokeWidth, "prev_stroke_width", "["),
+        (Action::NextColor, "next_color", "."),
+        (Action::PrevColor, "prev_color", ","),
+        (Action::NudgeLeft, "nudge_left", "ArrowLeft"),
+        (Action::NudgeRight, "nudge_right", "ArrowRight"),
+        (Action::NudgeUp, "nudge_up", "ArrowUp"),
+        (Action::NudgeDown, "nudge_down", "ArrowDown"),
+        (Action::Label(0), "label_1", "1"),
+        (Action::Label(1), "label_2", "2"),
+        (Action::Label(2), "label_3", "3"),
+        (Action::Label(3), "label_4", "4"),
+        (Action::Label(4), "label_5", "5"),
+        (Action::Label(5), "label_6", "6"),
+        (Action::Label(6), "label_7", "7"),
+        (Action::Label(7), "label_8", "8"),
+        (Action::Label(8), "label_9", "9"),
+    ];
+}
+
+/// 快捷键表
+pub struct KeyMap {
+    bindings: HashMap<Action, KeyboardShortcut>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let bindings = Action::ALL
+            .iter()
+            .filter_map(|&(action, _, shortcut)| Some((action, parse_shortcut(shortcut)?)))
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl KeyMap {
+    /// 读取配置目录下的 `keymap.toml`，覆盖默认快捷键
+    ///
+    /// 文件格式为 `动作名 = "快捷键"`，例如 `rectangle = "Shift+R"`，空字符串表示取消绑定
+    pub fn load() -> Self {
+        let mut keymap = Self::default();
+        let Some(path) = config::config_file(KEYMAP_FILE) else {
+            return keymap;
+        };
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return keymap;
+        };
+
+        let table = match content.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(err) => {
+                eprintln!("Failed to parse {}: {err}", path.display());
+                return keymap;
+            }
+        };
+        for (name, value) in table {
+            let Some(&(action, ..)) = Action::ALL.iter().find(|(_, n, _)| *n == name) else {
+                eprintln!("Unknown action `{name}` in {}", path.display());
+                continue;
+            };
+            match value.as_str() {
+                Some("") => {
+                    keymap.bindings.remove(&action);
+                }
+                Some(shortcut) => match parse_shortcut(shortcut) {
+                    Some(shortcut) => {
+                        keymap.bindings.insert(action, shortcut);
+                    }
+                    None => eprintln!("Invalid shortcut `{shortcut}` for `{name}`"),
+                },
+                None => eprintln!("Shortcut for `{name}` must be a string"),
+            }
+        }
+        keymap
+    }
+
+    /// 动作当前绑定的快捷键
+    pub fn shortcut(&self, action: Action) -> Option<&KeyboardShortcut> {
+        self.bindings.get(&action)
+    }
+
+    /// 提示文字后附上动作当前绑定的快捷键
+    pub fn hint(&self, ctx: &Context, action: Action, text: &str) -> String {
+        match self.shortcut(action) {
+            Some(shortcut) => format!("{text} ({})", ctx.format_shortcut(shortcut)),
+            None => text.to_owned(),
+        }
+    }
+
+    /// 本帧触发的动作
+    ///
+    /// 输入框获得焦点时所有按键都留给输入框，Ctrl+Z 等撤销的是输入而不是标注
+    pub fn triggered(&self, ctx: &Context) -> Vec<Action> {
+        if ctx.wants_keyboard_input() {
+            return Vec::new();
+        }
+
+        // 修饰键多的优先匹配，避免 Ctrl+S 抢走 Ctrl+Shift+S
+        let mut bindings: Vec<_> = self.bindings.iter().collect();
+        bindings.sort_by_key(|(_, s)| std::cmp::Reverse(modifier_count(s.modifiers)));
+
+        ctx.input_mut(|i| {
+            bindings
+                .into_iter()
+                .filter(|(_, s)| i.consume_shortcut(s))
+                .map(|(&action, _)| action)
+                .collect()
+        })
+    }
+}
+
+fn modifier_count(m: Modifiers) -> usize {
+    [m.alt, m.ctrl || m.command, m.shift, m.mac_cmd]
+        .into_iter()
+        .filter(|&b| b)
+        .count()
+}
+
+/// 解析形如 `Ctrl+Shift+S` 的快捷键
+fn parse_shortcut(text: &str) -> Option<KeyboardShortcut> {
+    let mut modifiers = Modifiers::NONE;
+    let mut key = None;
+    for part in text.split('+').map(str::trim) {
+        match part.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => modifiers |= Modifiers::COMMAND,
+            "shift" => modifiers |= Modifiers::SHIFT,
+            "alt" | "option" => modifiers |= Modifiers::ALT,
+            _ if key.is_none() => key = Key::from_name(part),
+            _ => return None,
+        }
+    }
+    Some(KeyboardShortcut::new(modifiers, key?))
+}
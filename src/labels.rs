@@ -0,0 +1,177 @@
+use std::path::{Path, PathBuf};
+
+use egui::{Color32, Context, Sense, Ui, ecolor::HsvaGamma};
+
+use crate::{dataset::DEFAULT_LABEL, toolbar::Tool};
+
+/// 标签面板中可以用数字键选择的类别数量
+pub const LABEL_SHORTCUTS: usize = 9;
+
+/// 用户定义的数据集类别，从文本文件加载
+#[derive(Debug, Clone, Default)]
+pub struct LabelSet {
+    /// 加载的文件
+    pub path: Option<PathBuf>,
+    pub names: Vec<String>,
+}
+
+impl LabelSet {
+    /// 读取类别文件：每行一个类别，忽略空行和 `#` 开头的注释，重复的类别只保留第一个
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut names: Vec<String> = Vec::new();
+        for line in content.lines().map(str::trim) {
+            if !line.is_empty() && !line.starts_with('#') && !names.iter().any(|n| n == line) {
+                names.push(line.to_owned());
+            }
+        }
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            names,
+        })
+    }
+}
+
+/// 类别颜色：按名称的哈希取色相，同一类别在任何图片和类别文件中颜色一致
+pub fn label_color(name: &str) -> Color32 {
+    // FNV-1a
+    let hash = name
+        .bytes()
+        .fold(0x811c_9dc5_u32, |h, b| (h ^ u32::from(b)).wrapping_mul(0x0100_0193));
+    let hue = hash as f32 / u32::MAX as f32;
+    HsvaGamma {
+        h: hue,
+        s: 0.75,
+        v: 0.9,
+        a: 1.0,
+    }
+    .into()
+}
+
+impl crate::AnnotatorApp {
+    /// 右侧标签面板：列出类别及当前标签页中的数量，单击设为当前类别
+    pub fn label_panel(&mut self, ctx: &Context) {
+        if !self.settings.show_labels {
+            return;
+        }
+
+        // 每个类别的标注数量，类别文件之外、标注上已有的类别排在后面
+        let mut names = self.labels.names.clone();
+        let mut counts = vec![0; names.len()];
+        let mut unlabelled = 0;
+        if let Some(doc) = self.documents.get(self.active) {
+            for op in doc.operators.iter().filter(|op| op.supports_label()) {
+                let Some(label) = op.label.as_deref() else {
+                    unlabelled += 1;
+                    continue;
+                };
+                let index = names.iter().position(|n| n == label).unwrap_or_else(|| {
+                    names.push(label.to_owned());
+                    counts.push(0);
+                    names.len() - 1
+                });
+                counts[index] += 1;
+            }
+        }
+
+        let mut pick = None;
+        let mut load = false;
+        egui::SidePanel::right("labels")
+            .resizable(true)
+            .default_width(180.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("Labels");
+                    load = ui
+                        .button("Load...")
+                        .on_hover_text("Load labels from a text file, one per line")
+                        .clicked();
+                });
+                if let Some(path) = &self.labels.path {
+                    ui.small(path.display().to_string());
+                }
+                ui.separator();
+
+                let active = self.current_tool_info.label.as_deref();
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (i, (name, &count)) in names.iter().zip(&counts).enumerate() {
+                        let shortcut = (i < self.labels.names.len() && i < LABEL_SHORTCUTS).then_some(i + 1);
+                        if label_row(ui, name, shortcut, count, active == Some(name)) {
+                            pick = Some(name.clone());
+                        }
+                    }
+                    if unlabelled > 0 {
+                        ui.separator();
+                        ui.weak(format!("{unlabelled} without label ({DEFAULT_LABEL})"));
+                    }
+                    if names.is_empty() {
+                        ui.weak("No labels yet");
+                    }
+                });
+            });
+
+        if load {
+            self.label_dialog.open();
+        }
+        if let Some(name) = pick {
+            self.set_label(Some(name));
+        }
+    }
+
+    /// 按数字键选择类别文件中的第 `index` 个类别
+    pub fn select_label(&mut self, index: usize) {
+        if let Some(name) = self.labels.names.get(index).cloned() {
+            self.set_label(Some(name));
+        }
+    }
+
+    /// 设置当前类别：选择工具下同时修改选中的标注，其他工具下切换到矩形工具
+    fn set_label(&mut self, label: Option<String>) {
+        let info = &mut self.current_tool_info;
+        if info.tool == Tool::Select
+            && let Some(op) = self
+                .documents
+                .get_mut(self.active)
+                .and_then(|doc| doc.operators.get_mut(doc.selected?))
+                .filter(|op| op.supports_label())
+        {
+            op.set_label(label.clone());
+        } else if !info.tool.supports_label() {
+            self.select_tool(Tool::Rectangle);
+        }
+        self.current_tool_info.label = label;
+    }
+
+    /// 选择类别文件后加载，并记入设置
+    pub fn handle_label_dialog(&mut self, ctx: &Context) {
+        let Some(path) = self.label_dialog.show(ctx) else {
+            return;
+        };
+        match LabelSet::load(&path) {
+            Ok(labels) => {
+                self.labels = labels;
+                self.settings.label_file = Some(path);
+                self.settings.show_labels = true;
+            }
+            Err(err) => eprintln!("Failed to load labels {}: {err}", path.display()),
+        }
+    }
+}
+
+/// 一行类别：色块、快捷键、名称和数量，返回是否被单击
+fn label_row(ui: &mut Ui, name: &str, shortcut: Option<usize>, count: usize, selected: bool) -> bool {
+    ui.horizontal(|ui| {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(12.0, 12.0), Sense::hover());
+        ui.painter().rect_filled(rect, 2.0, label_color(name));
+        let text = match shortcut {
+            Some(key) => format!("{key}  {name}"),
+            None => name.to_owned(),
+        };
+        let clicked = ui.selectable_label(selected, text).clicked();
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            ui.label(count.to_string());
+        });
+        clicked
+    })
+    .inner
+}
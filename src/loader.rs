@@ -0,0 +1,83 @@
+use std::{path::PathBuf, sync::Arc, sync::mpsc::Sender};
+
+use egui::{Context, Vec2};
+use image::RgbaImage;
+
+/// 加载完成的图片
+pub struct LoadedImage {
+    pub image: RgbaImage,
+    pub size: Vec2,
+    /// 显示在标签页上的名称
+    pub name: String,
+    /// 图片来源路径
+    pub path: Option<PathBuf>,
+}
+
+/// 后台加载结果，失败时为错误信息
+pub type LoadResult = Result<LoadedImage, String>;
+
+/// 图片来源
+pub enum ImageSource {
+    /// 本地文件
+    Path(PathBuf),
+    /// 编码后的图片数据（如拖放时只提供了字节）
+    Bytes(Arc<[u8]>),
+    /// 系统剪贴板中的图片
+    Clipboard,
+}
+
+impl ImageSource {
+    fn name(&self) -> String {
+        match self {
+            ImageSource::Path(path) => path
+                .file_name()
+                .map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned()),
+            ImageSource::Bytes(_) => "dropped".to_owned(),
+            ImageSource::Clipboard => "clipboard".to_owned(),
+        }
+    }
+}
+
+/// 在后台线程中加载图片，完成后发送到 `tx` 并请求重绘
+pub fn spawn_load(ctx: &Context, tx: Sender<LoadResult>, source: ImageSource) {
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        let name = source.name();
+        let path = match &source {
+            ImageSource::Path(path) => Some(path.clone()),
+            _ => None,
+        };
+        let result = load(source).map(|image| {
+            let (w, h) = image.dimensions();
+            LoadedImage {
+                image,
+                size: Vec2::new(w as f32, h as f32),
+                name,
+                path,
+            }
+        });
+        let _ = tx.send(result);
+        ctx.request_repaint();
+    });
+}
+
+fn load(source: ImageSource) -> Result<RgbaImage, String> {
+    match source {
+        ImageSource::Path(path) => image::open(&path)
+            .map(|img| img.to_rgba8())
+            .map_err(|e| format!("{}: {e}", path.display())),
+        ImageSource::Bytes(bytes) => image::load_from_memory(&bytes)
+            .map(|img| img.to_rgba8())
+            .map_err(|e| e.to_string()),
+        ImageSource::Clipboard => {
+            let mut clipboard = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+            let data = clipboard.get_image().map_err(|e| e.to_string())?;
+            RgbaImage::from_raw(
+                data.width as u32,
+                data.height as u32,
+                data.bytes.into_owned(),
+            )
+            .ok_or_else(|| "Invalid clipboard image data".to_owned())
+        }
+    }
+}
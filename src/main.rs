@@ -1,274 +1,751 @@
 // #![allow(unused)]
 
-use std::sync::mpsc::{self, Receiver};
+use std::{
+    path::Path,
+    sync::mpsc::{self, Receiver, Sender},
+};
 
-use ab_glyph::FontRef;
-use eframe::{App, egui, wgpu};
-use egui::{Color32, ColorImage, Pos2, Rect, Sense, TextureHandle, Vec2};
+use eframe::{App, egui};
+use egui::{Color32, Pos2, Rect, Sense, Vec2};
 
+mod cli;
 mod color_picker;
+mod config;
+mod dataset;
+mod document;
 mod drawable;
+mod export;
+mod eyedropper;
+mod file_dialog;
 mod font;
+mod font_picker;
+mod keymap;
+mod labels;
+mod loader;
 mod operators;
+mod palette;
+mod pdf;
+mod project;
+mod renderer;
+mod settings;
+mod svg;
+mod text_editor;
+mod tiles;
 mod toolbar;
 mod utils;
-
-use color_picker::ColorPickerButton;
-use drawable::DrawImage;
-use image::RgbaImage;
-use operators::{Operator, ToolType};
-use toolbar::{Tool, ToolInfo};
+mod webp;
+
+use color_picker::{ColorPickerButton, PRESET_COLORS};
+use dataset::DatasetFormat;
+use document::Document;
+use eyedropper::Eyedropper;
+use export::{EXPORT_EXTENSIONS, ExportFormat, ExportOptions};
+use file_dialog::FileDialog;
+use font::FontSet;
+use font_picker::FontPicker;
+use keymap::{Action, KeyMap};
+use labels::LabelSet;
+use loader::{ImageSource, LoadResult, LoadedImage};
+use operators::{Operator, TextStyle, ToolType};
+use renderer::{Adapters, Renderer};
+use settings::{HexColor, Settings, WindowGeometry};
+use text_editor::{TextEditOutcome, TextEditState};
+use toolbar::{StrokeWidth, Tool, ToolInfo};
 use utils::AppHelper;
 
 fn main() -> Result<(), eframe::Error> {
+    let args = cli::Args::parse();
+
+    // 未指定后端时自动检测，没有 GPU 时使用软件渲染；只有这两种情况需要枚举适配器
+    // 图片按块上传纹理，不需要调大 max_texture_dimension_2d
+    let adapters = match args.renderer {
+        None | Some(Renderer::Software) => Adapters::enumerate(),
+        Some(_) => Adapters::default(),
+    };
+    let mut renderer = args.renderer.unwrap_or_else(|| Renderer::detect(adapters));
+    if renderer == Renderer::Software {
+        // 让 Mesa 使用 llvmpipe 软件 OpenGL（没有 CPU 适配器或退回 glow 时），对 wgpu 的 CPU 适配器没有影响
+        // SAFETY: 枚举适配器的 wgpu 实例已经释放，事件循环和窗口还没有创建，没有其他线程读写环境变量
+        unsafe { std::env::set_var("LIBGL_ALWAYS_SOFTWARE", "1") };
+    }
+    let settings = Settings::load();
+    loop {
+        match (run(renderer, adapters, args.clone(), settings.clone()), renderer.fallback()) {
+            // 事件循环创建失败（如没有显示器）与后端无关，winit 也不允许再次创建
+            (Err(err), Some(next)) if !matches!(err, eframe::Error::WinitEventLoop(_)) => {
+                eprintln!("{renderer:?} renderer failed: {err}; retrying with {next:?}");
+                renderer = next;
+            }
+            (result, _) => return result,
+        }
+    }
+}
+
+/// 用指定的后端运行窗口，后端初始化失败时返回错误
+fn run(renderer: Renderer, adapters: Adapters, args: cli::Args, settings: Settings) -> Result<(), eframe::Error> {
+    // 恢复上次的窗口位置和大小
+    let mut viewport = egui::ViewportBuilder::default();
+    if let Some(window) = settings.window {
+        viewport = viewport
+            .with_position([window.x, window.y])
+            .with_inner_size([window.width, window.height]);
+    }
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default(),
-        wgpu_options: eframe::egui_wgpu::WgpuConfiguration {
-            wgpu_setup: eframe::egui_wgpu::WgpuSetup::CreateNew(
-                eframe::egui_wgpu::WgpuSetupCreateNew {
-                    device_descriptor: std::sync::Arc::new(|_adapter| {
-                        wgpu::DeviceDescriptor {
-                            required_limits: wgpu::Limits {
-                                max_texture_dimension_2d: 8192, // 调大
-                                ..wgpu::Limits::default()
-                            },
-                            ..Default::default()
-                        }
-                    }),
-                    ..Default::default()
-                },
-            ),
-            ..Default::default()
-        },
-        ..Default::default()
+        viewport,
+        ..renderer.native_options(adapters)
     };
     eframe::run_native(
         "Annotator",
         options,
         Box::new(|cc| {
             egui_extras::install_image_loaders(&cc.egui_ctx);
-            Ok(Box::new(AnnotatorApp::new(cc)))
+            Ok(Box::new(AnnotatorApp::new(cc, args, settings)))
         }),
     )
 }
 
 #[derive(Default)]
 struct AnnotatorApp {
-    // 显示
-    texture: Option<TextureHandle>,
-    last_image_rect: Option<Rect>,
-    zoom: f32,
-    pan: Vec2,
-    font_data: Option<&'static [u8]>,
-    // 图片相关
-    image_size: Vec2,
-    original_image: Option<RgbaImage>,
-    image_receiver: Option<Receiver<(RgbaImage, Vec2)>>,
+    fonts: FontSet,
+    font_picker: FontPicker,
+    /// 正在后台加载的字体链，只接收最后一次修改的结果
+    font_receiver: Option<Receiver<FontSet>>,
+    // 标签页
+    documents: Vec<Document>,
+    active: usize,
+    // 图片加载
+    image_sender: Option<Sender<LoadResult>>,
+    image_receiver: Option<Receiver<LoadResult>>,
+    /// 正在后台加载的图片数量
+    loading: usize,
+    file_dialog: FileDialog,
+    /// 另存为窗口，按扩展名确定格式
+    save_dialog: FileDialog,
+    /// 把所有标签页保存为一个 PDF 的窗口
+    report_dialog: FileDialog,
+    /// 导出数据集标注的窗口
+    dataset_dialog: FileDialog,
+    /// 数据集窗口确认后使用的格式
+    dataset_format: DatasetFormat,
+    /// 数据集类别
+    labels: LabelSet,
+    /// 选择类别文件的窗口
+    label_dialog: FileDialog,
+    /// 打开工程文件的窗口
+    open_project_dialog: FileDialog,
+    /// 保存工程文件的窗口
+    save_project_dialog: FileDialog,
+    /// 导出选项，命令行参数只在本次运行有效
+    export: ExportOptions,
     // 工具相关
     color_picker: ColorPickerButton,
+    fill_picker: ColorPickerButton,
+    eyedropper: Eyedropper,
     current_tool_info: ToolInfo,
-    // 进行过的操作
-    operators: Vec<Operator>,
+    keymap: KeyMap,
+    /// 用户设置，退出时写回
+    settings: Settings,
 }
 
 impl AnnotatorApp {
-    fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        // 从命令行读取图片路径
-        let args: Vec<String> = std::env::args().collect();
-        let mut image_receiver = None;
+    fn new(cc: &eframe::CreationContext<'_>, args: cli::Args, settings: Settings) -> Self {
+        let (tx, rx) = mpsc::channel();
 
-        if args.len() > 1 {
-            let (tx, rx) = mpsc::channel();
-            let ctx = cc.egui_ctx.clone();
-            let path = args[1].clone();
-
-            std::thread::spawn(move || {
-                let img = image::open(&path)
-                    .expect("Failed to reopen image")
-                    .to_rgba8();
-                let dim = img.dimensions();
-                let image_size = Vec2::new(dim.0 as f32, dim.1 as f32);
-                tx.send((img, image_size)).unwrap();
-                ctx.request_repaint();
-            });
-            image_receiver = Some(rx);
-        }
-
-        let mut font_data = None;
-        if let Some((data, name)) = font::try_load_font_data_from_system() {
-            font::init_egui_fonts(cc, Some((data, name)));
-            font_data = Some(data);
+        // 打开命令行指定的图片，没有时等待用户打开
+        let loading = args.paths.len();
+        for path in args.paths {
+            loader::spawn_load(&cc.egui_ctx, tx.clone(), ImageSource::Path(path));
+        }
+
+        let fonts = FontSet::load(settings.font.as_deref(), &settings.font_fallbacks);
+        font::init_egui_fonts(&cc.egui_ctx, &fonts);
+        let font_picker = FontPicker::new(settings.font.clone(), settings.font_fallbacks.clone());
+
+        // 恢复上次使用的工具、颜色和线宽
+        let color = settings.color.0;
+        let mut color_picker = ColorPickerButton::new("ColorPicker", color);
+        color_picker.set_custom_colors(settings.palette.iter().map(|c| c.0).collect());
+        color_picker.set_recent_colors(settings.recent_colors.iter().map(|c| c.0).collect());
+        color_picker.set_palettes(settings.palettes.clone());
+        let fill_picker = ColorPickerButton::new("FillPicker", Color32::from_white_alpha(128))
+            .title("Fill Color");
+        let mut current_tool_info = ToolInfo {
+            stroke_width: settings.stroke_width.into(),
+            tool_colors: settings.tool_colors.clone(),
+            ..ToolInfo::new(color)
+        };
+        current_tool_info.switch_tool(settings.tool);
+
+        let labels = match &settings.label_file {
+            Some(path) => LabelSet::load(path).unwrap_or_else(|err| {
+                eprintln!("Failed to load labels {}: {err}", path.display());
+                LabelSet::default()
+            }),
+            None => LabelSet::default(),
+        };
+        let mut export = settings.export.clone();
+        if let Some(format) = args.format {
+            export.format = format;
+        }
+        // 指定了质量说明要有损编码，WebP 随之改为有损
+        if let Some(quality) = args.quality {
+            export.quality = quality;
+            export.webp_lossless = false;
         }
+        if let Some(scale) = args.scale {
+            export.scale = scale;
+        }
+
         Self {
-            zoom: 1.0,
-            image_receiver,
-            color_picker: ColorPickerButton::new("ColorPicker", Color32::RED),
-            current_tool_info: ToolInfo::new(Color32::RED),
-            font_data,
+            image_sender: Some(tx),
+            image_receiver: Some(rx),
+            loading,
+            color_picker,
+            fill_picker,
+            current_tool_info,
+            fonts,
+            font_picker,
+            keymap: KeyMap::load(),
+            eyedropper: Eyedropper::new(settings.eyedropper_radius),
+            save_dialog: FileDialog::new("Save As", EXPORT_EXTENSIONS).saving(),
+            report_dialog: FileDialog::new("Save PDF Report", &["pdf"]).saving(),
+            dataset_dialog: FileDialog::new("Export Dataset", &["json", "xml", "txt"]).saving(),
+            labels,
+            label_dialog: FileDialog::new("Load Labels", &["txt"]),
+            open_project_dialog: FileDialog::new("Open Project", &[project::PROJECT_EXTENSION]),
+            save_project_dialog: FileDialog::new("Save Project", &[project::PROJECT_EXTENSION]).saving(),
+            export,
+            settings,
             ..Default::default()
         }
     }
 
-    fn save_image(&self, ctx: &egui::Context) {
-        if let Some(img) = &self.original_image {
-            let mut img = img.clone();
+    /// 当前标签页
+    fn document_mut(&mut self) -> Option<&mut Document> {
+        self.documents.get_mut(self.active)
+    }
 
-            let font_data = self.font_data.unwrap();
-            let font = FontRef::try_from_slice(font_data).expect("Failed to load system font");
-            for op in &self.operators {
-                op.draw_on_image(&mut img, &font);
+    /// 保存指定标签页，返回是否成功
+    fn save_document(&mut self, index: usize) -> bool {
+        let format = self.export.format;
+        let Some(doc) = self.documents.get(index) else {
+            return false;
+        };
+        let path = match &doc.saved_path {
+            Some(path) if path.extension().is_some_and(|e| e == format.extension()) => path.clone(),
+            _ => doc.output_path(self.settings.output_dir.as_deref(), format.extension()),
+        };
+        if !self.write_document(index, &path, format) {
+            return false;
+        }
+        self.documents[index].saved_path = Some(path);
+        true
+
+        // let _ = std::process::Command::new("wl-copy")
+        //     .arg("--type")
+        //     .arg("image/png")
+        //     .arg(path)
+        //     .spawn();
+    }
+
+    /// 把标签页渲染后按指定格式写入文件
+    fn write_document(&self, index: usize, path: &Path, format: ExportFormat) -> bool {
+        let Some(doc) = self.documents.get(index) else {
+            return false;
+        };
+
+        let (scale, layer) = (self.export.scale_factor(), self.export.layer());
+        let result = match format {
+            ExportFormat::Svg => svg::render(doc, &self.fonts, scale, layer)
+                .and_then(|svg| std::fs::write(path, svg).map_err(|e| e.to_string())),
+            ExportFormat::Pdf => pdf::render(&[doc], &self.fonts, scale, layer)
+                .and_then(|pdf| std::fs::write(path, pdf).map_err(|e| e.to_string())),
+            _ => export::save(&doc.render(&self.fonts, scale, layer), path, format, &self.export),
+        };
+
+        match result {
+            Ok(()) => {
+                println!("image saved: {}", path.display());
+                true
+            }
+            Err(err) => {
+                eprintln!("Failed to save {}: {err}", path.display());
+                false
             }
+        }
+    }
 
-            img.save_with_format("output.png", image::ImageFormat::Png).expect("Failed to save image");
-            println!("image saved!");
+    /// 打开另存为窗口
+    fn save_as(&mut self) {
+        let format = self.export.format;
+        if let Some(doc) = self.documents.get(self.active) {
+            let path = doc.output_path(self.settings.output_dir.as_deref(), format.extension());
+            self.save_dialog.set_path(&path);
+            self.save_dialog.open();
+        }
+    }
+
+    /// 打开保存 PDF 报告的窗口
+    fn save_report(&mut self) {
+        if !self.documents.is_empty() {
+            self.report_dialog.set_file_name("report.pdf");
+            self.report_dialog.open();
+        }
+    }
 
-            // let _ = std::process::Command::new("wl-copy")
-            //     .arg("--type")
-            //     .arg("image/png")
-            //     .arg(path)
-            //     .spawn();
+    /// 所有标签页按顺序保存为一个 PDF，每张图片一页
+    fn handle_report_dialog(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.report_dialog.show(ctx) else {
+            return;
+        };
+        let docs: Vec<&Document> = self.documents.iter().collect();
+        let result = pdf::render(&docs, &self.fonts, self.export.scale_factor(), self.export.layer())
+            .and_then(|pdf| std::fs::write(&path, pdf).map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => println!("report saved: {}", path.display()),
+            Err(err) => eprintln!("Failed to save {}: {err}", path.display()),
+        }
+    }
 
+    /// 打开导出数据集标注的窗口
+    fn export_dataset(&mut self, format: DatasetFormat) {
+        let Some(doc) = self.documents.get(self.active) else {
+            return;
+        };
+        let name = if format.per_image() {
+            dataset::annotation_file_name(doc, format)
+        } else {
+            "annotations.json".to_owned()
+        };
+        self.dataset_format = format;
+        self.dataset_dialog.set_file_name(name);
+        self.dataset_dialog.open();
+    }
+
+    /// 把矩形、椭圆的外接框和多边形的分割轮廓写成数据集标注，图片本身不导出
+    fn handle_dataset_dialog(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.dataset_dialog.show(ctx) else {
+            return;
+        };
+        let docs: Vec<&Document> = self.documents.iter().collect();
+        match dataset::export(&docs, &self.labels.names, self.active, self.dataset_format, &path) {
+            Ok(files) => {
+                for file in files {
+                    println!("annotations saved: {}", file.display());
+                }
+            }
+            Err(err) => eprintln!("Failed to save {}: {err}", path.display()),
+        }
+    }
+
+    /// 打开保存工程文件的窗口
+    fn save_project(&mut self) {
+        if let Some(doc) = self.documents.get(self.active) {
+            let path = doc.output_path(None, project::PROJECT_EXTENSION);
+            self.save_project_dialog.set_path(&path);
+            self.save_project_dialog.open();
+        }
+    }
+
+    /// 工程文件作为新标签页打开，标注和类别可以继续编辑
+    fn open_project(&mut self, ctx: &egui::Context, path: &Path) {
+        match project::load(path) {
+            Ok(project) => {
+                self.add_document(ctx, project.image);
+                if let Some(doc) = self.documents.last_mut() {
+                    doc.operators = project.operators;
+                    doc.number = project.number;
+                }
+            }
+            Err(err) => eprintln!("Failed to open project {}: {err}", path.display()),
+        }
+    }
+
+    fn handle_project_dialogs(&mut self, ctx: &egui::Context) {
+        if let Some(path) = self.open_project_dialog.show(ctx) {
+            self.open_project(ctx, &path);
+        }
+        if let Some(path) = self.save_project_dialog.show(ctx)
+            && let Some(doc) = self.documents.get(self.active)
+        {
+            match project::save(doc, &path) {
+                Ok(()) => println!("project saved: {}", path.display()),
+                Err(err) => eprintln!("Failed to save {}: {err}", path.display()),
+            }
+        }
+    }
+
+    /// 另存为：格式由文件扩展名决定
+    fn handle_save_dialog(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.save_dialog.show(ctx) else {
+            return;
+        };
+        match ExportFormat::from_path(&path) {
+            Some(format) => {
+                if self.write_document(self.active, &path, format) {
+                    // 之后的保存沿用这个目录
+                    self.settings.output_dir = path.parent().filter(|d| d.is_dir()).map(Path::to_path_buf);
+                }
+            }
+            None => eprintln!(
+                "Failed to save {}: unsupported extension, expected one of {}",
+                path.display(),
+                EXPORT_EXTENSIONS.join(", ")
+            ),
+        }
+    }
+
+    /// 保存当前标签页，只有一个标签页时保存后退出
+    fn save_image(&mut self, ctx: &egui::Context) {
+        if self.save_document(self.active) && self.documents.len() == 1 {
             ctx.send_viewport_cmd(egui::ViewportCommand::Close);
         }
     }
 
-    fn reset_view(&mut self, available_rect: Rect) {
-        if let Some(texture) = &self.texture {
-            let image_size = texture.size_vec2();
-            let panel_size = available_rect.size();
+    /// 保存所有标签页，全部成功后退出
+    fn save_all(&mut self, ctx: &egui::Context) {
+        let saved = (0..self.documents.len()).filter(|&i| self.save_document(i)).count();
+        if saved > 0 && saved == self.documents.len() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
+    }
+
+    /// 切换标签页
+    fn select_document(&mut self, index: usize) {
+        if index < self.documents.len() && index != self.active {
+            self.active = index;
+            self.current_tool_info.text_editing = None;
+            self.current_tool_info.polygon.clear();
+        }
+    }
+
+    /// 关闭标签页
+    fn close_document(&mut self, index: usize) {
+        if index >= self.documents.len() {
+            return;
+        }
+        self.documents.remove(index);
+        if self.active > index || self.active >= self.documents.len() {
+            self.active = self.active.saturating_sub(1);
+        }
+        self.current_tool_info.text_editing = None;
+        self.current_tool_info.polygon.clear();
+    }
+
+    /// 加载完成的图片作为新标签页打开
+    fn add_document(&mut self, ctx: &egui::Context, mut loaded: LoadedImage) {
+        // 同名标签页加上序号区分
+        let base = loaded.name.clone();
+        let mut n = 1;
+        while self.documents.iter().any(|d| d.title == loaded.name) {
+            n += 1;
+            loaded.name = format!("{base} ({n})");
+        }
+        self.documents.push(Document::new(ctx, loaded));
+        self.active = self.documents.len() - 1;
+        self.current_tool_info.text_editing = None;
+        self.current_tool_info.polygon.clear();
+    }
+
+    /// 显示字体窗口，设置改变时在后台重新加载字体，加载完成后替换
+    fn font_window(&mut self, ctx: &egui::Context) {
+        if self.font_picker.show(ctx) {
+            let primary = self.font_picker.primary.clone();
+            let fallbacks = self.font_picker.fallbacks.clone();
+            let (tx, rx) = mpsc::channel();
+            font::spawn_load(ctx, tx, primary.clone(), fallbacks.clone());
+            self.font_receiver = Some(rx);
+            self.settings.font = primary;
+            self.settings.font_fallbacks = fallbacks;
+        }
+        if let Some(fonts) = self.font_receiver.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            self.fonts = fonts;
+            font::init_egui_fonts(ctx, &self.fonts);
+            self.font_receiver = None;
+        }
+    }
+
+    /// 执行快捷键对应的动作
+    fn run_action(&mut self, ctx: &egui::Context, action: Action) {
+        match action {
+            Action::Tool(Tool::Open) => self.file_dialog.open(),
+            Action::Tool(Tool::Save) => self.save_image(ctx),
+            Action::Tool(tool) => self.select_tool(tool),
+            Action::Undo => {
+                if let Some(doc) = self.document_mut() {
+                    doc.undo();
+                }
+                self.current_tool_info.text_editing = None;
+            }
+            Action::SaveAll => self.save_all(ctx),
+            Action::NextStrokeWidth | Action::PrevStrokeWidth => {
+                const WIDTHS: [StrokeWidth; 3] =
+                    [StrokeWidth::ONE, StrokeWidth::THREE, StrokeWidth::FIVE];
+                let current = WIDTHS
+                    .iter()
+                    .position(|&w| w == self.current_tool_info.stroke_width)
+                    .unwrap_or(0);
+                let next = cycle(current, WIDTHS.len(), action == Action::NextStrokeWidth);
+                self.current_tool_info.stroke_width = WIDTHS[next];
+            }
+            Action::NextColor | Action::PrevColor => {
+                let current = PRESET_COLORS
+                    .iter()
+                    .position(|&c| c == self.current_tool_info.color)
+                    .unwrap_or(0);
+                let next = cycle(current, PRESET_COLORS.len(), action == Action::NextColor);
+                self.current_tool_info.color = PRESET_COLORS[next];
+                self.color_picker.set_color(PRESET_COLORS[next]);
+            }
+            Action::Label(index) => self.select_label(index),
+            Action::NudgeLeft | Action::NudgeRight | Action::NudgeUp | Action::NudgeDown => {
+                // 按住 Shift 时每次移动 10 像素
+                let step = if ctx.input(|i| i.modifiers.shift) { 10.0 } else { 1.0 };
+                let delta = match action {
+                    Action::NudgeLeft => Vec2::new(-step, 0.0),
+                    Action::NudgeRight => Vec2::new(step, 0.0),
+                    Action::NudgeUp => Vec2::new(0.0, -step),
+                    _ => Vec2::new(0.0, step),
+                };
+                if self.current_tool_info.tool == Tool::Select
+                    && let Some(doc) = self.document_mut()
+                {
+                    doc.nudge_selected(delta);
+                }
+            }
+        }
+    }
 
-            let scale_x = panel_size.x / image_size.x;
-            let scale_y = panel_size.y / image_size.y;
+    /// 从指定来源加载图片，结果通过 `image_receiver` 返回
+    fn load_image(&mut self, ctx: &egui::Context, source: ImageSource) {
+        if let Some(tx) = &self.image_sender {
+            loader::spawn_load(ctx, tx.clone(), source);
+            self.loading += 1;
+        }
+    }
 
-            self.zoom = scale_x.min(scale_y);
+    /// 处理拖放文件和剪贴板粘贴
+    fn handle_image_input(&mut self, ctx: &egui::Context) {
+        let dropped = ctx.input(|i| i.raw.dropped_files.clone());
+        for file in dropped {
+            if let Some(path) = file.path {
+                if path.extension().is_some_and(|ext| ext == project::PROJECT_EXTENSION) {
+                    self.open_project(ctx, &path);
+                } else {
+                    self.load_image(ctx, ImageSource::Path(path));
+                }
+            } else if let Some(bytes) = file.bytes {
+                self.load_image(ctx, ImageSource::Bytes(bytes));
+            }
+        }
 
-            let new_size = image_size * self.zoom;
+        // Ctrl + V 粘贴图片，编辑文字时交给输入框处理
+        // egui-winit 会吞掉粘贴键的按下事件，因此这里检测松开事件
+        if self.current_tool_info.text_editing.is_none()
+            && ctx.input(|i| i.modifiers.command && i.key_released(egui::Key::V))
+        {
+            self.load_image(ctx, ImageSource::Clipboard);
+        }
 
-            self.pan = (panel_size - new_size) / 2.0;
+        if let Some(path) = self.file_dialog.show(ctx) {
+            self.load_image(ctx, ImageSource::Path(path));
         }
     }
 }
 
 impl App for AnnotatorApp {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        // 取色是临时状态，保存取色前的工具
+        self.settings.tool = match self.current_tool_info.tool {
+            Tool::Eyedropper => self.eyedropper.return_to,
+            tool => tool,
+        };
+        self.settings.eyedropper_radius = self.eyedropper.radius;
+        self.current_tool_info.remember_colors();
+        self.settings.tool_colors = self.current_tool_info.tool_colors.clone();
+        self.settings.color = HexColor(self.current_tool_info.color);
+        self.settings.stroke_width = self.current_tool_info.stroke_width.into();
+        self.settings.palette = self
+            .color_picker
+            .custom_colors()
+            .iter()
+            .map(|&c| HexColor(c))
+            .collect();
+        self.settings.recent_colors = self
+            .color_picker
+            .recent_colors()
+            .iter()
+            .map(|&c| HexColor(c))
+            .collect();
+        self.settings.palettes = self.color_picker.palettes().to_vec();
+        self.settings.save();
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // 检查图片是否加载完成
-        if let Some(rx) = &self.image_receiver {
-            if let Ok((img, image_size)) = rx.try_recv() {
-                let color_image = ColorImage::from_rgba_unmultiplied(
-                    [image_size.x as usize, image_size.y as usize], 
-                    &img.as_raw()
-                );
-                self.texture = Some(ctx.load_texture("loaded_image", color_image, Default::default()));
-                self.image_size = image_size;
-                self.original_image = Some(img);
-                self.image_receiver = None;
+        let results: Vec<LoadResult> = self
+            .image_receiver
+            .as_ref()
+            .map(|rx| rx.try_iter().collect())
+            .unwrap_or_default();
+        for result in results {
+            self.loading = self.loading.saturating_sub(1);
+            match result {
+                Ok(loaded) => self.add_document(ctx, loaded),
+                Err(err) => eprintln!("Failed to load image: {err}"),
             }
         }
 
+        self.handle_image_input(ctx);
+        self.handle_save_dialog(ctx);
+        self.handle_report_dialog(ctx);
+        self.handle_dataset_dialog(ctx);
+        self.handle_label_dialog(ctx);
+        self.handle_project_dialogs(ctx);
+
         // 处理缩放（Ctrl + 鼠标滚轮）
         let scroll = ctx.input(|i| i.raw_scroll_delta.y);
-        if ctx.input(|i| i.modifiers.ctrl) && scroll != 0.0 {
-            if let Some(mouse_pos) = ctx.input(|i| i.pointer.hover_pos()) {
-                let old_zoom = self.zoom;
+        if ctx.input(|i| i.modifiers.ctrl) && scroll != 0.0
+            && let Some(mouse_pos) = ctx.input(|i| i.pointer.hover_pos())
+            && let Some(doc) = self.document_mut()
+        {
+            let old_zoom = doc.zoom;
 
-                let zoom_speed = 0.0015;
-                let new_zoom = (old_zoom * (scroll * zoom_speed).exp()).clamp(0.05, 20.0);
+            let zoom_speed = 0.0015;
+            let new_zoom = (old_zoom * (scroll * zoom_speed).exp()).clamp(0.05, 20.0);
 
-                // 当前图片左上角
-                let image_min = self.last_image_rect.map_or(Pos2::ZERO, |r| r.min);
+            // 当前图片左上角
+            let image_min = doc.last_image_rect.map_or(Pos2::ZERO, |r| r.min);
 
-                // 鼠标对应的图片坐标（缩放前）
-                let image_pos = (mouse_pos - image_min) / old_zoom;
+            // 鼠标对应的图片坐标（缩放前）
+            let image_pos = (mouse_pos - image_min) / old_zoom;
 
-                // 更新 zoom
-                self.zoom = new_zoom;
+            // 更新 zoom
+            doc.zoom = new_zoom;
 
-                // 重新计算 pan，使鼠标指向位置不变
-                self.pan += image_pos * (old_zoom - new_zoom);
-            }
+            // 重新计算 pan，使鼠标指向位置不变
+            doc.pan += image_pos * (old_zoom - new_zoom);
         }
 
-        // 撤销
-        if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::Z)) {
-            let opt = self.operators.pop();
-            if opt.is_some_and(|op| matches!(op.tool, ToolType::Number(..))) {
-                self.current_tool_info.number -= 1;
-            }
-        }
-
-        // 保存
-        if ctx.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::S)) {
-            self.save_image(ctx);
+        // 快捷键
+        for action in self.keymap.triggered(ctx) {
+            self.run_action(ctx, action);
         }
 
         self.toolbar(ctx);
+        self.tab_bar(ctx);
+        self.label_panel(ctx);
+        self.font_window(ctx);
 
         // 主画布
         egui::CentralPanel::default().show(ctx, |ui| {
-            if let Some(texture) = &self.texture {
+            if let Some(doc) = self.documents.get_mut(self.active) {
                 // painter 占满整个面板，不随 zoom 变化
                 let available = ui.available_rect_before_wrap();
                 let (response, painter) =
                     ui.allocate_painter(available.size(), Sense::click_and_drag());
 
                 // 图片的实际渲染区域
-                let image_size = texture.size_vec2() * self.zoom;
-                let image_rect = Rect::from_min_size(available.min + self.pan, image_size);
-                self.last_image_rect = Some(image_rect);
+                let image_size = doc.image_size * doc.zoom;
+                let image_rect = Rect::from_min_size(available.min + doc.pan, image_size);
+                doc.last_image_rect = Some(image_rect);
 
-                let helper = AppHelper::from_app(self);
+                let helper = AppHelper::from_document(doc);
 
-                // 绘制图片
-                painter.image(
-                    texture.id(),
-                    image_rect,
-                    Rect::from_min_max(Pos2::ZERO, egui::pos2(1.0, 1.0)),
-                    Color32::WHITE,
-                );
+                // 绘制图片（只绘制可见的纹理块）
+                tiles::paint_tiles(&doc.tiles, &helper, &painter);
 
                 // Ctrl + 左键拖动画布平移
                 if self.current_tool_info.tool == Tool::Select
                     && ctx.input(|i| i.modifiers.ctrl)
                     && response.dragged_by(egui::PointerButton::Primary)
                 {
-                    self.pan += response.drag_delta();
+                    doc.pan += response.drag_delta();
+                }
+
+                // 选择工具：点击选中标注，拖动移动
+                if self.current_tool_info.tool == Tool::Select && !ctx.input(|i| i.modifiers.ctrl) {
+                    let press = if response.drag_started_by(egui::PointerButton::Primary) {
+                        ui.input(|i| i.pointer.press_origin())
+                    } else if response.clicked_by(egui::PointerButton::Primary) {
+                        response.interact_pointer_pos()
+                    } else {
+                        None
+                    };
+                    if let Some(pos) = press {
+                        let image_pos = helper.screen_to_image(pos, None);
+                        doc.select_at(image_pos, &painter, 4.0 / doc.zoom);
+                    }
+                    if response.dragged_by(egui::PointerButton::Primary) {
+                        doc.drag_selected(response.drag_delta() / doc.zoom);
+                    }
+                }
+
+                // 取色：悬停显示放大镜，单击取样后切换回之前的工具
+                if self.current_tool_info.tool == Tool::Eyedropper {
+                    if let Some(pos) = response.hover_pos() {
+                        let image_pos = helper.screen_to_image(pos, None);
+                        self.eyedropper.paint_loupe(ctx, &doc.original_image, pos, image_pos);
+                        ctx.set_cursor_icon(egui::CursorIcon::Crosshair);
+                    }
+                    if response.clicked_by(egui::PointerButton::Primary)
+                        && let Some(pos) = response.interact_pointer_pos()
+                        && let Some(color) = self
+                            .eyedropper
+                            .sample(&doc.original_image, helper.screen_to_image(pos, None))
+                    {
+                        self.current_tool_info.switch_tool(self.eyedropper.return_to);
+                        self.current_tool_info.color = color;
+                        self.color_picker.set_color(color);
+                    } else if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+                        self.current_tool_info.switch_tool(self.eyedropper.return_to);
+                    }
                 }
 
                 // 读取输入法输入的文字
-                if self.current_tool_info.tool == Tool::Text {
+                if self.current_tool_info.tool.is_text() {
+                    let color = self.current_tool_info.color;
                     if let Some(editing) = &mut self.current_tool_info.text_editing {
-                        let screen_pos = helper.image_to_screen(editing.pos);
-                        let font_size = 16.0 * self.zoom;
-                
-                        // TextEdit 放在点击位置，承载输入法
-                        let edit_rect = Rect::from_min_size(screen_pos, Vec2::new(200.0, font_size + 8.0));
-                        let text_edit = egui::TextEdit::singleline(&mut editing.content)
-                            .font(egui::FontId::proportional(font_size))
-                            .frame(false)
-                            .desired_width(200.0)
-                            .text_color(self.current_tool_info.color);
-                
-                        let output = ui.put(edit_rect, text_edit);
-                        // 获得焦点
-                        output.request_focus();
-                        if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
-                            let content = editing.content.clone();
-                            let pos = editing.pos;
-                            if !content.is_empty() {
-                                let op = Operator::new(
-                                    ToolType::Text { pos, content },
+                        match editing.show(ui, &helper, color) {
+                            TextEditOutcome::Editing => {}
+                            TextEditOutcome::Commit => {
+                                let editing = self.current_tool_info.text_editing.take().unwrap();
+                                let replaces = editing.replaces;
+                                let retained = editing.retained.clone();
+                                let empty = editing.content.trim().is_empty();
+                                if replaces.is_none() {
+                                    // 换行宽度只属于这一段文字，不沿用到下一段
+                                    self.current_tool_info.text_style = TextStyle {
+                                        wrap_width: None,
+                                        ..editing.style.clone()
+                                    };
+                                }
+                                let mut op = Operator::new(
+                                    editing.into_tool_type(),
                                     self.current_tool_info.stroke_width,
-                                    self.current_tool_info.color,
+                                    color,
                                     None,
                                 );
-                                self.operators.push(op);
+                                // 修改已有标注时只替换文字和颜色，线宽、填充色和类别保持原样
+                                if let Some(retained) = retained {
+                                    op.stroke_width = retained.stroke_width;
+                                    op.fill_color = retained.fill_color;
+                                    op.label = retained.label;
+                                }
+                                match replaces {
+                                    // 修改已有文字，清空内容则删除
+                                    Some(index) if empty => {
+                                        doc.operators.remove(index);
+                                        doc.selected = None;
+                                    }
+                                    Some(index) => doc.operators[index] = op,
+                                    None if empty => {}
+                                    None => doc.operators.push(op),
+                                }
+                            }
+                            TextEditOutcome::Cancel => {
+                                self.current_tool_info.text_editing = None;
                             }
-                            self.current_tool_info.text_editing = None;
-                        }
-                
-                        // Escape 取消
-                        if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
-                            self.current_tool_info.text_editing = None;
                         }
                     }
                 } else {
@@ -287,29 +764,135 @@ impl App for AnnotatorApp {
                     );
                 }
 
-                if response.double_clicked() {
-                    self.reset_view(response.rect);
+                // 双击文字标注（文字工具下单击即可）重新编辑，双击其他位置重置视图
+                // 多边形工具的双击用于闭合多边形
+                let text_click = self.current_tool_info.tool.is_text()
+                    && self.current_tool_info.text_editing.is_none()
+                    && response.clicked_by(egui::PointerButton::Primary);
+                let double_click = response.double_clicked() && self.current_tool_info.tool != Tool::Polygon;
+                if double_click || text_click {
+                    let editing = response
+                        .interact_pointer_pos()
+                        .map(|pos| helper.screen_to_image(pos, None))
+                        .and_then(|pos| doc.hit_test(pos, &painter, 4.0 / doc.zoom))
+                        .and_then(|i| Some((i, TextEditState::from_operator(&doc.operators[i], i)?)));
+                    match editing {
+                        Some((i, editing)) => {
+                            let color = doc.operators[i].color;
+                            self.current_tool_info.switch_tool(editing.tool());
+                            self.current_tool_info.color = color;
+                            self.color_picker.set_color(color);
+                            self.current_tool_info.text_editing = Some(editing);
+                            doc.selected = None;
+                        }
+                        None if double_click => doc.reset_view(response.rect),
+                        None => {}
+                    }
                 }
 
                 // 根据工具进行绘制
                 if let Some(op) = self.current_tool_info.input_event(&helper, ui, &response) {
-                    self.operators.push(op);
+                    if matches!(op.tool, ToolType::Number(..)) {
+                        doc.number += 1;
+                    }
+                    doc.operators.push(op);
                 }
 
-                // 画已有标注
-                for op in &self.operators {
-                    op.draw(&helper, &painter);
+                // 画已有标注，正在重新编辑的文字由输入框显示
+                let replacing = self.current_tool_info.text_editing.as_ref().and_then(|e| e.replaces);
+                for (i, op) in doc.operators.iter().enumerate() {
+                    if replacing != Some(i) {
+                        op.draw_with_image(&helper, &painter, &doc.original_image, &mut doc.mosaic_cache);
+                    }
+                }
+                doc.mosaic_cache.end_frame();
+
+                // 选中标注的外框
+                if self.current_tool_info.tool == Tool::Select
+                    && let Some(op) = doc.selected.and_then(|i| doc.operators.get(i))
+                {
+                    let rect = op.bounding_rect(&painter);
+                    let screen_rect = Rect::from_min_max(
+                        helper.image_to_screen(rect.min),
+                        helper.image_to_screen(rect.max),
+                    )
+                    .expand(4.0);
+                    let handle_color = Color32::from_rgb(100, 150, 255);
+                    painter.rect_stroke(
+                        screen_rect,
+                        0.0,
+                        egui::Stroke::new(1.0, handle_color),
+                        egui::StrokeKind::Outside,
+                    );
+                    // 多边形的顶点可以单独拖动
+                    if let ToolType::Polygon(points) = &op.tool {
+                        for p in points {
+                            let handle = Rect::from_center_size(helper.image_to_screen(*p), Vec2::splat(7.0));
+                            painter.rect_filled(handle, 1.0, Color32::WHITE);
+                            painter.rect_stroke(handle, 1.0, egui::Stroke::new(1.0, handle_color), egui::StrokeKind::Inside);
+                        }
+                    }
                 }
 
                 // 画绘制过程
                 self.current_tool_info.input_event_process(&helper, &painter, &response);
-            } else {
+            } else if self.loading > 0 {
                 // 显示 loading
                 ui.centered_and_justified(|ui| {
                     ui.spinner();
                     ui.label("Loading image...");
                 });
+            } else {
+                // 没有图片时提示打开方式
+                ui.vertical_centered(|ui| {
+                    ui.add_space(ui.available_height() / 3.0);
+                    if ui.button("Open Image...").clicked() {
+                        self.file_dialog.open();
+                    }
+                    ui.label("or drop a file here, or paste an image with Ctrl+V");
+                });
             }
         });
+
+        // 记录窗口位置和大小，退出时保存
+        if let Some(geometry) = ctx.input(|i| {
+            let viewport = i.viewport();
+            let outer = viewport.outer_rect?;
+            let inner = viewport.inner_rect?;
+            Some(WindowGeometry {
+                x: outer.min.x,
+                y: outer.min.y,
+                width: inner.width(),
+                height: inner.height(),
+            })
+        }) {
+            self.settings.window = Some(geometry);
+        }
+
+        // 拖动文件到窗口上时的提示
+        if ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+            let painter = ctx.layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("file_drop_target"),
+            ));
+            let screen_rect = ctx.content_rect();
+            painter.rect_filled(screen_rect, 0.0, Color32::from_black_alpha(160));
+            painter.text(
+                screen_rect.center(),
+                egui::Align2::CENTER_CENTER,
+                "Drop image to open",
+                egui::FontId::proportional(24.0),
+                Color32::WHITE,
+            );
+        }
+    }
+}
+
+/// 在 `len` 个选项中循环切换
+fn cycle(current: usize, len: usize, forward: bool) -> usize {
+    if forward {
+        (current + 1) % len
+    } else {
+        (current + len - 1) % len
     }
 }
@@ -1,9 +1,13 @@
 use egui::{
-    Align2, Color32, FontId, Painter, Pos2, Rect, Stroke, StrokeKind, 
-    epaint::{CircleShape, EllipseShape, PathShape, PathStroke}
+    Align, Align2, Color32, FontId, Painter, Pos2, Rect, Stroke, StrokeKind, Vec2,
+    epaint::{CircleShape, EllipseShape, Mesh, PathShape, PathStroke},
+    text::{LayoutJob, TextFormat},
 };
 
-use crate::{toolbar::StrokeWidth, utils::AppHelper};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use crate::{drawable::MosaicCache, labels::label_color, toolbar::StrokeWidth, utils::AppHelper};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum ToolType {
@@ -14,9 +18,185 @@ pub enum ToolType {
     Pencil(Vec<Pos2>),
     Number(CircleShape, u8),
     Text {
+        /// 锚点：按对齐方式为文字的左上、中上或右上
+        pos: Pos2,
+        content: String,
+        style: TextStyle,
+    },
+    /// 标注气泡：圆角文字框和指向目标的尾巴
+    Callout {
+        /// 尾巴尖端指向的位置
+        target: Pos2,
+        /// 文字锚点，同 `Text`
         pos: Pos2,
         content: String,
+        style: TextStyle,
     },
+    /// 马赛克区域，色块大小随线宽变化
+    Mosaic(Rect),
+    /// 闭合多边形的顶点
+    Polygon(Vec<Pos2>),
+}
+
+/// 只导出标注层时马赛克区域的遮罩颜色
+pub const MASK_COLOR: Color32 = Color32::BLACK;
+
+/// 线宽对应的马赛克色块边长（图片像素）
+pub fn mosaic_block(stroke_width: f32) -> f32 {
+    stroke_width * 4.0 + 4.0
+}
+
+/// 拖动标注时移动的部分
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DragHandle {
+    /// 整个标注，气泡只移动文字框
+    #[default]
+    Body,
+    /// 气泡尾巴的尖端
+    Tip,
+    /// 多边形的第 n 个顶点
+    Vertex(usize),
+}
+
+/// 数字徽章中数字的颜色：底色与描边色相同时用白色，否则用描边色
+pub fn number_text_color(fill: Color32, stroke: Color32) -> Color32 {
+    if fill == stroke { Color32::WHITE } else { stroke }
+}
+
+/// 气泡没有背景色时的填充色
+pub const CALLOUT_FILL: Color32 = Color32::WHITE;
+
+/// 气泡尾巴的三个顶点：文字框中心两侧的底边和尖端，目标在文字框内时没有尾巴
+pub fn callout_tail(box_rect: Rect, target: Pos2) -> Option<[Pos2; 3]> {
+    if box_rect.contains(target) {
+        return None;
+    }
+    let center = box_rect.center();
+    let dir = (target - center).normalized();
+    let half_width = box_rect.width().min(box_rect.height()) / 4.0;
+    let side = dir.rot90() * half_width;
+    Some([center + side, target, center - side])
+}
+
+/// 文字水平对齐方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl From<TextAlign> for Align {
+    fn from(value: TextAlign) -> Self {
+        match value {
+            TextAlign::Left => Align::Min,
+            TextAlign::Center => Align::Center,
+            TextAlign::Right => Align::Max,
+        }
+    }
+}
+
+/// 文字标注的样式
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextStyle {
+    /// 字号（图片像素）
+    pub size: f32,
+    pub bold: bool,
+    pub italic: bool,
+    pub align: TextAlign,
+    /// 固定的换行宽度（图片像素），None 表示只在换行符处换行
+    pub wrap_width: Option<f32>,
+    /// 背景框颜色，None 表示没有背景框
+    pub background: Option<Color32>,
+    /// 背景框内边距（图片像素）
+    pub padding: f32,
+    /// 背景框圆角半径（图片像素）
+    pub corner_radius: f32,
+    /// 文字描边颜色，None 表示不描边
+    pub outline: Option<Color32>,
+    /// 描边宽度（图片像素）
+    pub outline_width: f32,
+    /// 阴影颜色，有背景框时为背景框的阴影，否则为文字的阴影
+    pub shadow: Option<Color32>,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            size: 16.0,
+            bold: false,
+            italic: false,
+            align: TextAlign::Left,
+            wrap_width: None,
+            background: None,
+            padding: 4.0,
+            corner_radius: 4.0,
+            outline: None,
+            outline_width: 2.0,
+            shadow: None,
+        }
+    }
+}
+
+impl TextStyle {
+    /// 模拟粗体时两次绘制的水平偏移（图片像素）
+    pub fn bold_offset(&self) -> f32 {
+        (self.size / 24.0).max(1.0)
+    }
+
+    /// 阴影相对文字或背景框的偏移（图片像素）
+    pub fn shadow_offset(&self) -> Vec2 {
+        Vec2::splat((self.size / 12.0).max(1.0))
+    }
+
+    /// 绘制字形的各次偏移（图片像素）和颜色，按从下到上的顺序
+    ///
+    /// 依次为文字阴影、描边（向八个方向错开绘制）和文字本身，粗体时每次都再错开绘制一遍
+    pub fn glyph_passes(&self, color: Color32) -> Vec<(Vec2, Color32)> {
+        let mut passes = Vec::new();
+        if let Some(shadow) = self.shadow
+            && self.background.is_none()
+        {
+            passes.push((self.shadow_offset(), shadow));
+        }
+        if let Some(outline) = self.outline {
+            let w = self.outline_width;
+            for i in 0..8 {
+                let angle = i as f32 * std::f32::consts::FRAC_PI_4;
+                passes.push((Vec2::angled(angle) * w, outline));
+            }
+        }
+        passes.push((Vec2::ZERO, color));
+
+        if self.bold {
+            let bold = Vec2::new(self.bold_offset(), 0.0);
+            passes = passes
+                .into_iter()
+                .flat_map(|(offset, color)| [(offset, color), (offset + bold, color)])
+                .collect();
+        }
+        passes
+    }
+
+    /// 按样式排版文字，`zoom` 为图片到屏幕的缩放
+    pub fn layout_job(&self, content: &str, zoom: f32, color: Color32) -> LayoutJob {
+        let mut job = LayoutJob::single_section(
+            content.to_owned(),
+            TextFormat {
+                font_id: FontId::proportional(self.size * zoom),
+                color,
+                italics: self.italic,
+                ..Default::default()
+            },
+        );
+        job.halign = self.align.into();
+        if let Some(width) = self.wrap_width {
+            job.wrap.max_width = width * zoom;
+        }
+        job
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -29,6 +209,8 @@ pub struct Operator {
     pub color: Color32,
     /// 填充颜色
     pub fill_color: Option<Color32>,
+    /// 导出数据集时的类别，不影响绘制
+    pub label: Option<String>,
 }
 
 impl Operator {
@@ -38,7 +220,118 @@ impl Operator {
             stroke_width,
             color,
             fill_color,
+            label: None,
+        }
+    }
+
+    /// 是否可以作为数据集中的目标导出
+    pub fn supports_label(&self) -> bool {
+        matches!(self.tool, ToolType::Rect(_) | ToolType::Ellipse(_) | ToolType::Polygon(_))
+    }
+
+    /// 设置数据集类别，带类别的标注使用类别颜色描边
+    pub fn set_label(&mut self, label: Option<String>) {
+        if let Some(label) = &label {
+            let color = label_color(label);
+            self.color = color;
+            if let ToolType::Ellipse(ellipse) = &mut self.tool {
+                ellipse.stroke.color = color;
+            }
         }
+        self.label = label;
+    }
+
+    /// 标注在图片坐标系中的外接矩形
+    pub fn bounding_rect(&self, painter: &Painter) -> Rect {
+        match &self.tool {
+            ToolType::Rect(rect) | ToolType::Mosaic(rect) => *rect,
+            ToolType::Ellipse(ellipse) => {
+                Rect::from_center_size(ellipse.center, ellipse.radius * 2.0)
+            }
+            ToolType::Arrow(arrow) => Rect::from_points(&arrow.points),
+            ToolType::Line(s, e) => Rect::from_two_pos(*s, *e),
+            ToolType::Pencil(points) | ToolType::Polygon(points) => Rect::from_points(points),
+            ToolType::Number(c, _) => Rect::from_center_size(c.center, Vec2::splat(c.radius * 2.0)),
+            ToolType::Text { pos, content, style } => {
+                let galley = painter.layout_job(style.layout_job(content, 1.0, self.color));
+                let mut rect = galley.rect.translate(pos.to_vec2());
+                if style.bold {
+                    rect.max.x += style.bold_offset();
+                }
+                if style.background.is_some() {
+                    rect = rect.expand(style.padding);
+                }
+                rect
+            }
+            ToolType::Callout { target, pos, content, style } => {
+                let galley = painter.layout_job(style.layout_job(content, 1.0, self.color));
+                let mut rect = galley.rect.translate(pos.to_vec2());
+                if style.bold {
+                    rect.max.x += style.bold_offset();
+                }
+                rect.expand(style.padding).union(Rect::from_pos(*target))
+            }
+        }
+    }
+
+    /// 位于 `pos`（图片坐标）的可拖动部分
+    pub fn handle_at(&self, pos: Pos2, tolerance: f32) -> DragHandle {
+        match &self.tool {
+            ToolType::Callout { target, .. } if target.distance(pos) <= tolerance * 2.0 => DragHandle::Tip,
+            ToolType::Polygon(points) => points
+                .iter()
+                .position(|p| p.distance(pos) <= tolerance * 2.0)
+                .map_or(DragHandle::Body, DragHandle::Vertex),
+            _ => DragHandle::Body,
+        }
+    }
+
+    /// 拖动标注的一部分（图片坐标）
+    pub fn drag(&mut self, handle: DragHandle, delta: Vec2) {
+        match (&mut self.tool, handle) {
+            (ToolType::Callout { target, .. }, DragHandle::Tip) => *target += delta,
+            (ToolType::Callout { pos, .. }, DragHandle::Body) => *pos += delta,
+            (ToolType::Polygon(points), DragHandle::Vertex(i)) => {
+                if let Some(p) = points.get_mut(i) {
+                    *p += delta;
+                }
+            }
+            _ => self.translate(delta),
+        }
+    }
+
+    /// 整体平移（图片坐标）
+    pub fn translate(&mut self, delta: Vec2) {
+        match &mut self.tool {
+            ToolType::Rect(rect) | ToolType::Mosaic(rect) => *rect = rect.translate(delta),
+            ToolType::Ellipse(ellipse) => ellipse.center += delta,
+            ToolType::Arrow(arrow) => arrow.points.iter_mut().for_each(|p| *p += delta),
+            ToolType::Line(s, e) => {
+                *s += delta;
+                *e += delta;
+            }
+            ToolType::Pencil(points) | ToolType::Polygon(points) => points.iter_mut().for_each(|p| *p += delta),
+            ToolType::Number(c, _) => c.center += delta,
+            ToolType::Text { pos, .. } => *pos += delta,
+            ToolType::Callout { target, pos, .. } => {
+                *target += delta;
+                *pos += delta;
+            }
+        }
+    }
+
+    /// 在画布上绘制，马赛克的色块需要从原图取色
+    pub fn draw_with_image(&self, helper: &AppHelper, painter: &Painter, image: &RgbaImage, cache: &mut MosaicCache) {
+        if let ToolType::Mosaic(rect) = &self.tool {
+            for &(cell, color) in cache.cells(image, *rect, mosaic_block(self.stroke_width.into())) {
+                let screen_rect = Rect::from_min_max(
+                    helper.image_to_screen(cell.min),
+                    helper.image_to_screen(cell.max),
+                );
+                painter.rect_filled(screen_rect, 0.0, color);
+            }
+        }
+        self.draw(helper, painter);
     }
 
     pub fn draw(&self, helper: &AppHelper, painter: &Painter) {
@@ -53,6 +346,9 @@ impl Operator {
                     helper.image_to_screen(rect.min),
                     helper.image_to_screen(rect.max),
                 );
+                if let Some(fill) = self.fill_color {
+                    painter.rect_filled(screen_rect, 0.0, fill);
+                }
                 painter.rect_stroke(
                     screen_rect,
                     0.0,
@@ -103,12 +399,207 @@ impl Operator {
                 let center = helper.image_to_screen(c.center);
                 let radius = c.radius * zoom;
                 painter.circle(center, radius, c.fill, c.stroke);
-                painter.text(center, Align2::CENTER_CENTER, n + 1, FontId::proportional(radius), Color32::WHITE);
+                painter.text(center, Align2::CENTER_CENTER, n + 1, FontId::proportional(radius), number_text_color(c.fill, color));
+            }
+            ToolType::Text { pos, content, style } => {
+                let screen_pos = helper.image_to_screen(*pos);
+                let galley = painter.layout_job(style.layout_job(content, zoom, color));
+                if let Some(background) = style.background {
+                    let mut text_rect = galley.rect.translate(screen_pos.to_vec2());
+                    if style.bold {
+                        text_rect.max.x += style.bold_offset() * zoom;
+                    }
+                    let box_rect = text_rect.expand(style.padding * zoom);
+                    let radius = style.corner_radius * zoom;
+                    if let Some(shadow) = style.shadow {
+                        painter.rect_filled(box_rect.translate(style.shadow_offset() * zoom), radius, shadow);
+                    }
+                    painter.rect_filled(box_rect, radius, background);
+                }
+                paint_glyphs(painter, screen_pos, galley, style, color, zoom);
             }
-            ToolType::Text { pos, content } => {
+            ToolType::Callout { target, pos, content, style } => {
                 let screen_pos = helper.image_to_screen(*pos);
-                painter.text(screen_pos, Align2::LEFT_TOP, content, FontId::proportional(16.0 * zoom), color);
+                let galley = painter.layout_job(style.layout_job(content, zoom, color));
+                let mut text_rect = galley.rect.translate(screen_pos.to_vec2());
+                if style.bold {
+                    text_rect.max.x += style.bold_offset() * zoom;
+                }
+                let box_rect = text_rect.expand(style.padding * zoom);
+                let tail = callout_tail(box_rect, helper.image_to_screen(*target));
+                let radius = style.corner_radius * zoom;
+                let fill = style.background.unwrap_or(CALLOUT_FILL);
+
+                if let Some(shadow) = style.shadow {
+                    let offset = style.shadow_offset() * zoom;
+                    if let Some(tail) = tail {
+                        painter.add(PathShape::convex_polygon(
+                            tail.iter().map(|p| *p + offset).collect(),
+                            shadow,
+                            Stroke::NONE,
+                        ));
+                    }
+                    painter.rect_filled(box_rect.translate(offset), radius, shadow);
+                }
+                // 先画两倍宽的边框再填充，填充盖住内侧一半，只留下外轮廓
+                let outline = Stroke::new(width * 2.0, color);
+                if let Some(tail) = tail {
+                    painter.add(PathShape::closed_line(tail.to_vec(), outline));
+                }
+                painter.rect_stroke(box_rect, radius, outline, StrokeKind::Middle);
+                if let Some(tail) = tail {
+                    painter.add(PathShape::convex_polygon(tail.to_vec(), fill, Stroke::NONE));
+                }
+                painter.rect_filled(box_rect, radius, fill);
+
+                // 气泡已经是背景，文字不再画阴影
+                let style = TextStyle {
+                    shadow: None,
+                    ..style.clone()
+                };
+                paint_glyphs(painter, screen_pos, galley, &style, color, zoom);
+            }
+            // 色块由 draw_with_image 绘制
+            ToolType::Mosaic(_) => {}
+            ToolType::Polygon(points) => {
+                let points: Vec<Pos2> = points.iter().map(|p| helper.image_to_screen(*p)).collect();
+                if let Some(fill) = self.fill_color {
+                    painter.add(polygon_mesh(&points, fill));
+                }
+                painter.add(PathShape::closed_line(points, PathStroke::new(width, color)));
             }
         }
     }
 }
+
+/// 填充任意简单多边形的网格：egui 的路径只能正确填充凸多边形
+fn polygon_mesh(points: &[Pos2], color: Color32) -> Mesh {
+    let mut mesh = Mesh::default();
+    for &p in points {
+        mesh.colored_vertex(p, color);
+    }
+    for [a, b, c] in triangulate(points) {
+        mesh.add_triangle(a as u32, b as u32, c as u32);
+    }
+    mesh
+}
+
+/// 耳切法三角化简单多边形，返回顶点序号；自相交时剩余部分按扇形连接
+pub fn triangulate(points: &[Pos2]) -> Vec<[usize; 3]> {
+    let cross = |a: Pos2, b: Pos2, c: Pos2| (b - a).x * (c - a).y - (b - a).y * (c - a).x;
+    // 顶点顺序的方向，耳朵的转向要与之一致
+    let orientation: f32 = (0..points.len())
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            a.x * b.y - b.x * a.y
+        })
+        .sum::<f32>()
+        .signum();
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::new();
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]);
+            let (pa, pb, pc) = (points[a], points[b], points[c]);
+            cross(pa, pb, pc) * orientation > 0.0
+                && remaining.iter().filter(|&&j| j != a && j != b && j != c).all(|&j| {
+                    let p = points[j];
+                    let inside = [cross(pa, pb, p), cross(pb, pc, p), cross(pc, pa, p)]
+                        .iter()
+                        .all(|&d| d * orientation >= 0.0);
+                    !inside
+                })
+        });
+        let Some(i) = ear else {
+            break;
+        };
+        triangles.push([remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]]);
+        remaining.remove(i);
+    }
+    for i in 1..remaining.len().saturating_sub(1) {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+    triangles
+}
+
+/// 按样式绘制排好版的文字（屏幕坐标）
+fn paint_glyphs(
+    painter: &Painter,
+    screen_pos: Pos2,
+    galley: std::sync::Arc<egui::Galley>,
+    style: &TextStyle,
+    color: Color32,
+    zoom: f32,
+) {
+    // 没有粗体字重、描边效果时，错开绘制多次模拟
+    for (offset, pass_color) in style.glyph_passes(color) {
+        painter.galley_with_override_text_color(screen_pos + offset * zoom, galley.clone(), pass_color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Pos2, pos2};
+
+    use super::triangulate;
+
+    /// 有向面积，逆时针为正
+    fn signed_area(a: Pos2, b: Pos2, c: Pos2) -> f32 {
+        ((b - a).x * (c - a).y - (b - a).y * (c - a).x) / 2.0
+    }
+
+    /// 三角形数为 n - 2，方向与多边形一致，面积之和等于多边形面积（没有重叠和遗漏）
+    fn assert_covers(points: &[Pos2], area: f32) {
+        let triangles = triangulate(points);
+        assert_eq!(triangles.len(), points.len() - 2);
+        let mut total = 0.0;
+        for [a, b, c] in triangles {
+            let part = signed_area(points[a], points[b], points[c]) * area.signum();
+            assert!(part >= 0.0, "triangle {a} {b} {c} is reversed");
+            total += part;
+        }
+        assert!((total - area.abs()).abs() < 1e-3, "area {total} != {area}");
+    }
+
+    #[test]
+    fn convex() {
+        let square = [pos2(0.0, 0.0), pos2(4.0, 0.0), pos2(4.0, 4.0), pos2(0.0, 4.0)];
+        assert_covers(&square, 16.0);
+        let reversed: Vec<_> = square.iter().rev().copied().collect();
+        assert_covers(&reversed, -16.0);
+    }
+
+    #[test]
+    fn concave() {
+        // 凹口朝上的 U 形，凹顶点处的扇形会盖到凹口外
+        let u = [
+            pos2(0.0, 0.0),
+            pos2(6.0, 0.0),
+            pos2(6.0, 6.0),
+            pos2(4.0, 6.0),
+            pos2(4.0, 2.0),
+            pos2(2.0, 2.0),
+            pos2(2.0, 6.0),
+            pos2(0.0, 6.0),
+        ];
+        assert_covers(&u, 28.0);
+        let reversed: Vec<_> = u.iter().rev().copied().collect();
+        assert_covers(&reversed, -28.0);
+    }
+
+    #[test]
+    fn collinear_vertices() {
+        // 边上多出的顶点不能成为耳朵，但也不能让三角化提前结束
+        let square = [
+            pos2(0.0, 0.0),
+            pos2(2.0, 0.0),
+            pos2(4.0, 0.0),
+            pos2(4.0, 4.0),
+            pos2(2.0, 4.0),
+            pos2(0.0, 4.0),
+        ];
+        assert_covers(&square, 16.0);
+    }
+}
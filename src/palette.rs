@@ -0,0 +1,176 @@
+use std::path::Path;
+
+use egui::Color32;
+use serde::{Deserialize, Serialize};
+
+use crate::{color_picker, settings::HexColor};
+
+/// 可以导入、导出的调色板文件扩展名
+pub const PALETTE_EXTENSIONS: &[&str] = &["gpl", "txt", "hex"];
+
+/// 命名调色板
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Palette {
+    pub name: String,
+    pub colors: Vec<HexColor>,
+}
+
+impl Palette {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            colors: Vec::new(),
+        }
+    }
+
+    /// 调色板中的颜色
+    pub fn colors(&self) -> impl Iterator<Item = Color32> + '_ {
+        self.colors.iter().map(|c| c.0)
+    }
+
+    /// 从文件导入，`.gpl` 按 GIMP 调色板解析，其他按每行一个十六进制颜色解析
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Imported".to_owned());
+        if text.trim_start().starts_with("GIMP Palette") {
+            Self::parse_gpl(&text, name)
+        } else {
+            Self::parse_hex_list(&text, name)
+        }
+    }
+
+    /// 导出到文件，`.gpl` 保存为 GIMP 调色板（不含透明度），其他保存为十六进制列表
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let is_gpl = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("gpl"));
+        let text = if is_gpl { self.to_gpl() } else { self.to_hex_list() };
+        std::fs::write(path, text).map_err(|e| e.to_string())
+    }
+
+    /// 解析 GIMP 调色板：`Name:` 行给出名称，颜色行为 `R G B [名称]`
+    fn parse_gpl(text: &str, fallback_name: String) -> Result<Self, String> {
+        let mut palette = Self::new(fallback_name);
+        for (i, line) in text.lines().enumerate().skip(1) {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("Columns:") {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix("Name:") {
+                palette.name = name.trim().to_owned();
+                continue;
+            }
+            let channels: Vec<u8> = line
+                .split_whitespace()
+                .take(3)
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("line {}: invalid color `{line}`", i + 1))?;
+            let [r, g, b] = channels[..] else {
+                return Err(format!("line {}: invalid color `{line}`", i + 1));
+            };
+            palette.colors.push(HexColor(Color32::from_rgb(r, g, b)));
+        }
+        Ok(palette)
+    }
+
+    /// 解析十六进制颜色列表，每行一个 `#RRGGBB` 或 `#RRGGBBAA`
+    ///
+    /// `;` 开头的行为注释，颜色之前的第一行注释作为调色板名称
+    fn parse_hex_list(text: &str, fallback_name: String) -> Result<Self, String> {
+        let mut palette = Self::new(fallback_name);
+        let mut named = false;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if let Some(comment) = line.strip_prefix(';') {
+                if !named && palette.colors.is_empty() && !comment.trim().is_empty() {
+                    palette.name = comment.trim().to_owned();
+                    named = true;
+                }
+                continue;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let color = color_picker::hex_to_color(line)
+                .ok_or_else(|| format!("line {}: invalid color `{line}`", i + 1))?;
+            palette.colors.push(HexColor(color));
+        }
+        Ok(palette)
+    }
+
+    fn to_gpl(&self) -> String {
+        let mut text = format!("GIMP Palette\nName: {}\nColumns: 5\n#\n", self.name);
+        for color in self.colors() {
+            let [r, g, b, _] = color.to_srgba_unmultiplied();
+            let hex = color_picker::color_to_hex(color);
+            text.push_str(&format!("{r:3} {g:3} {b:3}\t{}\n", &hex[..7]));
+        }
+        text
+    }
+
+    fn to_hex_list(&self) -> String {
+        let mut text = format!("; {}\n", self.name);
+        for color in self.colors() {
+            text.push_str(&color_picker::color_to_hex(color));
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn palette(colors: &[Color32]) -> Palette {
+        Palette {
+            name: "Test".to_owned(),
+            colors: colors.iter().copied().map(HexColor).collect(),
+        }
+    }
+
+    #[test]
+    fn gpl_round_trip() {
+        let palette = palette(&[Color32::RED, Color32::from_rgb(1, 128, 254), Color32::BLACK]);
+        let text = palette.to_gpl();
+        assert!(text.starts_with("GIMP Palette\nName: Test\n"));
+        assert_eq!(Palette::parse_gpl(&text, "Other".to_owned()), Ok(palette));
+    }
+
+    #[test]
+    fn gpl_drops_alpha() {
+        let color = Color32::from_rgba_unmultiplied(10, 20, 30, 40);
+        let parsed = Palette::parse_gpl(&palette(&[color]).to_gpl(), String::new()).unwrap();
+        // Color32 预乘保存，半透明色的 RGB 以反预乘后的值为准
+        let [r, g, b, _] = color.to_srgba_unmultiplied();
+        assert_eq!(parsed.colors, vec![HexColor(Color32::from_rgb(r, g, b))]);
+    }
+
+    #[test]
+    fn gpl_skips_comments_and_keeps_fallback_name() {
+        let text = "GIMP Palette\n# comment\nColumns: 3\n\n  0   0 255 Blue\n";
+        let parsed = Palette::parse_gpl(text, "file".to_owned()).unwrap();
+        assert_eq!(parsed.name, "file");
+        assert_eq!(parsed.colors, vec![HexColor(Color32::BLUE)]);
+    }
+
+    #[test]
+    fn gpl_rejects_malformed_colors() {
+        for line in ["255 0", "256 0 0", "-1 0 0", "red green blue", "0x10 0 0"] {
+            let text = format!("GIMP Palette\nName: Bad\n{line}\n");
+            let err = Palette::parse_gpl(&text, String::new()).unwrap_err();
+            assert!(err.starts_with("line 3:"), "{line:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn hex_list_round_trip() {
+        let palette = palette(&[Color32::from_rgba_unmultiplied(255, 0, 0, 128), Color32::WHITE]);
+        assert_eq!(Palette::parse_hex_list(&palette.to_hex_list(), String::new()), Ok(palette));
+        assert!(Palette::parse_hex_list("#FFFFFF\nnope\n", String::new()).unwrap_err().starts_with("line 2:"));
+    }
+}
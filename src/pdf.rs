@@ -0,0 +1,562 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use ab_glyph::{Font, FontArc, GlyphId, PxScale};
+use egui::{Color32, Pos2, Rect, Vec2};
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use pdf_writer::types::{
+    CidFontType, FontFlags, LineJoinStyle, SystemInfo, TextRenderingMode, UnicodeCmap,
+};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect as PdfRect, Ref, Str};
+
+use crate::{
+    document::Document,
+    drawable::{ITALIC_SHEAR, Layer, TextLayout, layout_text},
+    font::FontSet,
+    operators::{
+        CALLOUT_FILL, MASK_COLOR, Operator, TextAlign, TextStyle, ToolType, callout_tail, number_text_color,
+    },
+};
+
+/// 压缩级别（0-10）
+const COMPRESSION: u8 = 6;
+
+/// 导出为 PDF：每张图片一页，原图为位图 XObject，标注为矢量路径和可选中的文字
+///
+/// 页面大小与图片一致，1 像素对应 1 pt；`Layer::Overlay` 时不嵌入原图，页面背景透明
+pub fn render(docs: &[&Document], fonts: &FontSet, scale: f32, layer: Layer) -> Result<Vec<u8>, String> {
+    let mut pdf = Pdf::new();
+    let mut next_id = Ref::new(1);
+    let mut alloc = || next_id.bump();
+
+    let catalog_id = alloc();
+    let pages_id = alloc();
+    // 字体对象在所有页面写完、知道用到哪些字形后才写入
+    let font_ids: Vec<Ref> = fonts.fonts().iter().map(|_| alloc()).collect();
+    let mut glyphs = vec![BTreeMap::new(); font_ids.len()];
+
+    let mut page_ids = Vec::with_capacity(docs.len());
+    for doc in docs {
+        let page_id = alloc();
+        let content_id = alloc();
+        page_ids.push(page_id);
+
+        let (width, height) = doc.original_image.dimensions();
+        let composite = layer == Layer::Composite;
+        let image_id = composite.then(|| {
+            let image_id = alloc();
+            write_image(&mut pdf, image_id, &mut alloc, doc);
+            image_id
+        });
+
+        let mut page = PageContent::new(fonts, &mut glyphs, layer);
+        // 导出倍率只改变页面尺寸，原图仍按原始分辨率嵌入
+        page.content.transform([scale, 0.0, 0.0, scale, 0.0, 0.0]);
+        if image_id.is_some() {
+            page.content.save_state();
+            page.content.transform([width as f32, 0.0, 0.0, height as f32, 0.0, 0.0]);
+            page.content.x_object(Name(b"Im0"));
+            page.content.restore_state();
+        }
+        // 之后使用图片坐标：原点在左上角，y 轴向下
+        page.content.transform([1.0, 0.0, 0.0, -1.0, 0.0, height as f32]);
+        for op in &doc.operators {
+            page.operator(op);
+        }
+        let PageContent { content, alphas, used_fonts, .. } = page;
+        let content = compress_to_vec_zlib(&content.finish(), COMPRESSION);
+        pdf.stream(content_id, &content).filter(Filter::FlateDecode);
+
+        let alpha_ids: Vec<(String, Ref)> = alphas
+            .iter()
+            .map(|&(stroke, alpha)| {
+                let id = alloc();
+                let mut state = pdf.ext_graphics(id);
+                let value = f32::from(alpha) / 255.0;
+                if stroke {
+                    state.stroking_alpha(value);
+                } else {
+                    state.non_stroking_alpha(value);
+                }
+                (alpha_name(stroke, alpha), id)
+            })
+            .collect();
+
+        let mut page = pdf.page(page_id);
+        page.parent(pages_id)
+            .media_box(PdfRect::new(0.0, 0.0, width as f32 * scale, height as f32 * scale))
+            .contents(content_id);
+        let mut resources = page.resources();
+        if let Some(image_id) = image_id {
+            resources.x_objects().pair(Name(b"Im0"), image_id);
+        }
+        if !used_fonts.is_empty() {
+            let mut dict = resources.fonts();
+            for &i in &used_fonts {
+                dict.pair(Name(format!("F{i}").as_bytes()), font_ids[i]);
+            }
+        }
+        if !alpha_ids.is_empty() {
+            let mut dict = resources.ext_g_states();
+            for (name, id) in &alpha_ids {
+                dict.pair(Name(name.as_bytes()), *id);
+            }
+        }
+        resources.finish();
+        page.finish();
+    }
+
+    for (i, used) in glyphs.iter().enumerate() {
+        if !used.is_empty() {
+            write_font(&mut pdf, &mut alloc, font_ids[i], &fonts.fonts()[i], used)?;
+        }
+    }
+
+    pdf.pages(pages_id).kids(page_ids.iter().copied()).count(page_ids.len() as i32);
+    pdf.catalog(catalog_id).pages(pages_id);
+    Ok(pdf.finish())
+}
+
+/// 写入马赛克区域已打码的原图，带透明度时附加 SMask
+fn write_image(pdf: &mut Pdf, id: Ref, alloc: &mut impl FnMut() -> Ref, doc: &Document) {
+    let img = doc.redacted_image();
+    let (width, height) = img.dimensions();
+    let rgb: Vec<u8> = img.pixels().flat_map(|p| [p[0], p[1], p[2]]).collect();
+    let has_alpha = img.pixels().any(|p| p[3] < 255);
+
+    let mask_id = has_alpha.then(|| {
+        let alpha: Vec<u8> = img.pixels().map(|p| p[3]).collect();
+        let mask_id = alloc();
+        let data = compress_to_vec_zlib(&alpha, COMPRESSION);
+        let mut mask = pdf.image_xobject(mask_id, &data);
+        mask.filter(Filter::FlateDecode);
+        mask.width(width as i32).height(height as i32).bits_per_component(8);
+        mask.color_space().device_gray();
+        mask_id
+    });
+
+    let data = compress_to_vec_zlib(&rgb, COMPRESSION);
+    let mut image = pdf.image_xobject(id, &data);
+    image.filter(Filter::FlateDecode);
+    image.width(width as i32).height(height as i32).bits_per_component(8);
+    image.color_space().device_rgb();
+    if let Some(mask_id) = mask_id {
+        image.s_mask(mask_id);
+    }
+}
+
+/// 子集字体名的六个大写字母前缀，由字体和字形集合决定，不同子集不会重名
+fn subset_tag(loaded: &crate::font::LoadedFont, used: &BTreeMap<u16, char>) -> String {
+    let mut hasher = DefaultHasher::new();
+    loaded.name.hash(&mut hasher);
+    used.keys().for_each(|id| id.hash(&mut hasher));
+    let mut hash = Hasher::finish(&hasher);
+    (0..6)
+        .map(|_| {
+            let c = char::from(b'A' + (hash % 26) as u8);
+            hash /= 26;
+            c
+        })
+        .collect()
+}
+
+/// BaseFont 只能用 ASCII：优先用去掉其他字符的字体族名，为空时（如中文名）用 PostScript 名称
+fn base_font_name(loaded: &crate::font::LoadedFont) -> String {
+    let sanitize = |name: &str| name.replace(|c: char| !(c.is_ascii_alphanumeric() || c == '-'), "");
+    [Some(loaded.name.as_str()), loaded.postscript_name.as_deref()]
+        .into_iter()
+        .flatten()
+        .map(sanitize)
+        .find(|name| !name.is_empty())
+        .unwrap_or_else(|| "Font".to_owned())
+}
+
+/// 嵌入子集化的字体：Identity-H 编码，字符码即字形序号，附带 ToUnicode 以便复制文字
+fn write_font(
+    pdf: &mut Pdf,
+    alloc: &mut impl FnMut() -> Ref,
+    id: Ref,
+    loaded: &crate::font::LoadedFont,
+    used: &BTreeMap<u16, char>,
+) -> Result<(), String> {
+    let cid_id = alloc();
+    let descriptor_id = alloc();
+    let cmap_id = alloc();
+    let file_id = alloc();
+
+    let font = loaded.font();
+    let units = font.units_per_em().unwrap_or(1000.0);
+    let to_pdf = |v: f32| v * 1000.0 / units;
+    let base_name = format!("{}+{}", subset_tag(loaded, used), base_font_name(loaded));
+    let base_font = Name(base_name.as_bytes());
+
+    let glyph_ids: Vec<u16> = used.keys().copied().collect();
+    let subset = subsetter::subset(loaded.data(), loaded.index(), subsetter::Profile::pdf(&glyph_ids))
+        .map_err(|e| format!("failed to subset font {}: {e}", loaded.name))?;
+    let cff = is_cff(loaded.data(), loaded.index());
+
+    pdf.type0_font(id)
+        .base_font(base_font)
+        .encoding_predefined(Name(b"Identity-H"))
+        .descendant_font(cid_id)
+        .to_unicode(cmap_id);
+
+    let system_info = SystemInfo {
+        registry: Str(b"Adobe"),
+        ordering: Str(b"Identity"),
+        supplement: 0,
+    };
+    let mut cid = pdf.cid_font(cid_id);
+    cid.subtype(if cff { CidFontType::Type0 } else { CidFontType::Type2 })
+        .base_font(base_font)
+        .system_info(system_info)
+        .font_descriptor(descriptor_id)
+        .default_width(0.0);
+    if !cff {
+        cid.cid_to_gid_map_predefined(Name(b"Identity"));
+    }
+    let mut widths = cid.widths();
+    for &gid in &glyph_ids {
+        widths.consecutive(gid, [to_pdf(font.h_advance_unscaled(GlyphId(gid)))]);
+    }
+    widths.finish();
+    cid.finish();
+
+    let ascent = to_pdf(font.ascent_unscaled());
+    let descent = to_pdf(font.descent_unscaled());
+    let mut descriptor = pdf.font_descriptor(descriptor_id);
+    descriptor
+        .name(base_font)
+        .flags(FontFlags::SYMBOLIC)
+        .bbox(PdfRect::new(0.0, descent, 1000.0, ascent))
+        .italic_angle(0.0)
+        .ascent(ascent)
+        .descent(descent)
+        .cap_height(ascent)
+        .stem_v(80.0);
+    if cff {
+        descriptor.font_file3(file_id);
+    } else {
+        descriptor.font_file2(file_id);
+    }
+    descriptor.finish();
+
+    let mut cmap = UnicodeCmap::new(Name(b"Custom"), system_info);
+    for (&gid, &c) in used {
+        cmap.pair(gid, c);
+    }
+    pdf.cmap(cmap_id, &cmap.finish());
+
+    let data = compress_to_vec_zlib(&subset, COMPRESSION);
+    let mut stream = pdf.stream(file_id, &data);
+    stream.filter(Filter::FlateDecode);
+    if cff {
+        stream.pair(Name(b"Subtype"), Name(b"OpenType"));
+    }
+    Ok(())
+}
+
+/// 字体（字体集合时为第 `index` 个字体）是否为 CFF 轮廓
+fn is_cff(data: &[u8], index: u32) -> bool {
+    let offset = if data.starts_with(b"ttcf") {
+        let entry = 12 + index as usize * 4;
+        data.get(entry..entry + 4)
+            .map_or(0, |b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    } else {
+        0
+    };
+    data.get(offset..offset + 4) == Some(b"OTTO")
+}
+
+fn alpha_name(stroke: bool, alpha: u8) -> String {
+    format!("{}{alpha}", if stroke { "SA" } else { "FA" })
+}
+
+/// 一页的内容流及其用到的资源
+struct PageContent<'a> {
+    content: Content,
+    fonts: &'a FontSet,
+    /// 每个字体用到的字形及对应的字符
+    glyphs: &'a mut Vec<BTreeMap<u16, char>>,
+    used_fonts: BTreeSet<usize>,
+    /// 用到的透明度：(是否为描边, alpha)
+    alphas: BTreeSet<(bool, u8)>,
+    /// 只导出标注层时马赛克画成遮罩，合成时已打码在嵌入的原图上
+    layer: Layer,
+}
+
+impl<'a> PageContent<'a> {
+    fn new(fonts: &'a FontSet, glyphs: &'a mut Vec<BTreeMap<u16, char>>, layer: Layer) -> Self {
+        Self {
+            content: Content::new(),
+            fonts,
+            glyphs,
+            layer,
+            used_fonts: BTreeSet::new(),
+            alphas: BTreeSet::new(),
+        }
+    }
+
+    fn operator(&mut self, op: &Operator) {
+        let width = f32::from(op.stroke_width);
+        match &op.tool {
+            ToolType::Rect(rect) => {
+                self.content.rect(rect.left(), rect.top(), rect.width(), rect.height());
+                self.paint(op.fill_color, Some((op.color, width)));
+            }
+            ToolType::Ellipse(ellipse) => {
+                self.ellipse(ellipse.center, ellipse.radius);
+                self.paint(op.fill_color, Some((op.color, width)));
+            }
+            ToolType::Arrow(arrow) => {
+                self.polyline(&arrow.points, true);
+                self.paint(op.fill_color, Some((op.color, width)));
+            }
+            ToolType::Polygon(points) => {
+                self.polyline(points, true);
+                self.paint(op.fill_color, Some((op.color, width)));
+            }
+            ToolType::Line(start, end) => {
+                self.polyline(&[*start, *end], false);
+                self.paint(None, Some((op.color, width)));
+            }
+            ToolType::Pencil(points) => {
+                self.polyline(points, false);
+                self.paint(None, Some((op.color, width)));
+            }
+            ToolType::Number(c, n) => {
+                self.ellipse(c.center, Vec2::splat(c.radius));
+                self.paint(Some(c.fill), Some((op.color, width)));
+                let style = TextStyle {
+                    size: c.radius,
+                    align: TextAlign::Center,
+                    ..Default::default()
+                };
+                if let Some(layout) = layout_text(c.center, &(n + 1).to_string(), &style, self.fonts, true) {
+                    self.text(&layout, &style, number_text_color(c.fill, op.color));
+                }
+            }
+            ToolType::Text { pos, content, style } => {
+                let Some(layout) = layout_text(*pos, content, style, self.fonts, false) else {
+                    return;
+                };
+                if let Some(background) = style.background {
+                    let box_rect = layout.rect.expand(style.padding);
+                    if let Some(shadow) = style.shadow {
+                        self.rounded_rect(box_rect.translate(style.shadow_offset()), style.corner_radius);
+                        self.paint(Some(shadow), None);
+                    }
+                    self.rounded_rect(box_rect, style.corner_radius);
+                    self.paint(Some(background), None);
+                }
+                self.text(&layout, style, op.color);
+            }
+            ToolType::Callout { target, pos, content, style } => {
+                let Some(layout) = layout_text(*pos, content, style, self.fonts, false) else {
+                    return;
+                };
+                let box_rect = layout.rect.expand(style.padding);
+                let tail = callout_tail(box_rect, *target);
+                let fill = style.background.unwrap_or(CALLOUT_FILL);
+                let bubble = |page: &mut Self, offset: Vec2| {
+                    if let Some(tail) = tail {
+                        page.polyline(&tail.map(|p| p + offset), true);
+                    }
+                    page.rounded_rect(box_rect.translate(offset), style.corner_radius);
+                };
+
+                if let Some(shadow) = style.shadow {
+                    bubble(self, style.shadow_offset());
+                    self.paint(Some(shadow), None);
+                }
+                // 与位图导出相同：两倍宽的边框被填充盖住内侧一半，只留下外轮廓
+                bubble(self, Vec2::ZERO);
+                self.paint(None, Some((op.color, width * 2.0)));
+                bubble(self, Vec2::ZERO);
+                self.paint(Some(fill), None);
+
+                let style = TextStyle {
+                    shadow: None,
+                    ..style.clone()
+                };
+                self.text(&layout, &style, op.color);
+            }
+            ToolType::Mosaic(rect) => {
+                if self.layer == Layer::Overlay {
+                    self.content.rect(rect.left(), rect.top(), rect.width(), rect.height());
+                    self.paint(Some(MASK_COLOR), None);
+                }
+            }
+        }
+    }
+
+    /// 按指定的填充色和描边填充当前路径
+    fn paint(&mut self, fill: Option<Color32>, stroke: Option<(Color32, f32)>) {
+        self.content.save_state();
+        if let Some(fill) = fill {
+            self.set_fill(fill);
+        }
+        if let Some((color, width)) = stroke {
+            self.set_stroke(color, width);
+        }
+        match (fill, stroke) {
+            (Some(_), Some(_)) => self.content.fill_nonzero_and_stroke(),
+            (Some(_), None) => self.content.fill_nonzero(),
+            (None, Some(_)) => self.content.stroke(),
+            (None, None) => self.content.end_path(),
+        };
+        self.content.restore_state();
+    }
+
+    fn set_fill(&mut self, color: Color32) {
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        self.content.set_fill_rgb(unit(r), unit(g), unit(b));
+        self.set_alpha(false, a);
+    }
+
+    fn set_stroke(&mut self, color: Color32, width: f32) {
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        self.content.set_stroke_rgb(unit(r), unit(g), unit(b));
+        self.content.set_line_width(width);
+        self.set_alpha(true, a);
+    }
+
+    fn set_alpha(&mut self, stroke: bool, alpha: u8) {
+        if alpha < 255 {
+            self.alphas.insert((stroke, alpha));
+            self.content.set_parameters(Name(alpha_name(stroke, alpha).as_bytes()));
+        }
+    }
+
+    fn polyline(&mut self, points: &[Pos2], close: bool) {
+        let Some((first, rest)) = points.split_first() else {
+            return;
+        };
+        self.content.move_to(first.x, first.y);
+        for p in rest {
+            self.content.line_to(p.x, p.y);
+        }
+        if close {
+            self.content.close_path();
+        }
+    }
+
+    /// 用四段三次贝塞尔曲线近似椭圆
+    fn ellipse(&mut self, center: Pos2, radius: Vec2) {
+        let k = 0.552_284_8;
+        let (cx, cy, rx, ry) = (center.x, center.y, radius.x, radius.y);
+        self.content.move_to(cx + rx, cy);
+        self.content.cubic_to(cx + rx, cy + ry * k, cx + rx * k, cy + ry, cx, cy + ry);
+        self.content.cubic_to(cx - rx * k, cy + ry, cx - rx, cy + ry * k, cx - rx, cy);
+        self.content.cubic_to(cx - rx, cy - ry * k, cx - rx * k, cy - ry, cx, cy - ry);
+        self.content.cubic_to(cx + rx * k, cy - ry, cx + rx, cy - ry * k, cx + rx, cy);
+        self.content.close_path();
+    }
+
+    fn rounded_rect(&mut self, rect: Rect, radius: f32) {
+        let r = radius.min(rect.width() / 2.0).min(rect.height() / 2.0).max(0.0);
+        if r <= 0.0 {
+            self.content.rect(rect.left(), rect.top(), rect.width(), rect.height());
+            return;
+        }
+        let k = r * 0.552_284_8;
+        let (l, t, rt, b) = (rect.left(), rect.top(), rect.right(), rect.bottom());
+        self.content.move_to(l + r, t);
+        self.content.line_to(rt - r, t);
+        self.content.cubic_to(rt - r + k, t, rt, t + r - k, rt, t + r);
+        self.content.line_to(rt, b - r);
+        self.content.cubic_to(rt, b - r + k, rt - r + k, b, rt - r, b);
+        self.content.line_to(l + r, b);
+        self.content.cubic_to(l + r - k, b, l, b - r + k, l, b - r);
+        self.content.line_to(l, t + r);
+        self.content.cubic_to(l, t + r - k, l + r - k, t, l + r, t);
+        self.content.close_path();
+    }
+
+    /// 绘制排好版的文字：阴影、描边和正文依次绘制，粗体用同色描边加粗
+    fn text(&mut self, layout: &TextLayout, style: &TextStyle, color: Color32) {
+        if let Some(shadow) = style.shadow
+            && style.background.is_none()
+        {
+            self.text_pass(layout, style, style.shadow_offset(), shadow, None);
+        }
+        if let Some(outline) = style.outline {
+            self.text_pass(layout, style, Vec2::ZERO, outline, Some(style.outline_width * 2.0));
+        }
+        self.text_pass(layout, style, Vec2::ZERO, color, None);
+    }
+
+    /// 绘制一遍文字，`outline` 为描边宽度，为 None 时填充
+    fn text_pass(&mut self, layout: &TextLayout, style: &TextStyle, offset: Vec2, color: Color32, outline: Option<f32>) {
+        let shear = if style.italic { ITALIC_SHEAR } else { 0.0 };
+        self.content.save_state();
+        match outline {
+            Some(width) => {
+                self.set_stroke(color, width);
+                self.content.set_line_join(LineJoinStyle::RoundJoin);
+            }
+            None => {
+                self.set_fill(color);
+                if style.bold {
+                    self.set_stroke(color, style.bold_offset());
+                }
+            }
+        }
+        let mode = match outline {
+            Some(_) => TextRenderingMode::Stroke,
+            None if style.bold => TextRenderingMode::FillStroke,
+            None => TextRenderingMode::Fill,
+        };
+
+        self.content.begin_text();
+        self.content.set_text_rendering_mode(mode);
+        for (line, start) in &layout.lines {
+            let mut pos = *start + offset;
+            for (font_index, run) in self.runs(line.trim_end()) {
+                let font = self.fonts.fonts()[font_index].font();
+                let size = font_size(font, layout.scale);
+                let mut encoded = Vec::with_capacity(run.len() * 2);
+                let mut advance = 0.0;
+                for c in run.chars() {
+                    let gid = font.glyph_id(c);
+                    self.glyphs[font_index].entry(gid.0).or_insert(c);
+                    encoded.extend_from_slice(&gid.0.to_be_bytes());
+                    advance += font.h_advance_unscaled(gid) / font.units_per_em().unwrap_or(1000.0) * size;
+                }
+                self.used_fonts.insert(font_index);
+                self.content.set_font(Name(format!("F{font_index}").as_bytes()), size);
+                // 内容流的 y 轴向下，文字矩阵再翻转回来
+                self.content.set_text_matrix([1.0, 0.0, shear, -1.0, pos.x, pos.y]);
+                self.content.show(Str(&encoded));
+                pos.x += advance;
+            }
+        }
+        self.content.end_text();
+        self.content.restore_state();
+    }
+
+    /// 按字体链把一行文字拆成连续使用同一字体的片段
+    fn runs(&self, line: &str) -> Vec<(usize, String)> {
+        let mut runs: Vec<(usize, String)> = Vec::new();
+        for c in line.chars() {
+            let Some(index) = self.fonts.index_for(c) else {
+                continue;
+            };
+            match runs.last_mut() {
+                Some((last, run)) if *last == index => run.push(c),
+                _ => runs.push((index, c.to_string())),
+            }
+        }
+        runs
+    }
+}
+
+/// ab_glyph 的缩放是行高，换算成该字体的 em 大小
+fn font_size(font: &FontArc, scale: PxScale) -> f32 {
+    let units = font.units_per_em().unwrap_or(1000.0);
+    scale.y * units / font.height_unscaled()
+}
+
+fn unit(c: u8) -> f32 {
+    f32::from(c) / 255.0
+}
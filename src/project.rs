@@ -0,0 +1,375 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use egui::{
+    Color32, Pos2, Rect, Stroke, Vec2,
+    epaint::{CircleShape, EllipseShape, PathShape, PathStroke},
+};
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    document::Document,
+    loader::LoadedImage,
+    operators::{Operator, TextAlign, TextStyle, ToolType},
+    settings::HexColor,
+};
+
+/// 工程文件扩展名
+pub const PROJECT_EXTENSION: &str = "annot";
+
+/// 工程文件格式版本，不兼容的修改时递增
+const PROJECT_VERSION: u32 = 1;
+
+/// 读取的工程：原图、标注和下一个编号
+pub struct Project {
+    pub image: LoadedImage,
+    pub operators: Vec<Operator>,
+    pub number: u8,
+}
+
+/// 工程文件：JSON，原图以 base64 PNG 嵌入，标注保存为可以继续编辑的形状
+#[derive(Serialize, Deserialize)]
+struct ProjectFile {
+    version: u32,
+    title: String,
+    /// 原图路径，只用于显示和导出文件名
+    path: Option<PathBuf>,
+    image: String,
+    number: u8,
+    operators: Vec<OperatorData>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OperatorData {
+    #[serde(flatten)]
+    shape: ShapeData,
+    stroke_width: f32,
+    color: HexColor,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fill: Option<HexColor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+}
+
+/// 标注形状，坐标为图片坐标；颜色和线宽由 `OperatorData` 保存
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ShapeData {
+    Rect { min: [f32; 2], max: [f32; 2] },
+    Ellipse { center: [f32; 2], radius: [f32; 2] },
+    Arrow { points: Vec<[f32; 2]> },
+    Line { start: [f32; 2], end: [f32; 2] },
+    Pencil { points: Vec<[f32; 2]> },
+    Number { center: [f32; 2], radius: f32, number: u8 },
+    Text { pos: [f32; 2], content: String, style: StyleData },
+    Callout { target: [f32; 2], pos: [f32; 2], content: String, style: StyleData },
+    Mosaic { min: [f32; 2], max: [f32; 2] },
+    Polygon { points: Vec<[f32; 2]> },
+}
+
+/// `TextStyle` 的文件形式，颜色写成十六进制
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct StyleData {
+    size: f32,
+    bold: bool,
+    italic: bool,
+    align: TextAlign,
+    wrap_width: Option<f32>,
+    background: Option<HexColor>,
+    padding: f32,
+    corner_radius: f32,
+    outline: Option<HexColor>,
+    outline_width: f32,
+    shadow: Option<HexColor>,
+}
+
+impl Default for StyleData {
+    fn default() -> Self {
+        Self::from(&TextStyle::default())
+    }
+}
+
+impl From<&TextStyle> for StyleData {
+    fn from(style: &TextStyle) -> Self {
+        Self {
+            size: style.size,
+            bold: style.bold,
+            italic: style.italic,
+            align: style.align,
+            wrap_width: style.wrap_width,
+            background: style.background.map(HexColor),
+            padding: style.padding,
+            corner_radius: style.corner_radius,
+            outline: style.outline.map(HexColor),
+            outline_width: style.outline_width,
+            shadow: style.shadow.map(HexColor),
+        }
+    }
+}
+
+impl From<StyleData> for TextStyle {
+    fn from(style: StyleData) -> Self {
+        Self {
+            size: style.size,
+            bold: style.bold,
+            italic: style.italic,
+            align: style.align,
+            wrap_width: style.wrap_width,
+            background: style.background.map(|c| c.0),
+            padding: style.padding,
+            corner_radius: style.corner_radius,
+            outline: style.outline.map(|c| c.0),
+            outline_width: style.outline_width,
+            shadow: style.shadow.map(|c| c.0),
+        }
+    }
+}
+
+/// 保存标签页为工程文件
+pub fn save(doc: &Document, path: &Path) -> Result<(), String> {
+    let mut png = Vec::new();
+    doc.original_image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    let file = ProjectFile {
+        version: PROJECT_VERSION,
+        title: doc.title.clone(),
+        path: doc.path.clone(),
+        image: base64::engine::general_purpose::STANDARD.encode(&png),
+        number: doc.number,
+        operators: doc.operators.iter().map(OperatorData::from).collect(),
+    };
+    let json = serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| e.to_string())
+}
+
+/// 读取工程文件
+pub fn load(path: &Path) -> Result<Project, String> {
+    let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let file: ProjectFile = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    if file.version > PROJECT_VERSION {
+        return Err(format!("project version {} is newer than supported {PROJECT_VERSION}", file.version));
+    }
+    let png = base64::engine::general_purpose::STANDARD
+        .decode(&file.image)
+        .map_err(|e| e.to_string())?;
+    let image = image::load_from_memory_with_format(&png, ImageFormat::Png)
+        .map_err(|e| e.to_string())?
+        .to_rgba8();
+    let size = Vec2::new(image.width() as f32, image.height() as f32);
+    Ok(Project {
+        image: LoadedImage {
+            image,
+            size,
+            name: file.title,
+            path: file.path,
+        },
+        operators: file.operators.into_iter().map(Operator::from).collect(),
+        number: file.number,
+    })
+}
+
+fn point(p: Pos2) -> [f32; 2] {
+    [p.x, p.y]
+}
+
+fn pos([x, y]: [f32; 2]) -> Pos2 {
+    Pos2::new(x, y)
+}
+
+impl From<&Operator> for OperatorData {
+    fn from(op: &Operator) -> Self {
+        let shape = match &op.tool {
+            ToolType::Rect(rect) => ShapeData::Rect {
+                min: point(rect.min),
+                max: point(rect.max),
+            },
+            ToolType::Ellipse(ellipse) => ShapeData::Ellipse {
+                center: point(ellipse.center),
+                radius: [ellipse.radius.x, ellipse.radius.y],
+            },
+            ToolType::Arrow(arrow) => ShapeData::Arrow {
+                points: arrow.points.iter().copied().map(point).collect(),
+            },
+            ToolType::Line(start, end) => ShapeData::Line {
+                start: point(*start),
+                end: point(*end),
+            },
+            ToolType::Pencil(points) => ShapeData::Pencil {
+                points: points.iter().copied().map(point).collect(),
+            },
+            ToolType::Number(c, n) => ShapeData::Number {
+                center: point(c.center),
+                radius: c.radius,
+                number: *n,
+            },
+            ToolType::Text { pos, content, style } => ShapeData::Text {
+                pos: point(*pos),
+                content: content.clone(),
+                style: style.into(),
+            },
+            ToolType::Callout { target, pos, content, style } => ShapeData::Callout {
+                target: point(*target),
+                pos: point(*pos),
+                content: content.clone(),
+                style: style.into(),
+            },
+            ToolType::Mosaic(rect) => ShapeData::Mosaic {
+                min: point(rect.min),
+                max: point(rect.max),
+            },
+            ToolType::Polygon(points) => ShapeData::Polygon {
+                points: points.iter().copied().map(point).collect(),
+            },
+        };
+        Self {
+            shape,
+            stroke_width: op.stroke_width.into(),
+            color: HexColor(op.color),
+            fill: op.fill_color.map(HexColor),
+            label: op.label.clone(),
+        }
+    }
+}
+
+/// 按绘制工具创建标注时的方式还原形状中重复保存的颜色和线宽
+impl From<OperatorData> for Operator {
+    fn from(data: OperatorData) -> Self {
+        let width = data.stroke_width;
+        let color = data.color.0;
+        let fill = data.fill.map(|c| c.0);
+        let tool = match data.shape {
+            ShapeData::Rect { min, max } => ToolType::Rect(Rect::from_min_max(pos(min), pos(max))),
+            ShapeData::Ellipse { center, radius } => ToolType::Ellipse(EllipseShape {
+                center: pos(center),
+                radius: Vec2::new(radius[0], radius[1]),
+                fill: fill.unwrap_or(Color32::TRANSPARENT),
+                stroke: Stroke::new(width, color),
+            }),
+            ShapeData::Arrow { points } => ToolType::Arrow(PathShape {
+                points: points.into_iter().map(pos).collect(),
+                closed: true,
+                fill: fill.unwrap_or(color),
+                stroke: PathStroke::new(width, color),
+            }),
+            ShapeData::Line { start, end } => ToolType::Line(pos(start), pos(end)),
+            ShapeData::Pencil { points } => ToolType::Pencil(points.into_iter().map(pos).collect()),
+            ShapeData::Number { center, radius, number } => ToolType::Number(
+                CircleShape {
+                    center: pos(center),
+                    radius,
+                    fill: fill.unwrap_or(color),
+                    stroke: Stroke::new(width, color),
+                },
+                number,
+            ),
+            ShapeData::Text { pos: p, content, style } => ToolType::Text {
+                pos: pos(p),
+                content,
+                style: style.into(),
+            },
+            ShapeData::Callout { target, pos: p, content, style } => ToolType::Callout {
+                target: pos(target),
+                pos: pos(p),
+                content,
+                style: style.into(),
+            },
+            ShapeData::Mosaic { min, max } => ToolType::Mosaic(Rect::from_min_max(pos(min), pos(max))),
+            ShapeData::Polygon { points } => ToolType::Polygon(points.into_iter().map(pos).collect()),
+        };
+        let mut op = Operator::new(tool, width.into(), color, fill);
+        op.label = data.label;
+        op
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::Context;
+    use image::{Rgba, RgbaImage};
+
+    use super::*;
+    use crate::toolbar::StrokeWidth;
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let mut image = RgbaImage::from_pixel(4, 3, Rgba([10, 20, 30, 255]));
+        image.put_pixel(1, 1, Rgba([200, 100, 0, 128]));
+        let mut doc = Document::new(
+            &Context::default(),
+            LoadedImage {
+                size: Vec2::new(4.0, 3.0),
+                image,
+                name: "shot.png".to_owned(),
+                path: Some(PathBuf::from("pictures/shot.png")),
+            },
+        );
+        let red = Color32::from_rgba_unmultiplied(255, 0, 0, 200);
+        let mut polygon = Operator::new(
+            ToolType::Polygon(vec![Pos2::new(0.0, 0.0), Pos2::new(3.0, 0.5), Pos2::new(1.0, 2.0)]),
+            StrokeWidth::Custom(2.5),
+            red,
+            Some(Color32::BLUE),
+        );
+        polygon.label = Some("cat".to_owned());
+        let style = TextStyle {
+            bold: true,
+            background: Some(Color32::WHITE),
+            ..TextStyle::default()
+        };
+        doc.operators = vec![
+            Operator::new(
+                ToolType::Rect(Rect::from_min_max(Pos2::new(0.5, 0.5), Pos2::new(2.0, 2.5))),
+                StrokeWidth::THREE,
+                red,
+                None,
+            ),
+            Operator::new(
+                ToolType::Ellipse(EllipseShape {
+                    center: Pos2::new(2.0, 1.5),
+                    radius: Vec2::new(1.0, 0.5),
+                    fill: Color32::TRANSPARENT,
+                    stroke: Stroke::new(1.0, red),
+                }),
+                StrokeWidth::ONE,
+                red,
+                None,
+            ),
+            Operator::new(
+                ToolType::Callout {
+                    target: Pos2::new(1.0, 1.0),
+                    pos: Pos2::new(3.0, 2.0),
+                    content: "note\nline".to_owned(),
+                    style,
+                },
+                StrokeWidth::FIVE,
+                red,
+                None,
+            ),
+            Operator::new(
+                ToolType::Mosaic(Rect::from_min_max(Pos2::ZERO, Pos2::new(2.0, 2.0))),
+                StrokeWidth::THREE,
+                red,
+                None,
+            ),
+            polygon,
+        ];
+        doc.number = 7;
+
+        let path = std::env::temp_dir().join(format!("annotator-project-{}.{PROJECT_EXTENSION}", std::process::id()));
+        save(&doc, &path).unwrap();
+        let project = load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let project = project.unwrap();
+
+        assert_eq!(project.image.image, doc.original_image);
+        assert_eq!(project.image.size, doc.image_size);
+        assert_eq!(project.image.name, doc.title);
+        assert_eq!(project.image.path, doc.path);
+        assert_eq!(project.number, doc.number);
+        assert_eq!(project.operators, doc.operators);
+    }
+}
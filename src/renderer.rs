@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use eframe::{egui_wgpu, wgpu};
+
+/// 渲染后端
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Renderer {
+    /// wgpu 硬件加速
+    Wgpu,
+    /// OpenGL (glow)
+    Glow,
+    /// 纯软件渲染，不需要 GPU
+    Software,
+}
+
+impl Renderer {
+    /// 解析 `--renderer` 参数或 `ANNOTATOR_RENDERER` 环境变量的值
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "wgpu" => Some(Renderer::Wgpu),
+            "glow" | "gl" | "opengl" => Some(Renderer::Glow),
+            "software" | "cpu" => Some(Renderer::Software),
+            _ => None,
+        }
+    }
+
+    /// 自动选择：有 wgpu 硬件适配器时使用 wgpu，否则退回软件渲染
+    pub fn detect(adapters: Adapters) -> Self {
+        if adapters.hardware { Renderer::Wgpu } else { Renderer::Software }
+    }
+
+    /// 启动失败时改用的后端：wgpu（包括 CPU 适配器）不可用时退回 OpenGL
+    pub fn fallback(self) -> Option<Self> {
+        match self {
+            Renderer::Wgpu | Renderer::Software => Some(Renderer::Glow),
+            Renderer::Glow => None,
+        }
+    }
+
+    /// 生成对应后端的 `NativeOptions`
+    pub fn native_options(self, adapters: Adapters) -> eframe::NativeOptions {
+        match self {
+            Renderer::Wgpu => eframe::NativeOptions {
+                renderer: eframe::Renderer::Wgpu,
+                ..Default::default()
+            },
+            Renderer::Glow => eframe::NativeOptions {
+                renderer: eframe::Renderer::Glow,
+                ..Default::default()
+            },
+            Renderer::Software => {
+                if adapters.cpu {
+                    // wgpu 的 CPU 适配器（如 lavapipe、WARP）
+                    let setup = egui_wgpu::WgpuSetupCreateNew {
+                        instance_descriptor: instance_descriptor(),
+                        native_adapter_selector: Some(Arc::new(|adapters, _surface| {
+                            adapters
+                                .iter()
+                                .find(|a| a.get_info().device_type == wgpu::DeviceType::Cpu)
+                                .cloned()
+                                .ok_or_else(|| "No software wgpu adapter found".to_owned())
+                        })),
+                        ..Default::default()
+                    };
+                    eframe::NativeOptions {
+                        renderer: eframe::Renderer::Wgpu,
+                        wgpu_options: egui_wgpu::WgpuConfiguration {
+                            wgpu_setup: egui_wgpu::WgpuSetup::CreateNew(setup),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }
+                } else {
+                    // 没有 CPU 适配器时使用 Mesa 的 llvmpipe 软件 OpenGL，
+                    // LIBGL_ALWAYS_SOFTWARE 由 main 在确定使用软件渲染后设置
+                    eframe::NativeOptions {
+                        renderer: eframe::Renderer::Glow,
+                        ..Default::default()
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn instance_descriptor() -> wgpu::InstanceDescriptor {
+    egui_wgpu::WgpuSetupCreateNew::default().instance_descriptor
+}
+
+/// 启动时枚举到的 wgpu 适配器种类
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Adapters {
+    /// 有 GPU 适配器
+    pub hardware: bool,
+    /// 有 CPU 适配器（如 lavapipe、WARP）
+    pub cpu: bool,
+}
+
+impl Adapters {
+    /// 枚举所有后端的适配器，启动时只调用一次，自动检测和软件渲染共用结果
+    pub fn enumerate() -> Self {
+        let instance = wgpu::Instance::new(&instance_descriptor());
+        let mut adapters = Adapters::default();
+        for adapter in instance.enumerate_adapters(wgpu::Backends::all()) {
+            if adapter.get_info().device_type == wgpu::DeviceType::Cpu {
+                adapters.cpu = true;
+            } else {
+                adapters.hardware = true;
+            }
+        }
+        adapters
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use egui::Color32;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{color_picker, config, export::ExportOptions, font, palette::Palette, toolbar::{Tool, ToolColors}};
+
+/// 设置文件名
+const SETTINGS_FILE: &str = "settings.toml";
+
+/// 跨会话保存的用户设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// 上次使用的工具
+    pub tool: Tool,
+    /// 上次使用的颜色
+    pub color: HexColor,
+    /// 上次使用的线宽
+    pub stroke_width: f32,
+    /// 每个工具上次使用的描边色和填充色
+    pub tool_colors: HashMap<Tool, ToolColors>,
+    /// 自定义调色板
+    pub palette: Vec<HexColor>,
+    /// 最近使用的颜色
+    pub recent_colors: Vec<HexColor>,
+    /// 命名调色板
+    pub palettes: Vec<Palette>,
+    /// 取色器的取样半径
+    pub eyedropper_radius: u32,
+    /// 标注使用的主字体
+    pub font: Option<String>,
+    /// 主字体缺少字形时按顺序使用的后备字体
+    pub font_fallbacks: Vec<String>,
+    /// 保存时使用的目录，记录上次另存为的目录；未设置时保存在原图所在目录
+    pub output_dir: Option<PathBuf>,
+    /// 导出格式和选项
+    pub export: ExportOptions,
+    /// 数据集类别文件
+    pub label_file: Option<PathBuf>,
+    /// 是否显示标签面板
+    pub show_labels: bool,
+    /// 窗口位置和大小
+    pub window: Option<WindowGeometry>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            tool: Tool::default(),
+            color: HexColor(Color32::RED),
+            stroke_width: 3.0,
+            tool_colors: HashMap::new(),
+            palette: Vec::new(),
+            recent_colors: Vec::new(),
+            palettes: Vec::new(),
+            eyedropper_radius: 1,
+            font: None,
+            font_fallbacks: font::DEFAULT_FONT_NAMES.iter().map(|s| s.to_string()).collect(),
+            output_dir: None,
+            export: ExportOptions::default(),
+            label_file: None,
+            show_labels: false,
+            window: None,
+        }
+    }
+}
+
+/// 窗口位置和大小（逻辑像素）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// 以 `#RRGGBBAA` 形式保存的颜色
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HexColor(pub Color32);
+
+impl Serialize for HexColor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&color_picker::color_to_hex(self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for HexColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        color_picker::hex_to_color(&text)
+            .map(HexColor)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid color `{text}`")))
+    }
+}
+
+impl Settings {
+    /// 读取设置，文件不存在时使用默认值
+    ///
+    /// 无法解析的项使用默认值，原文件另存为 `.bak`，避免退出时被默认值覆盖
+    pub fn load() -> Self {
+        let Some(path) = config::config_file(SETTINGS_FILE) else {
+            return Self::default();
+        };
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        let (settings, errors) = Self::parse(&content);
+        if !errors.is_empty() {
+            for err in &errors {
+                eprintln!("Ignoring invalid setting in {}: {err}", path.display());
+            }
+            let backup = path.with_extension("toml.bak");
+            match std::fs::copy(&path, &backup) {
+                Ok(_) => eprintln!("Saved the original settings file as {}", backup.display()),
+                Err(err) => eprintln!("Failed to back up settings to {}: {err}", backup.display()),
+            }
+        }
+        settings
+    }
+
+    /// 逐项解析设置，返回设置和被忽略的项的错误
+    fn parse(content: &str) -> (Self, Vec<String>) {
+        let table = match content.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(err) => return (Self::default(), vec![err.to_string()]),
+        };
+        // 一项出错时 toml::from_str 会拒绝整个文件，所以逐项加入，出错的项不加入
+        let mut accepted = toml::Table::new();
+        let mut errors = Vec::new();
+        for (key, value) in table {
+            accepted.insert(key.clone(), value);
+            if let Err(err) = accepted.clone().try_into::<Self>() {
+                accepted.remove(&key);
+                errors.push(format!("`{key}`: {}", err.message()));
+            }
+        }
+        let mut settings: Self = accepted.try_into().unwrap_or_default();
+        errors.extend(settings.validate());
+        (settings, errors)
+    }
+
+    /// 把能解析但不能使用的值改回默认值，返回被改回的项
+    fn validate(&mut self) -> Vec<String> {
+        let default = Self::default();
+        let mut errors = Vec::new();
+        if !self.tool.is_restorable() {
+            errors.push(format!("`tool`: {:?} cannot be restored", self.tool));
+            self.tool = default.tool;
+        }
+        if !(self.stroke_width.is_finite() && self.stroke_width > 0.0) {
+            errors.push(format!("`stroke_width`: {} is not a positive width", self.stroke_width));
+            self.stroke_width = default.stroke_width;
+        }
+        errors
+    }
+
+    /// 写入设置文件
+    pub fn save(&self) {
+        let Some(path) = config::config_file(SETTINGS_FILE) else {
+            return;
+        };
+        let result = toml::to_string_pretty(self)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                if let Some(dir) = path.parent() {
+                    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+                }
+                std::fs::write(&path, content).map_err(|e| e.to_string())
+            });
+        if let Err(err) = result {
+            eprintln!("Failed to save settings to {}: {err}", path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_field_keeps_the_rest() {
+        let (settings, errors) = Settings::parse(
+            r##"
+            color = "not a color"
+            stroke_width = 5.0
+            tool = "rectangle"
+            eyedropper_radius = 4
+            "##,
+        );
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert!(errors[0].contains("color"));
+        assert_eq!(settings.color, Settings::default().color);
+        assert_eq!(settings.stroke_width, 5.0);
+        assert_eq!(settings.tool, Tool::Rectangle);
+        assert_eq!(settings.eyedropper_radius, 4);
+    }
+
+    #[test]
+    fn unusable_values_fall_back_to_defaults() {
+        let (settings, errors) = Settings::parse("tool = \"emoji\"\nstroke_width = nan\n");
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert_eq!(settings.tool, Tool::default());
+        assert_eq!(settings.stroke_width, Settings::default().stroke_width);
+
+        let (settings, errors) = Settings::parse("stroke_width = -2.0\n");
+        assert_eq!(errors.len(), 1, "{errors:?}");
+        assert_eq!(settings.stroke_width, Settings::default().stroke_width);
+    }
+
+    #[test]
+    fn broken_file_uses_defaults() {
+        let (settings, errors) = Settings::parse("tool = \"rectangle");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(settings.tool, Tool::default());
+    }
+}
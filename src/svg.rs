@@ -0,0 +1,292 @@
+use std::fmt::Write;
+use std::io::Cursor;
+
+use base64::Engine;
+use egui::{Color32, Pos2, Rect, Vec2};
+use image::ImageFormat;
+
+use crate::{
+    document::Document,
+    drawable::{Layer, TextLayout, layout_text},
+    font::FontSet,
+    operators::{
+        CALLOUT_FILL, MASK_COLOR, Operator, TextAlign, TextStyle, ToolType, callout_tail,
+        number_text_color,
+    },
+};
+
+/// 导出为 SVG：原图以 base64 PNG 嵌入，标注输出为可编辑的矢量元素
+///
+/// 导出倍率只改变显示尺寸，`viewBox` 保持图片坐标；`Layer::Overlay` 时不嵌入原图
+pub fn render(doc: &Document, fonts: &FontSet, scale: f32, layer: Layer) -> Result<String, String> {
+    let (width, height) = doc.original_image.dimensions();
+
+    let mut svg = String::new();
+    let _ = writeln!(svg, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{}" height="{}" viewBox="0 0 {width} {height}">"#,
+        width as f32 * scale,
+        height as f32 * scale,
+    );
+    if layer == Layer::Composite {
+        // 马赛克直接打码在嵌入的位图上，文件里不保留被遮住的像素
+        let mut png = Vec::new();
+        doc.redacted_image()
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .map_err(|e| e.to_string())?;
+        let data = base64::engine::general_purpose::STANDARD.encode(&png);
+        let _ = writeln!(
+            svg,
+            r#"<image x="0" y="0" width="{width}" height="{height}" xlink:href="data:image/png;base64,{data}"/>"#
+        );
+    }
+    let family = font_family(fonts);
+    for op in &doc.operators {
+        write_operator(&mut svg, op, fonts, &family, layer);
+    }
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+/// 只导出标注层时马赛克写成遮罩，合成时马赛克已在嵌入的原图上
+fn write_operator(svg: &mut String, op: &Operator, fonts: &FontSet, family: &str, layer: Layer) {
+    let width = f32::from(op.stroke_width);
+    let stroke = paint("stroke", Some(op.color));
+    match &op.tool {
+        ToolType::Rect(rect) => {
+            let _ = writeln!(
+                svg,
+                r#"<rect x="{}" y="{}" width="{}" height="{}" {} {stroke} stroke-width="{width}"/>"#,
+                rect.left(),
+                rect.top(),
+                rect.width(),
+                rect.height(),
+                paint("fill", op.fill_color),
+            );
+        }
+        ToolType::Ellipse(ellipse) => {
+            let _ = writeln!(
+                svg,
+                r#"<ellipse cx="{}" cy="{}" rx="{}" ry="{}" {} {stroke} stroke-width="{width}"/>"#,
+                ellipse.center.x,
+                ellipse.center.y,
+                ellipse.radius.x,
+                ellipse.radius.y,
+                paint("fill", op.fill_color),
+            );
+        }
+        ToolType::Arrow(arrow) => {
+            let _ = writeln!(
+                svg,
+                r#"<path d="{}" {} {stroke} stroke-width="{width}"/>"#,
+                path_data(&arrow.points, true),
+                paint("fill", op.fill_color),
+            );
+        }
+        ToolType::Polygon(points) => {
+            let _ = writeln!(
+                svg,
+                r#"<path d="{}" {} {stroke} stroke-width="{width}"/>"#,
+                path_data(points, true),
+                paint("fill", op.fill_color),
+            );
+        }
+        ToolType::Line(start, end) => {
+            let _ = writeln!(
+                svg,
+                r#"<path d="{}" fill="none" {stroke} stroke-width="{width}"/>"#,
+                path_data(&[*start, *end], false),
+            );
+        }
+        ToolType::Pencil(points) => {
+            let _ = writeln!(
+                svg,
+                r#"<path d="{}" fill="none" {stroke} stroke-width="{width}" stroke-linejoin="round" stroke-linecap="round"/>"#,
+                path_data(points, false),
+            );
+        }
+        ToolType::Number(c, n) => {
+            let _ = writeln!(
+                svg,
+                r#"<circle cx="{}" cy="{}" r="{}" {} {stroke} stroke-width="{width}"/>"#,
+                c.center.x,
+                c.center.y,
+                c.radius,
+                paint("fill", Some(c.fill)),
+            );
+            let style = TextStyle {
+                size: c.radius,
+                align: TextAlign::Center,
+                ..Default::default()
+            };
+            if let Some(layout) = layout_text(c.center, &(n + 1).to_string(), &style, fonts, true) {
+                write_text(svg, &layout, &style, family, number_text_color(c.fill, op.color));
+            }
+        }
+        ToolType::Text { pos, content, style } => {
+            let Some(layout) = layout_text(*pos, content, style, fonts, false) else {
+                return;
+            };
+            if let Some(background) = style.background {
+                let box_rect = layout.rect.expand(style.padding);
+                if let Some(shadow) = style.shadow {
+                    write_rounded_rect(svg, box_rect.translate(style.shadow_offset()), style.corner_radius, &paint("fill", Some(shadow)));
+                }
+                write_rounded_rect(svg, box_rect, style.corner_radius, &paint("fill", Some(background)));
+            }
+            write_text(svg, &layout, style, family, op.color);
+        }
+        ToolType::Callout { target, pos, content, style } => {
+            let Some(layout) = layout_text(*pos, content, style, fonts, false) else {
+                return;
+            };
+            let box_rect = layout.rect.expand(style.padding);
+            let tail = callout_tail(box_rect, *target).map(|points| path_data(&points, true));
+            let fill = paint("fill", Some(style.background.unwrap_or(CALLOUT_FILL)));
+
+            let _ = writeln!(svg, "<g>");
+            if let Some(shadow) = style.shadow {
+                let offset = style.shadow_offset();
+                let shadow = paint("fill", Some(shadow));
+                let _ = writeln!(svg, r#"<g transform="translate({} {})">"#, offset.x, offset.y);
+                if let Some(tail) = &tail {
+                    let _ = writeln!(svg, r#"<path d="{tail}" {shadow}/>"#);
+                }
+                write_rounded_rect(svg, box_rect, style.corner_radius, &shadow);
+                let _ = writeln!(svg, "</g>");
+            }
+            // 与位图导出相同：两倍宽的边框被填充盖住内侧一半，只留下外轮廓
+            let outline = format!(r#"fill="none" {stroke} stroke-width="{}""#, width * 2.0);
+            for attrs in [outline.as_str(), fill.as_str()] {
+                if let Some(tail) = &tail {
+                    let _ = writeln!(svg, r#"<path d="{tail}" {attrs}/>"#);
+                }
+                write_rounded_rect(svg, box_rect, style.corner_radius, attrs);
+            }
+            let style = TextStyle {
+                shadow: None,
+                ..style.clone()
+            };
+            write_text(svg, &layout, &style, family, op.color);
+            let _ = writeln!(svg, "</g>");
+        }
+        ToolType::Mosaic(rect) => {
+            if layer == Layer::Overlay {
+                write_rounded_rect(svg, *rect, 0.0, &paint("fill", Some(MASK_COLOR)));
+            }
+        }
+    }
+}
+
+/// 输出排好版的文字，每行一个 `<text>`，描边文字用 `paint-order` 把描边放在字形下面
+fn write_text(svg: &mut String, layout: &TextLayout, style: &TextStyle, family: &str, color: Color32) {
+    let mut attrs = format!(
+        r#"font-family="{}" font-size="{}" xml:space="preserve""#,
+        escape(family),
+        style.size
+    );
+    if style.bold {
+        attrs.push_str(r#" font-weight="bold""#);
+    }
+    if style.italic {
+        attrs.push_str(r#" font-style="italic""#);
+    }
+
+    let _ = writeln!(svg, "<g {attrs}>");
+    if let Some(shadow) = style.shadow
+        && style.background.is_none()
+    {
+        write_lines(svg, layout, style.shadow_offset(), &paint("fill", Some(shadow)));
+    }
+    let mut fill = paint("fill", Some(color));
+    if let Some(outline) = style.outline {
+        let _ = write!(
+            fill,
+            r#" {} stroke-width="{}" stroke-linejoin="round" paint-order="stroke""#,
+            paint("stroke", Some(outline)),
+            style.outline_width * 2.0
+        );
+    }
+    write_lines(svg, layout, Vec2::ZERO, &fill);
+    let _ = writeln!(svg, "</g>");
+}
+
+fn write_lines(svg: &mut String, layout: &TextLayout, offset: Vec2, attrs: &str) {
+    for (line, start) in &layout.lines {
+        let start = *start + offset;
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}" {attrs}>{}</text>"#,
+            start.x,
+            start.y,
+            escape(line.trim_end())
+        );
+    }
+}
+
+fn write_rounded_rect(svg: &mut String, rect: Rect, radius: f32, attrs: &str) {
+    let r = radius.min(rect.width() / 2.0).min(rect.height() / 2.0).max(0.0);
+    let _ = writeln!(
+        svg,
+        r#"<rect x="{}" y="{}" width="{}" height="{}" rx="{r}" {attrs}/>"#,
+        rect.left(),
+        rect.top(),
+        rect.width(),
+        rect.height(),
+    );
+}
+
+/// 折线的路径数据
+fn path_data(points: &[Pos2], close: bool) -> String {
+    let mut d = String::new();
+    for (i, p) in points.iter().enumerate() {
+        let _ = write!(d, "{}{} {} ", if i == 0 { "M" } else { "L" }, p.x, p.y);
+    }
+    if close {
+        d.push('Z');
+    }
+    d.trim_end().to_owned()
+}
+
+/// 颜色属性，透明度单独写成 `*-opacity`
+fn paint(name: &str, color: Option<Color32>) -> String {
+    let Some(color) = color else {
+        return format!(r#"{name}="none""#);
+    };
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    let mut attr = format!(r##"{name}="#{r:02x}{g:02x}{b:02x}""##);
+    if a < 255 {
+        let _ = write!(attr, r#" {name}-opacity="{:.3}""#, f32::from(a) / 255.0);
+    }
+    attr
+}
+
+/// 字体链对应的 CSS 字体族
+fn font_family(fonts: &FontSet) -> String {
+    let mut names: Vec<String> = fonts
+        .fonts()
+        .iter()
+        // 字体名放在 CSS 单引号字符串里，先做 CSS 转义，写入属性时再做 XML 转义
+        .map(|f| {
+            let name = f.name.trim_end_matches(" (bundled)").replace('\\', "\\\\").replace('\'', "\\'");
+            format!("'{name}'")
+        })
+        .collect();
+    names.push("sans-serif".to_owned());
+    names.join(", ")
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
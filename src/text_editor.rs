@@ -0,0 +1,168 @@
+use egui::{Color32, CursorIcon, Key, KeyboardShortcut, Modifiers, Pos2, Rect, Sense, Stroke, StrokeKind, Ui, Vec2};
+
+use crate::{
+    operators::{Operator, TextAlign, TextStyle, ToolType},
+    toolbar::{StrokeWidth, Tool},
+    utils::AppHelper,
+};
+
+/// 拖动调整换行宽度的手柄宽度（屏幕像素）
+const HANDLE_WIDTH: f32 = 6.0;
+/// 最小换行宽度（图片像素）
+const MIN_WRAP_WIDTH: f32 = 20.0;
+
+/// 正在输入的文字
+#[derive(Debug, Default, Clone)]
+pub struct TextEditState {
+    /// 锚点（图片坐标）
+    pub pos: Pos2,
+    pub content: String,
+    pub style: TextStyle,
+    /// 气泡尾巴指向的位置，None 表示普通文字
+    pub target: Option<Pos2>,
+    /// 正在修改的已有文字标注的下标，None 表示新建
+    pub replaces: Option<usize>,
+    /// 修改已有标注时沿用的属性
+    pub retained: Option<Retained>,
+}
+
+/// 重新编辑时从原标注保留下来、输入框不会修改的属性
+#[derive(Debug, Clone)]
+pub struct Retained {
+    pub stroke_width: StrokeWidth,
+    pub fill_color: Option<Color32>,
+    pub label: Option<String>,
+}
+
+/// 文字输入框本帧的结果
+pub enum TextEditOutcome {
+    Editing,
+    /// Enter：提交文字
+    Commit,
+    /// Escape：放弃输入
+    Cancel,
+}
+
+impl TextEditState {
+    /// 重新编辑已有的文字或气泡标注，其他标注返回 None
+    pub fn from_operator(op: &Operator, index: usize) -> Option<Self> {
+        let (pos, content, style, target) = match &op.tool {
+            ToolType::Text { pos, content, style } => (pos, content, style, None),
+            ToolType::Callout { target, pos, content, style } => (pos, content, style, Some(*target)),
+            _ => return None,
+        };
+        Some(Self {
+            pos: *pos,
+            content: content.clone(),
+            style: style.clone(),
+            target,
+            replaces: Some(index),
+            retained: Some(Retained {
+                stroke_width: op.stroke_width,
+                fill_color: op.fill_color,
+                label: op.label.clone(),
+            }),
+        })
+    }
+
+    /// 输入完成后的标注类型
+    pub fn into_tool_type(self) -> ToolType {
+        let Self { pos, content, style, target, .. } = self;
+        match target {
+            Some(target) => ToolType::Callout { target, pos, content, style },
+            None => ToolType::Text { pos, content, style },
+        }
+    }
+
+    /// 对应的工具
+    pub fn tool(&self) -> Tool {
+        if self.target.is_some() { Tool::Callout } else { Tool::Text }
+    }
+
+    /// 在锚点处显示多行输入框，Shift+Enter 换行，Enter 提交
+    ///
+    /// 输入框边缘的手柄可以拖动设置换行宽度，双击手柄取消固定宽度
+    pub fn show(&mut self, ui: &mut Ui, helper: &AppHelper, color: Color32) -> TextEditOutcome {
+        let zoom = helper.zoom;
+        let font_size = self.style.size * zoom;
+        let screen_pos = helper.image_to_screen(self.pos);
+
+        // 按当前内容排版，确定输入框大小
+        let style = self.style.clone();
+        let galley = ui.fonts_mut(|f| f.layout_job(style.layout_job(&self.content, zoom, color)));
+        let edit_width = match style.wrap_width {
+            Some(width) => width * zoom,
+            None => galley.size().x.max(font_size * 4.0) + font_size,
+        };
+        let edit_size = Vec2::new(edit_width, galley.size().y.max(font_size * 1.2));
+        // TextEdit 放在锚点处，承载输入法；锚点随对齐方式变化
+        let anchor = match style.align {
+            TextAlign::Left => 0.0,
+            TextAlign::Center => edit_width / 2.0,
+            TextAlign::Right => edit_width,
+        };
+        let edit_rect = Rect::from_min_size(screen_pos - Vec2::new(anchor, 0.0), edit_size);
+
+        let mut layouter = |ui: &Ui, text: &dyn egui::TextBuffer, _wrap_width: f32| {
+            // 对齐交给 TextEdit 处理，排版本身保持左对齐
+            let mut job = style.layout_job(text.as_str(), zoom, color);
+            job.halign = egui::Align::LEFT;
+            ui.fonts_mut(|f| f.layout_job(job))
+        };
+        let text_edit = egui::TextEdit::multiline(&mut self.content)
+            .frame(false)
+            .desired_width(edit_width)
+            .desired_rows(1)
+            .horizontal_align(style.align.into())
+            .return_key(KeyboardShortcut::new(Modifiers::SHIFT, Key::Enter))
+            .layouter(&mut layouter);
+
+        let output = ui.put(edit_rect, text_edit);
+        // 获得焦点
+        output.request_focus();
+
+        self.wrap_handle(ui, edit_rect, output.id, zoom);
+        if let Some(target) = self.target {
+            let target = helper.image_to_screen(target);
+            if !edit_rect.contains(target) {
+                ui.painter().line_segment([edit_rect.center(), target], Stroke::new(1.0, color));
+                ui.painter().circle_filled(target, 3.0, color);
+            }
+        }
+
+        if ui.input(|i| i.key_pressed(Key::Escape)) {
+            TextEditOutcome::Cancel
+        } else if ui.input(|i| i.key_pressed(Key::Enter) && !i.modifiers.shift) {
+            TextEditOutcome::Commit
+        } else {
+            TextEditOutcome::Editing
+        }
+    }
+
+    /// 输入框远离锚点一侧的边缘手柄，拖动设置换行宽度
+    fn wrap_handle(&mut self, ui: &mut Ui, edit_rect: Rect, id: egui::Id, zoom: f32) {
+        let (edge, direction) = match self.style.align {
+            TextAlign::Left => (edit_rect.right(), 1.0),
+            TextAlign::Center => (edit_rect.right(), 2.0),
+            TextAlign::Right => (edit_rect.left(), -1.0),
+        };
+        let handle_rect = Rect::from_center_size(
+            Pos2::new(edge, edit_rect.center().y),
+            Vec2::new(HANDLE_WIDTH, edit_rect.height()),
+        );
+        let response = ui
+            .interact(handle_rect, id.with("wrap_handle"), Sense::click_and_drag())
+            .on_hover_cursor(CursorIcon::ResizeHorizontal)
+            .on_hover_text("Drag to set wrap width, double-click to reset");
+
+        ui.painter().rect_stroke(edit_rect, 2.0, Stroke::new(1.0, Color32::GRAY), StrokeKind::Outside);
+        ui.painter().rect_filled(handle_rect.shrink2(Vec2::new(1.0, 4.0)), 2.0, Color32::GRAY);
+
+        if response.double_clicked() {
+            self.style.wrap_width = None;
+        } else if response.dragged() {
+            let width = edit_rect.width() + response.drag_delta().x * direction;
+            self.style.wrap_width = Some((width / zoom).max(MIN_WRAP_WIDTH));
+        }
+    }
+}
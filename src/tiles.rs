@@ -0,0 +1,74 @@
+use egui::{Color32, ColorImage, Context, Painter, Pos2, Rect, TextureHandle};
+use image::RgbaImage;
+
+use crate::utils::AppHelper;
+
+/// 单个纹理块的最大边长，低于所有后端的纹理尺寸下限
+const TILE_SIZE: u32 = 2048;
+
+/// 纹理块四周多上传的相邻像素，线性过滤在块边缘采样到真实的邻居，缩放时不出现接缝
+const TILE_BORDER: u32 = 1;
+
+/// 图片切分后的一个纹理块
+pub struct ImageTile {
+    /// 在图片坐标系中的区域
+    rect: Rect,
+    /// `rect` 在纹理中的 UV 区域，不含四周的重叠像素
+    uv: Rect,
+    texture: TextureHandle,
+}
+
+/// 将图片切分为多个纹理块并上传，避免超过 GPU 的最大纹理尺寸
+pub fn upload_tiles(ctx: &Context, img: &RgbaImage) -> Vec<ImageTile> {
+    let tile_size = TILE_SIZE.min(ctx.input(|i| i.max_texture_side) as u32) - 2 * TILE_BORDER;
+    let (width, height) = img.dimensions();
+
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(tile_size as usize) {
+        for x in (0..width).step_by(tile_size as usize) {
+            let w = tile_size.min(width - x);
+            let h = tile_size.min(height - y);
+            // 连同相邻像素一起上传，图片边缘没有邻居时由纹理的边缘钳制处理
+            let x0 = x.saturating_sub(TILE_BORDER);
+            let y0 = y.saturating_sub(TILE_BORDER);
+            let x1 = (x + w + TILE_BORDER).min(width);
+            let y1 = (y + h + TILE_BORDER).min(height);
+            let (tex_w, tex_h) = ((x1 - x0) as f32, (y1 - y0) as f32);
+            let uv = Rect::from_min_max(
+                egui::pos2((x - x0) as f32 / tex_w, (y - y0) as f32 / tex_h),
+                egui::pos2((x + w - x0) as f32 / tex_w, (y + h - y0) as f32 / tex_h),
+            );
+            let sub = image::imageops::crop_imm(img, x0, y0, x1 - x0, y1 - y0).to_image();
+            let color_image = ColorImage::from_rgba_unmultiplied(
+                [(x1 - x0) as usize, (y1 - y0) as usize],
+                sub.as_raw(),
+            );
+            let texture = ctx.load_texture(
+                format!("image_tile_{x}_{y}"),
+                color_image,
+                Default::default(),
+            );
+            let rect = Rect::from_min_size(
+                Pos2::new(x as f32, y as f32),
+                egui::vec2(w as f32, h as f32),
+            );
+            tiles.push(ImageTile { rect, uv, texture });
+        }
+    }
+    tiles
+}
+
+/// 只绘制在可见区域内的纹理块
+pub fn paint_tiles(tiles: &[ImageTile], helper: &AppHelper, painter: &Painter) {
+    let clip_rect = painter.clip_rect();
+    for tile in tiles {
+        let screen_rect = Rect::from_min_max(
+            helper.image_to_screen(tile.rect.min),
+            helper.image_to_screen(tile.rect.max),
+        );
+        if !clip_rect.intersects(screen_rect) {
+            continue;
+        }
+        painter.image(tile.texture.id(), screen_rect, tile.uv, Color32::WHITE);
+    }
+}
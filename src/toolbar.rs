@@ -1,13 +1,23 @@
+use std::collections::HashMap;
 use std::ops::Mul;
 
 use egui::{
-    Button, Color32, Context, Frame, Image, Margin, Painter, PointerButton, Pos2, Rect, Response,
-    Stroke, TopBottomPanel, Ui, Vec2,
+    Button, Color32, Context, Frame, Image, Key, Margin, Painter, PointerButton, Pos2, Rect, Response,
+    Stroke, StrokeKind, TopBottomPanel, Ui, Vec2,
     epaint::{CircleShape, EllipseShape, PathShape, PathStroke},
 };
 
+use serde::{Deserialize, Serialize};
+
 use crate::{
-    operators::{Operator, ToolType},
+    dataset::{DEFAULT_LABEL, DatasetFormat},
+    labels::label_color,
+    export::{ExportFormat, SCALE_PRESETS, SCALE_RANGE},
+    eyedropper,
+    keymap::Action,
+    operators::{Operator, TextAlign, TextStyle, ToolType},
+    settings::HexColor,
+    text_editor::TextEditState,
     utils::AppHelper,
 };
 
@@ -20,17 +30,22 @@ const PENCIL_ICON: &[u8] = include_bytes!("../assets/pencil.svg");
 const NUMBER_ICON: &[u8] = include_bytes!("../assets/number.svg");
 const EMOJI_ICON: &[u8] = include_bytes!("../assets/emoji.svg");
 const TEXT_ICON: &[u8] = include_bytes!("../assets/text.svg");
+const CALLOUT_ICON: &[u8] = include_bytes!("../assets/callout.svg");
 const MOSAIC_ICON: &[u8] = include_bytes!("../assets/mosaic.svg");
+const POLYGON_ICON: &[u8] = include_bytes!("../assets/polygon.svg");
 const PIN_ICON: &[u8] = include_bytes!("../assets/pin.svg");
 const COPY_ICON: &[u8] = include_bytes!("../assets/copy.svg");
 const SAVE_ICON: &[u8] = include_bytes!("../assets/save.svg");
+const OPEN_ICON: &[u8] = include_bytes!("../assets/open.svg");
+const EYEDROPPER_ICON: &[u8] = include_bytes!("../assets/eyedropper.svg");
 
 const DOT_1_ICON: &[u8] = include_bytes!("../assets/dot1.svg");
 const DOT_3_ICON: &[u8] = include_bytes!("../assets/dot3.svg");
 const DOT_5_ICON: &[u8] = include_bytes!("../assets/dot5.svg");
 
 /// 工具栏
-#[derive(PartialEq, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Tool {
     /// 选择
     #[default]
@@ -51,7 +66,12 @@ pub enum Tool {
     Emoji,
     /// 文本
     Text,
+    /// 标注气泡
+    Callout,
+    /// 多边形
+    Polygon,
     /// 马赛克
+    #[serde(rename = "mosaic")]
     Masaic,
     /// Pin
     Pin,
@@ -59,9 +79,54 @@ pub enum Tool {
     Copy,
     /// 保存到本地
     Save,
+    /// 打开图片
+    Open,
+    /// 从图片取色
+    Eyedropper,
 }
 
 impl Tool {
+    /// 是否为需要输入文字的工具
+    pub fn is_text(&self) -> bool {
+        matches!(self, Tool::Text | Tool::Callout)
+    }
+
+    /// 是否可以设置填充色
+    pub fn has_fill(&self) -> bool {
+        matches!(self, Tool::Rectangle | Tool::Circle | Tool::Arrow | Tool::Number | Tool::Polygon)
+    }
+
+    /// 画出的标注是否可以带数据集类别
+    pub fn supports_label(&self) -> bool {
+        matches!(self, Tool::Rectangle | Tool::Circle | Tool::Polygon)
+    }
+
+    /// 启动时能否恢复为上次的工具：未实现的工具和一次性动作不行
+    pub fn is_restorable(&self) -> bool {
+        !matches!(
+            self,
+            Tool::Emoji | Tool::Pin | Tool::Copy | Tool::Save | Tool::Open | Tool::Eyedropper
+        )
+    }
+
+    /// 是否为绘制标注的工具，切换时记住各自使用的颜色
+    fn remembers_colors(&self) -> bool {
+        matches!(
+            self,
+            Tool::Rectangle
+                | Tool::Circle
+                | Tool::Arrow
+                | Tool::Line
+                | Tool::Pencil
+                | Tool::Number
+                | Tool::Emoji
+                | Tool::Text
+                | Tool::Callout
+                | Tool::Polygon
+                | Tool::Masaic
+        )
+    }
+
     /// 工具栏图标信息
     fn tool_icon(&self) -> (&'static str, &'static [u8], &'static str) {
         match self {
@@ -74,10 +139,14 @@ impl Tool {
             Tool::Number => ("bytes://number_icon.svg", NUMBER_ICON, "Number"),
             Tool::Emoji => ("bytes://emoji_icon.svg", EMOJI_ICON, "Emoji"),
             Tool::Text => ("bytes://text_icon.svg", TEXT_ICON, "Text"),
+            Tool::Callout => ("bytes://callout_icon.svg", CALLOUT_ICON, "Callout"),
+            Tool::Polygon => ("bytes://polygon_icon.svg", POLYGON_ICON, "Polygon"),
             Tool::Masaic => ("bytes://mosaic_icon.svg", MOSAIC_ICON, "Mosaic"),
             Tool::Pin => ("bytes://pin_icon.svg", PIN_ICON, "Pin"),
             Tool::Copy => ("bytes://copy_icon.svg", COPY_ICON, "Copy to Clipboard"),
             Tool::Save => ("bytes://save_icon.svg", SAVE_ICON, "Save"),
+            Tool::Open => ("bytes://open_icon.svg", OPEN_ICON, "Open Image"),
+            Tool::Eyedropper => ("bytes://eyedropper_icon.svg", EYEDROPPER_ICON, "Pick Color from Image"),
         }
     }
 }
@@ -110,10 +179,13 @@ impl crate::AnnotatorApp {
                                     self.toolbar_button(ui, Tool::Number);
                                     self.toolbar_button(ui, Tool::Emoji);
                                     self.toolbar_button(ui, Tool::Text);
+                                    self.toolbar_button(ui, Tool::Callout);
+                                    self.toolbar_button(ui, Tool::Polygon);
                                     self.toolbar_button(ui, Tool::Masaic);
 
                                     ui.separator();
 
+                                    self.toolbar_button(ui, Tool::Open);
                                     self.toolbar_button(ui, Tool::Pin);
                                     self.toolbar_button(ui, Tool::Copy);
                                     self.toolbar_button(ui, Tool::Save);
@@ -124,9 +196,28 @@ impl crate::AnnotatorApp {
                                     self.line_width_button(ui, StrokeWidth::THREE);
                                     self.line_width_button(ui, StrokeWidth::FIVE);
                                     ui.separator();
-                                    if self.color_picker.ui(ui) {
-                                        self.current_tool_info.color = self.color_picker.color();
+                                    self.color_controls(ui);
+                                    self.toolbar_button(ui, Tool::Eyedropper);
+                                    if self.current_tool_info.tool == Tool::Eyedropper {
+                                        ui.add(
+                                            egui::DragValue::new(&mut self.eyedropper.radius)
+                                                .range(0..=eyedropper::MAX_RADIUS)
+                                                .prefix("r "),
+                                        )
+                                        .on_hover_text("Sample radius in pixels");
+                                    }
+                                    if ui
+                                        .add(Button::new("Aa").min_size(egui::vec2(30.0, 30.0)))
+                                        .on_hover_text("Fonts")
+                                        .clicked()
+                                    {
+                                        self.font_picker.open();
                                     }
+                                    if self.current_tool_info.tool.is_text() {
+                                        ui.separator();
+                                        self.text_style_controls(ui);
+                                    }
+                                    self.label_controls(ui);
                                 });
                             });
                         });
@@ -134,18 +225,320 @@ impl crate::AnnotatorApp {
             });
     }
 
+    /// 描边色和填充色选择器，两个选择器共用调色板
+    fn color_controls(&mut self, ui: &mut Ui) {
+        let info = &mut self.current_tool_info;
+        // 切换工具或取色后颜色可能已改变
+        if !self.color_picker.is_open() {
+            self.color_picker.set_color(info.color);
+        }
+        if !self.fill_picker.is_open()
+            && let Some(fill) = info.fill_color
+        {
+            self.fill_picker.set_color(fill);
+        }
+        let fill_open = self.fill_picker.is_open();
+
+        let (response, color) = self.color_picker.show(ui);
+        response.on_hover_text("Stroke color");
+        if let Some(color) = color {
+            info.color = color;
+        }
+        if info.tool.has_fill() {
+            let mut filled = info.fill_color.is_some();
+            if ui.checkbox(&mut filled, "Fill").changed() {
+                info.fill_color = filled.then(|| self.fill_picker.color());
+            }
+            if filled {
+                let (response, color) = self.fill_picker.show(ui);
+                response.on_hover_text("Fill color");
+                if color.is_some() {
+                    info.fill_color = color;
+                }
+            }
+        }
+
+        if fill_open {
+            self.fill_picker.share_library(&mut self.color_picker);
+        } else {
+            self.color_picker.share_library(&mut self.fill_picker);
+        }
+    }
+
+    /// 数据集类别：选择工具下修改选中标注的类别，矩形和椭圆工具下设置新标注的类别
+    fn label_controls(&mut self, ui: &mut Ui) {
+        let info = &mut self.current_tool_info;
+        let mut selected = self
+            .documents
+            .get_mut(self.active)
+            .and_then(|doc| doc.operators.get_mut(doc.selected?))
+            .filter(|op| info.tool == Tool::Select && op.supports_label());
+        let label = match &selected {
+            Some(op) => &op.label,
+            None if info.tool.supports_label() => &info.label,
+            None => return,
+        };
+
+        ui.separator();
+        let mut text = label.clone().unwrap_or_default();
+        if let Some(label) = label {
+            let (rect, _) = ui.allocate_exact_size(egui::vec2(12.0, 12.0), egui::Sense::hover());
+            ui.painter().rect_filled(rect, 2.0, label_color(label));
+        }
+        let changed = ui
+            .add(
+                egui::TextEdit::singleline(&mut text)
+                    .hint_text(DEFAULT_LABEL)
+                    .desired_width(100.0),
+            )
+            .on_hover_text("Class label for dataset export (number keys pick from the label panel)")
+            .changed();
+        if changed {
+            let label = (!text.is_empty()).then_some(text);
+            match &mut selected {
+                Some(op) => op.set_label(label),
+                None => info.label = label,
+            }
+        }
+    }
+
+    /// 文字样式：字号、粗体、斜体和对齐方式
+    ///
+    /// 正在输入时修改当前文字，否则修改之后新建文字的默认样式
+    fn text_style_controls(&mut self, ui: &mut Ui) {
+        let info = &mut self.current_tool_info;
+        let style = match &mut info.text_editing {
+            Some(editing) => &mut editing.style,
+            None => &mut info.text_style,
+        };
+
+        ui.add(
+            egui::DragValue::new(&mut style.size)
+                .range(6.0..=200.0)
+                .speed(0.5)
+                .suffix(" px"),
+        )
+        .on_hover_text("Font size");
+        ui.toggle_value(&mut style.bold, egui::RichText::new("B").strong())
+            .on_hover_text("Bold");
+        ui.toggle_value(&mut style.italic, egui::RichText::new("I").italics())
+            .on_hover_text("Italic");
+        ui.separator();
+        for (align, label, hint) in [
+            (TextAlign::Left, "⏴", "Align left"),
+            (TextAlign::Center, "⏺", "Align center"),
+            (TextAlign::Right, "⏵", "Align right"),
+        ] {
+            ui.selectable_value(&mut style.align, align, label)
+                .on_hover_text(hint);
+        }
+        ui.separator();
+        optional_color(ui, &mut style.background, "Box", Color32::from_white_alpha(220));
+        if style.background.is_some() {
+            ui.add(egui::DragValue::new(&mut style.padding).range(0.0..=100.0).prefix("pad "))
+                .on_hover_text("Box padding");
+            ui.add(egui::DragValue::new(&mut style.corner_radius).range(0.0..=100.0).prefix("r "))
+                .on_hover_text("Box corner radius");
+        }
+        optional_color(ui, &mut style.outline, "Outline", Color32::WHITE);
+        if style.outline.is_some() {
+            ui.add(egui::DragValue::new(&mut style.outline_width).range(0.5..=20.0).speed(0.1))
+                .on_hover_text("Outline width");
+        }
+        optional_color(ui, &mut style.shadow, "Shadow", Color32::from_black_alpha(128));
+    }
+
+    /// 标签页栏，只有打开了图片时显示
+    pub fn tab_bar(&mut self, ctx: &Context) {
+        if self.documents.is_empty() {
+            return;
+        }
+
+        let mut select = None;
+        let mut close = None;
+        let mut save_all = false;
+        let mut save_as = false;
+        let mut save_report = false;
+        let mut export_dataset = None;
+        let mut save_project = false;
+        let save_all_hint = self.keymap.hint(ctx, Action::SaveAll, "Save every tab");
+        TopBottomPanel::top("tab_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                egui::ScrollArea::horizontal()
+                    .max_width(ui.available_width() - 470.0)
+                    .show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            for (i, doc) in self.documents.iter().enumerate() {
+                                let title = match &doc.path {
+                                    Some(path) => ui
+                                        .selectable_label(i == self.active, &doc.title)
+                                        .on_hover_text(path.display().to_string()),
+                                    None => ui.selectable_label(i == self.active, &doc.title),
+                                };
+                                if title.clicked() {
+                                    select = Some(i);
+                                }
+                                if title.middle_clicked()
+                                    || ui.small_button("×").on_hover_text("Close").clicked()
+                                {
+                                    close = Some(i);
+                                }
+                                ui.separator();
+                            }
+                        });
+                    });
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    save_all = ui
+                        .button("Save All")
+                        .on_hover_text(save_all_hint)
+                        .clicked();
+                    save_report = ui
+                        .button("PDF Report...")
+                        .on_hover_text("Save every tab into one PDF, one page per image")
+                        .clicked();
+                    save_as = ui
+                        .button("Save As...")
+                        .on_hover_text("Save this tab, format chosen by file extension")
+                        .clicked();
+                    ui.menu_button("Project", |ui| {
+                        if ui.button("Open Project...").clicked() {
+                            self.open_project_dialog.open();
+                        }
+                        save_project = ui
+                            .add_enabled(!self.documents.is_empty(), Button::new("Save Project..."))
+                            .on_hover_text("Save the image with editable annotations and labels")
+                            .clicked();
+                    });
+                    ui.menu_button("Dataset", |ui| {
+                        for format in DatasetFormat::ALL {
+                            if ui.button(format.label()).clicked() {
+                                export_dataset = Some(format);
+                            }
+                        }
+                        ui.separator();
+                        if ui.button("Load Labels...").clicked() {
+                            self.label_dialog.open();
+                        }
+                        ui.checkbox(&mut self.settings.show_labels, "Label Panel");
+                    })
+                    .response
+                    .on_hover_text("Export rectangles, ellipses and polygons as labelled boxes and segmentation masks");
+                    ui.menu_button(self.export.format.label(), |ui| self.export_options(ui))
+                        .response
+                        .on_hover_text("Export format");
+                });
+            });
+        });
+
+        if let Some(i) = select {
+            self.select_document(i);
+        }
+        if let Some(i) = close {
+            self.close_document(i);
+        }
+        if save_all {
+            self.save_all(ctx);
+        }
+        if save_as {
+            self.save_as();
+        }
+        if save_report {
+            self.save_report();
+        }
+        if let Some(format) = export_dataset {
+            self.export_dataset(format);
+        }
+        if save_project {
+            self.save_project();
+        }
+    }
+
+    /// 导出格式和选项，修改后记入设置
+    fn export_options(&mut self, ui: &mut Ui) {
+        let before = self.export.clone();
+        let export = &mut self.export;
+
+        ui.label("Format for Save");
+        for format in ExportFormat::ALL {
+            ui.radio_value(&mut export.format, format, format.label());
+        }
+        ui.separator();
+        ui.add(egui::Slider::new(&mut export.quality, 1..=100).text("Quality"))
+            .on_hover_text("JPEG and lossy WebP quality");
+        ui.checkbox(&mut export.webp_lossless, "Lossless WebP");
+        ui.horizontal(|ui| {
+            ui.color_edit_button_srgba(&mut export.background.0);
+            ui.label("Background");
+        })
+        .response
+        .on_hover_text("Fills transparent areas for formats without alpha (JPEG, BMP)");
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Scale");
+            for preset in SCALE_PRESETS {
+                ui.selectable_value(&mut export.scale, preset, format!("{preset}x"));
+            }
+            ui.add(
+                egui::DragValue::new(&mut export.scale)
+                    .range(SCALE_RANGE)
+                    .speed(0.05)
+                    .max_decimals(2)
+                    .suffix("x"),
+            )
+            .on_hover_text("Custom scale; annotations are rendered at the target resolution");
+        });
+        ui.checkbox(&mut export.overlay, "Annotations only")
+            .on_hover_text("Export a transparent overlay layer; mosaic regions become solid masks");
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("Save to");
+            match &self.settings.output_dir {
+                Some(dir) => {
+                    ui.label(dir.display().to_string());
+                    if ui.small_button("Reset").on_hover_text("Save next to the source image").clicked() {
+                        self.settings.output_dir = None;
+                    }
+                }
+                None => {
+                    ui.weak("Next to the source image");
+                }
+            }
+        })
+        .response
+        .on_hover_text("Folder used by Save and Save All; Save As remembers the folder it saved to");
+
+        if self.export != before {
+            self.settings.export = self.export.clone();
+        }
+    }
+
     // 工具栏图标按钮辅助函数
     fn toolbar_button(&mut self, ui: &mut Ui, tool: Tool) {
         let selected = self.current_tool_info.tool == tool;
         let (svg_uri, svg_data, tooltip) = tool.tool_icon();
+        // 提示中显示当前绑定的快捷键
+        let tooltip = self.keymap.hint(ui.ctx(), Action::Tool(tool), tooltip);
         let image = Image::from_bytes(svg_uri, svg_data);
         let button = Button::image(image)
             .fill(Color32::LIGHT_GRAY)
             .min_size(Self::BUTTON_SIZE)
             .frame(selected);
         if ui.add(button).on_hover_text(tooltip).clicked() {
-            self.current_tool_info.tool = tool;
+            match tool {
+                // 打开图片是一次性动作，不切换当前工具
+                Tool::Open => self.file_dialog.open(),
+                _ => self.select_tool(tool),
+            }
+        }
+    }
+
+    /// 切换工具，进入取色时记住当前工具，取色完成后切换回来
+    pub fn select_tool(&mut self, tool: Tool) {
+        let current = self.current_tool_info.tool;
+        if tool == Tool::Eyedropper && current != Tool::Eyedropper {
+            self.eyedropper.return_to = current;
         }
+        self.current_tool_info.switch_tool(tool);
     }
 
     // 线宽选择按钮辅助函数
@@ -169,7 +562,18 @@ impl crate::AnnotatorApp {
     }
 }
 
-#[allow(unused)]
+/// 可选颜色：勾选后显示取色按钮，取消勾选表示不使用
+fn optional_color(ui: &mut Ui, color: &mut Option<Color32>, label: &str, default: Color32) {
+    let mut enabled = color.is_some();
+    if ui.checkbox(&mut enabled, label).changed() {
+        *color = enabled.then_some(default);
+    }
+    if let Some(color) = color {
+        ui.color_edit_button_srgba(color);
+    }
+}
+
+#[allow(unused, clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Default, Copy, PartialEq)]
 pub enum StrokeWidth {
     ONE,
@@ -179,9 +583,9 @@ pub enum StrokeWidth {
     Custom(f32),
 }
 
-impl Into<f32> for StrokeWidth {
-    fn into(self) -> f32 {
-        match self {
+impl From<StrokeWidth> for f32 {
+    fn from(value: StrokeWidth) -> Self {
+        match value {
             StrokeWidth::ONE => 1f32,
             StrokeWidth::THREE => 3f32,
             StrokeWidth::FIVE => 5f32,
@@ -190,6 +594,17 @@ impl Into<f32> for StrokeWidth {
     }
 }
 
+impl From<f32> for StrokeWidth {
+    fn from(value: f32) -> Self {
+        match value {
+            1.0 => StrokeWidth::ONE,
+            3.0 => StrokeWidth::THREE,
+            5.0 => StrokeWidth::FIVE,
+            x => StrokeWidth::Custom(x),
+        }
+    }
+}
+
 impl Mul<f32> for StrokeWidth {
     type Output = f32;
 
@@ -207,10 +622,11 @@ impl Mul<StrokeWidth> for f32 {
         self * base
     }
 }
-#[derive(Debug, Default, Clone)]
-pub struct TextEditState {
-    pub pos: Pos2,
-    pub content: String,
+/// 工具上次使用的描边色和填充色
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ToolColors {
+    pub stroke: HexColor,
+    pub fill: Option<HexColor>,
 }
 
 /// 当前选择的工具信息及事件相关属性
@@ -218,12 +634,22 @@ pub struct TextEditState {
 pub struct ToolInfo {
     pub tool: Tool,
     pub stroke_width: StrokeWidth,
+    /// 描边色
     pub color: Color32,
+    /// 填充色，为 None 时不填充
+    pub fill_color: Option<Color32>,
+    /// 每个工具上次使用的颜色
+    pub tool_colors: HashMap<Tool, ToolColors>,
     pub start_pos: Option<Pos2>,
     pub end_pos: Option<Pos2>,
     pub tracks: Vec<Option<Pos2>>,
-    pub number: u8,
     pub text_editing: Option<TextEditState>,
+    /// 新建文字使用的样式
+    pub text_style: TextStyle,
+    /// 新建矩形、椭圆和多边形的数据集类别
+    pub label: Option<String>,
+    /// 正在绘制的多边形顶点（图片坐标）
+    pub polygon: Vec<Pos2>,
 }
 
 impl ToolInfo {
@@ -234,6 +660,28 @@ impl ToolInfo {
         }
     }
 
+    /// 切换工具：记住当前工具的颜色，恢复新工具上次使用的颜色
+    pub fn switch_tool(&mut self, tool: Tool) {
+        self.remember_colors();
+        self.polygon.clear();
+        if let Some(colors) = self.tool_colors.get(&tool) {
+            self.color = colors.stroke.0;
+            self.fill_color = colors.fill.map(|c| c.0);
+        }
+        self.tool = tool;
+    }
+
+    /// 把当前颜色记为当前工具上次使用的颜色
+    pub fn remember_colors(&mut self) {
+        if self.tool.remembers_colors() {
+            let colors = ToolColors {
+                stroke: HexColor(self.color),
+                fill: self.fill_color.map(HexColor),
+            };
+            self.tool_colors.insert(self.tool, colors);
+        }
+    }
+
     /// 事件：拖动，点击..
     pub fn input_event(
         &mut self,
@@ -243,11 +691,11 @@ impl ToolInfo {
     ) -> Option<Operator> {
         match self.tool {
             Tool::Select => {}
-            Tool::Rectangle | Tool::Circle | Tool::Line | Tool::Arrow => {
-                if response.drag_started_by(PointerButton::Primary) {
-                    if let Some(origin) = ui.input(|i| i.pointer.press_origin()) {
-                        self.start_pos = Some(origin);
-                    }
+            Tool::Rectangle | Tool::Circle | Tool::Line | Tool::Arrow | Tool::Masaic => {
+                if response.drag_started_by(PointerButton::Primary)
+                    && let Some(origin) = ui.input(|i| i.pointer.press_origin())
+                {
+                    self.start_pos = Some(origin);
                 }
 
                 if response.drag_stopped_by(PointerButton::Primary) {
@@ -261,10 +709,10 @@ impl ToolInfo {
                 }
             }
             Tool::Pencil => {
-                if response.drag_started_by(PointerButton::Primary) {
-                    if let Some(origin) = ui.input(|i| i.pointer.press_origin()) {
-                        self.tracks.push(Some(origin));
-                    }
+                if response.drag_started_by(PointerButton::Primary)
+                    && let Some(origin) = ui.input(|i| i.pointer.press_origin())
+                {
+                    self.tracks.push(Some(origin));
                 }
                 if response.dragged_by(PointerButton::Primary) {
                     self.tracks.push(response.interact_pointer_pos());
@@ -277,32 +725,84 @@ impl ToolInfo {
                 }
             }
             Tool::Number => {
-                if response.clicked_by(PointerButton::Primary) {
-                    if let Some(point) = response.interact_pointer_pos() {
-                        self.start_pos = Some(point);
-                        let opt = self.get_operator(helper, None);
-                        self.start_pos = None;
-                        self.number += 1;
-                        return opt;
-                    }
+                if response.clicked_by(PointerButton::Primary)
+                    && let Some(point) = response.interact_pointer_pos()
+                {
+                    self.start_pos = Some(point);
+                    let opt = self.get_operator(helper, None);
+                    self.start_pos = None;
+                    return opt;
                 }
             }
             Tool::Emoji => {}
             Tool::Text => {
-                if self.text_editing.is_none() && response.clicked_by(PointerButton::Primary) {
-                    if let Some(point) = response.interact_pointer_pos() {
-                        let img_pos = helper.screen_to_image(point, None);
-                        self.text_editing = Some(TextEditState {
-                            pos: img_pos,
-                            content: String::new(),
-                        });
-                    }
+                if self.text_editing.is_none() && response.clicked_by(PointerButton::Primary)
+                    && let Some(point) = response.interact_pointer_pos()
+                {
+                    let img_pos = helper.screen_to_image(point, None);
+                    self.text_editing = Some(TextEditState {
+                        pos: img_pos,
+                        content: String::new(),
+                        style: self.text_style.clone(),
+                        target: None,
+                        replaces: None,
+                        retained: None,
+                    });
+                }
+            }
+            Tool::Callout => {
+                // 从指向的目标拖到气泡位置，单击时气泡放在目标右上方
+                if self.text_editing.is_some() {
+                    return None;
+                }
+                if response.drag_started_by(PointerButton::Primary)
+                    && let Some(origin) = ui.input(|i| i.pointer.press_origin())
+                {
+                    self.start_pos = Some(origin);
+                }
+                let target_and_pos = if response.drag_stopped_by(PointerButton::Primary) {
+                    self.start_pos.take().zip(response.interact_pointer_pos())
+                } else if response.clicked_by(PointerButton::Primary) {
+                    response
+                        .interact_pointer_pos()
+                        .map(|p| (p, p + Vec2::new(40.0, -60.0)))
+                } else {
+                    None
+                };
+                if let Some((target, pos)) = target_and_pos {
+                    self.text_editing = Some(TextEditState {
+                        pos: helper.screen_to_image(pos, None),
+                        content: String::new(),
+                        style: self.text_style.clone(),
+                        target: Some(helper.screen_to_image(target, None)),
+                        replaces: None,
+                        retained: None,
+                    });
+                }
+            }
+            Tool::Polygon => {
+                // 单击添加顶点，双击（第一次单击已添加最后一个顶点）或回车闭合，Esc 放弃；
+                // 输入框有焦点时回车属于输入框
+                let enter = !ui.ctx().wants_keyboard_input() && ui.input(|i| i.key_pressed(Key::Enter));
+                if response.double_clicked_by(PointerButton::Primary) || enter {
+                    let op = self.get_operator(helper, None);
+                    self.polygon.clear();
+                    return op;
+                }
+                if response.clicked_by(PointerButton::Primary)
+                    && let Some(pos) = response.interact_pointer_pos()
+                {
+                    self.polygon.push(helper.screen_to_image(pos, None));
+                }
+                if ui.input(|i| i.key_pressed(Key::Escape)) {
+                    self.polygon.clear();
                 }
             }
-            Tool::Masaic => {}
             Tool::Pin => {}
             Tool::Copy => {}
             Tool::Save => {}
+            Tool::Open => {}
+            Tool::Eyedropper => {}
         }
         None
     }
@@ -316,33 +816,70 @@ impl ToolInfo {
         match self.tool {
             Tool::Select => {}
             Tool::Rectangle | Tool::Circle | Tool::Line | Tool::Arrow => {
-                if self.start_pos.is_some() {
-                    if let Some(end) = response.interact_pointer_pos() {
-                        let op = self.get_operator(helper, Some(end)).unwrap();
-                        op.draw(helper, painter);
-                    }
+                if self.start_pos.is_some()
+                    && let Some(end) = response.interact_pointer_pos()
+                {
+                    let op = self.get_operator(helper, Some(end)).unwrap();
+                    op.draw(helper, painter);
                 }
             }
             Tool::Pencil => {
-                if let Some(end) = response.interact_pointer_pos() {
-                    if let Some(op) = self.get_operator(helper, Some(end)) {
-                        op.draw(helper, painter);
-                    }
+                if let Some(end) = response.interact_pointer_pos()
+                    && let Some(op) = self.get_operator(helper, Some(end))
+                {
+                    op.draw(helper, painter);
                 }
             }
             Tool::Number => {
-                if self.start_pos.is_some() {
-                    if let Some(op) = self.get_operator(helper, None) {
-                        op.draw(helper, painter);
-                    }
+                if self.start_pos.is_some()
+                    && let Some(op) = self.get_operator(helper, None)
+                {
+                    op.draw(helper, painter);
                 }
             }
             Tool::Emoji => {}
             Tool::Text => {}
-            Tool::Masaic => {}
+            Tool::Callout => {
+                if let Some(start) = self.start_pos
+                    && let Some(end) = response.interact_pointer_pos()
+                {
+                    painter.line_segment([start, end], Stroke::new(1.0, self.color));
+                    painter.circle_filled(start, 3.0, self.color);
+                }
+            }
+            Tool::Polygon => {
+                // 已有的边加上到指针的边
+                let mut points: Vec<Pos2> = self.polygon.iter().map(|p| helper.image_to_screen(*p)).collect();
+                let vertices = points.len();
+                if vertices > 0 {
+                    if let Some(hover) = response.hover_pos() {
+                        points.push(hover);
+                    }
+                    let stroke = Stroke::new(self.stroke_width, self.color);
+                    painter.add(PathShape::line(points.clone(), stroke));
+                    for &p in &points[..vertices] {
+                        painter.circle_filled(p, 3.0, self.color);
+                    }
+                }
+            }
+            Tool::Masaic => {
+                // 拖动时只显示区域边框，松开后再打码
+                if let Some(start) = self.start_pos
+                    && let Some(end) = response.interact_pointer_pos()
+                {
+                    painter.rect_stroke(
+                        Rect::from_two_pos(start, end),
+                        0.0,
+                        Stroke::new(1.0, Color32::GRAY),
+                        StrokeKind::Outside,
+                    );
+                }
+            }
             Tool::Pin => {}
             Tool::Copy => {}
             Tool::Save => {}
+            Tool::Open => {}
+            Tool::Eyedropper => {}
         }
     }
 
@@ -356,11 +893,14 @@ impl ToolInfo {
 
         let width = self.stroke_width;
         let color = self.color;
+        let fill = self.fill_color;
         match self.tool {
             Tool::Select => None,
             Tool::Rectangle => {
                 let rect = Rect::from_two_pos(start, end);
-                Some(Operator::new(ToolType::Rect(rect), width, color, None))
+                let mut op = Operator::new(ToolType::Rect(rect), width, color, fill);
+                op.set_label(self.label.clone());
+                Some(op)
             }
             Tool::Circle => {
                 let radius =
@@ -369,23 +909,27 @@ impl ToolInfo {
                 let e = EllipseShape {
                     center,
                     radius,
-                    fill: Color32::TRANSPARENT,
+                    fill: fill.unwrap_or(Color32::TRANSPARENT),
                     stroke: Stroke::new(width, color),
                 };
-                Some(Operator::new(ToolType::Ellipse(e), width, color, None))
+                let mut op = Operator::new(ToolType::Ellipse(e), width, color, fill);
+                op.set_label(self.label.clone());
+                Some(op)
             }
             Tool::Arrow => {
+                // 不填充时箭头为描边色的实心箭头
+                let fill = fill.unwrap_or(color);
                 let ps = PathShape {
                     points: arrow_points(start, end, width),
                     closed: true,
-                    fill: color,
+                    fill,
                     stroke: PathStroke::new(width, color),
                 };
                 Some(Operator::new(
                     ToolType::Arrow(ps),
                     width,
                     color,
-                    Some(color),
+                    Some(fill),
                 ))
             }
             Tool::Line => Some(Operator::new(
@@ -414,26 +958,42 @@ impl ToolInfo {
                 }
             }
             Tool::Number => {
+                // 不填充时徽章底色为描边色
+                let fill = fill.unwrap_or(color);
                 let radius = 10.0 + width * 5.0;
                 let shape = CircleShape {
                     center: start,
                     radius,
-                    fill: color,
-                    stroke: Stroke::new(1.0, Color32::BLACK),
+                    fill,
+                    stroke: Stroke::new(width, color),
                 };
                 Some(Operator::new(
-                    ToolType::Number(shape, self.number),
+                    ToolType::Number(shape, helper.number),
                     width,
                     color,
-                    Some(color),
+                    Some(fill),
                 ))
             }
             Tool::Emoji => todo!(),
-            Tool::Text => None, // 需要等输入完成后才创建 Operator
-            Tool::Masaic => todo!(),
+            Tool::Text | Tool::Callout => None, // 需要等输入完成后才创建 Operator
+            Tool::Polygon => {
+                if self.polygon.len() < 3 {
+                    return None;
+                }
+                let mut op = Operator::new(ToolType::Polygon(self.polygon.clone()), width, color, fill);
+                op.set_label(self.label.clone());
+                Some(op)
+            }
+            Tool::Masaic => Some(Operator::new(
+                ToolType::Mosaic(Rect::from_two_pos(start, end)),
+                width,
+                color,
+                None,
+            )),
             Tool::Pin => todo!(),
             Tool::Copy => todo!(),
             Tool::Save => todo!(),
+            Tool::Open | Tool::Eyedropper => None,
         }
     }
 }
@@ -1,20 +1,23 @@
 use egui::{Pos2, Rect, Vec2};
 
-use crate::AnnotatorApp;
+use crate::document::Document;
 
 #[derive(Debug, Clone, Copy)]
 pub struct AppHelper {
     pub image_rect_min: Pos2,
     pub image_size: Vec2,
     pub zoom: f32,
+    /// 下一个数字标注的编号
+    pub number: u8,
 }
 
 impl AppHelper {
-    pub fn from_app(app: &AnnotatorApp) -> Self {
+    pub fn from_document(doc: &Document) -> Self {
         AppHelper {
-            image_rect_min: app.last_image_rect.map_or(Pos2::default(), |r| r.min),
-            image_size: app.image_size,
-            zoom: app.zoom,
+            image_rect_min: doc.last_image_rect.map_or(Pos2::default(), |r| r.min),
+            image_size: doc.image_size,
+            zoom: doc.zoom,
+            number: doc.number,
         }
     }
 
@@ -0,0 +1,853 @@
+use image::codecs::webp::WebPEncoder;
+use image::{ExtendedColorType, RgbaImage};
+
+/// VP8 允许的最大宽高
+const MAX_SIZE: u32 = 16383;
+
+/// 有损 WebP：VP8 关键帧，宏块使用 16x16 帧内预测；带透明度时透明通道无损压缩后写入 ALPH 块
+///
+/// `quality` 为 1-100，越高量化步长越小
+pub fn encode_lossy(img: &RgbaImage, quality: u8) -> Result<Vec<u8>, String> {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 || width > MAX_SIZE || height > MAX_SIZE {
+        return Err(format!("lossy WebP supports 1-{MAX_SIZE} pixels per side, got {width}x{height}"));
+    }
+    let q_index = (u32::from(100 - quality.clamp(1, 100)) * 127 + 49) / 99;
+    let vp8 = encode_frame(img, q_index as usize);
+
+    let mut chunks = Vec::new();
+    if img.pixels().any(|p| p[3] < 255) {
+        let mut vp8x = vec![0x10, 0, 0, 0];
+        vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+        write_chunk(&mut chunks, b"VP8X", &vp8x);
+        write_chunk(&mut chunks, b"ALPH", &encode_alpha(img)?);
+    }
+    write_chunk(&mut chunks, b"VP8 ", &vp8);
+
+    let mut riff = Vec::with_capacity(chunks.len() + 12);
+    riff.extend_from_slice(b"RIFF");
+    riff.extend_from_slice(&(chunks.len() as u32 + 4).to_le_bytes());
+    riff.extend_from_slice(b"WEBP");
+    riff.extend_from_slice(&chunks);
+    Ok(riff)
+}
+
+fn write_chunk(out: &mut Vec<u8>, name: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(name);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// ALPH 块：压缩方式 1 为无损图像流，不带 VP8L 的 5 字节头，透明度放在绿色通道
+fn encode_alpha(img: &RgbaImage) -> Result<Vec<u8>, String> {
+    let alpha: Vec<u8> = img.pixels().map(|p| p[3]).collect();
+    let mut webp = Vec::new();
+    WebPEncoder::new_lossless(&mut webp)
+        .encode(&alpha, img.width(), img.height(), ExtendedColorType::L8)
+        .map_err(|e| e.to_string())?;
+
+    let mut rest = webp.get(12..).unwrap_or_default();
+    while rest.len() >= 8 {
+        let size = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        let data = rest.get(8..8 + size).ok_or("truncated lossless alpha stream")?;
+        if &rest[..4] == b"VP8L" {
+            let mut alph = vec![1];
+            alph.extend_from_slice(data.get(5..).unwrap_or_default());
+            return Ok(alph);
+        }
+        rest = rest.get(8 + size + size % 2..).unwrap_or_default();
+    }
+    Err("lossless encoder produced no VP8L chunk".to_owned())
+}
+
+/// 宏块的 16x16 和色度预测模式，取值与码流中一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Dc,
+    V,
+    H,
+    Tm,
+}
+
+const MODES: [Mode; 4] = [Mode::Dc, Mode::V, Mode::H, Mode::Tm];
+
+/// 一个量化段的量化步长
+struct Quant {
+    y_dc: i32,
+    y_ac: i32,
+    y2_dc: i32,
+    y2_ac: i32,
+    uv_dc: i32,
+    uv_ac: i32,
+}
+
+impl Quant {
+    /// 与解码器相同：所有 delta 为 0 时由一个索引导出
+    fn new(index: usize) -> Self {
+        let dc = i32::from(DC_QUANT[index]);
+        let ac = i32::from(AC_QUANT[index]);
+        Self {
+            y_dc: dc,
+            y_ac: ac,
+            y2_dc: dc * 2,
+            y2_ac: (ac * 155 / 100).max(8),
+            uv_dc: dc.min(132),
+            uv_ac: ac,
+        }
+    }
+}
+
+/// 按 16 像素对齐并复制边缘填充的 YUV 4:2:0 平面
+struct Plane {
+    data: Vec<u8>,
+    stride: usize,
+}
+
+impl Plane {
+    fn at(&self, x: usize, y: usize) -> u8 {
+        self.data[y * self.stride + x]
+    }
+
+    /// 宏块 (mbx, mby) 的 `size`x`size` 块
+    fn block(&self, mbx: usize, mby: usize, size: usize) -> Vec<u8> {
+        (0..size * size).map(|i| self.at(mbx * size + i % size, mby * size + i / size)).collect()
+    }
+}
+
+/// BT.601 有限范围，与 libwebp 相同的定点系数；色度取 2x2 像素的和再换算
+fn to_yuv(img: &RgbaImage, mb_width: usize, mb_height: usize) -> [Plane; 3] {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let pixel = |x: usize, y: usize| {
+        let p = img.get_pixel(x.min(width - 1) as u32, y.min(height - 1) as u32);
+        [i32::from(p[0]), i32::from(p[1]), i32::from(p[2])]
+    };
+    let luma_stride = mb_width * 16;
+    let mut y_plane = vec![0; luma_stride * mb_height * 16];
+    for (i, luma) in y_plane.iter_mut().enumerate() {
+        let [r, g, b] = pixel(i % luma_stride, i / luma_stride);
+        *luma = ((16839 * r + 33059 * g + 6420 * b + (16 << 16) + (1 << 15)) >> 16) as u8;
+    }
+    let chroma_stride = mb_width * 8;
+    let mut u_plane = vec![0; chroma_stride * mb_height * 8];
+    let mut v_plane = vec![0; chroma_stride * mb_height * 8];
+    for i in 0..u_plane.len() {
+        let (x, y) = (i % chroma_stride * 2, i / chroma_stride * 2);
+        let mut sum = [0; 3];
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            for (s, c) in sum.iter_mut().zip(pixel(x + dx, y + dy)) {
+                *s += c;
+            }
+        }
+        let [r, g, b] = sum;
+        let clip = |v: i32| ((v + (1 << 17) + (128 << 18)) >> 18).clamp(0, 255) as u8;
+        u_plane[i] = clip(-9719 * r - 19081 * g + 28800 * b);
+        v_plane[i] = clip(28800 * r - 24116 * g - 4684 * b);
+    }
+    [
+        Plane { data: y_plane, stride: luma_stride },
+        Plane { data: u_plane, stride: chroma_stride },
+        Plane { data: v_plane, stride: chroma_stride },
+    ]
+}
+
+/// 编码一个 VP8 关键帧：第一分区为帧头和宏块模式，第二分区为系数
+fn encode_frame(img: &RgbaImage, q_index: usize) -> Vec<u8> {
+    let (width, height) = img.dimensions();
+    let mb_width = width.div_ceil(16) as usize;
+    let mb_height = height.div_ceil(16) as usize;
+    let source = to_yuv(img, mb_width, mb_height);
+    let mut recon = [
+        Plane { data: vec![0; source[0].data.len()], stride: source[0].stride },
+        Plane { data: vec![0; source[1].data.len()], stride: source[1].stride },
+        Plane { data: vec![0; source[2].data.len()], stride: source[2].stride },
+    ];
+    let quant = Quant::new(q_index);
+
+    let mut header = BoolEncoder::new();
+    // 色彩空间、钳位方式、不分段、不做环路滤波
+    header.put_literal(0, 2);
+    header.put_flag(false);
+    header.put_literal(0, 1);
+    header.put_literal(0, 6);
+    header.put_literal(0, 3);
+    header.put_flag(false);
+    // 一个系数分区
+    header.put_literal(0, 2);
+    header.put_literal(q_index as u32, 7);
+    for _ in 0..5 {
+        header.put_flag(false);
+    }
+    // refresh_entropy_probs，系数概率不更新，不跳过宏块
+    header.put_literal(0, 1);
+    for prob in COEFF_UPDATE_PROBS.iter().flatten().flatten().flatten() {
+        header.put(*prob, false);
+    }
+    header.put_literal(0, 1);
+
+    let mut tokens = BoolEncoder::new();
+    // 每个宏块上方和左侧的 9 个非零标记：Y2、4 列 Y、2 列 U、2 列 V
+    let mut top_nz = vec![[false; 9]; mb_width];
+    for mby in 0..mb_height {
+        let mut left_nz = [false; 9];
+        for (mbx, top) in top_nz.iter_mut().enumerate() {
+            let luma = encode_luma(&source[0], &mut recon[0], mbx, mby, &quant);
+            let chroma = [1, 2].map(|i| encode_chroma_planes(&source[i], &recon[i], mbx, mby));
+            let uv_mode = best_chroma_mode(&chroma);
+            let uv = [1, 2].map(|i| {
+                let (src, pred) = (&chroma[i - 1].0, &chroma[i - 1].1[uv_mode as usize]);
+                reconstruct_chroma(src, pred, &mut recon[i], mbx, mby, &quant)
+            });
+
+            header.put_luma_mode(luma.mode);
+            header.put_chroma_mode(uv_mode);
+            write_macroblock(&mut tokens, &luma.y2, &luma.y, &uv, (top, &mut left_nz));
+        }
+    }
+
+    let first = header.finish();
+    let second = tokens.finish();
+    let mut frame = Vec::with_capacity(10 + first.len() + second.len());
+    let tag = ((first.len() as u32) << 5) | (1 << 4);
+    frame.extend_from_slice(&tag.to_le_bytes()[..3]);
+    frame.extend_from_slice(&[0x9d, 0x01, 0x2a]);
+    frame.extend_from_slice(&(width as u16).to_le_bytes());
+    frame.extend_from_slice(&(height as u16).to_le_bytes());
+    frame.extend_from_slice(&first);
+    frame.extend_from_slice(&second);
+    frame
+}
+
+/// 亮度宏块的编码结果：预测模式、Y2 和 16 个 Y 块的量化系数
+struct Luma {
+    mode: Mode,
+    y2: [i32; 16],
+    y: [[i32; 16]; 16],
+}
+
+/// 与解码器相同的边界：第一行上方为 127，第一列左侧为 129
+fn borders(recon: &Plane, mbx: usize, mby: usize, size: usize) -> (Vec<u8>, Vec<u8>, u8) {
+    let (x0, y0) = (mbx * size, mby * size);
+    let above = if mby == 0 { vec![127; size] } else { (0..size).map(|x| recon.at(x0 + x, y0 - 1)).collect() };
+    let left = if mbx == 0 { vec![129; size] } else { (0..size).map(|y| recon.at(x0 - 1, y0 + y)).collect() };
+    let corner = if mby == 0 {
+        127
+    } else if mbx == 0 {
+        129
+    } else {
+        recon.at(x0 - 1, y0 - 1)
+    };
+    (above, left, corner)
+}
+
+fn predict(mode: Mode, size: usize, (above, left, corner): &(Vec<u8>, Vec<u8>, u8), mbx: usize, mby: usize) -> Vec<u8> {
+    match mode {
+        Mode::Dc => {
+            let mut shift = if size == 8 { 2 } else { 3 };
+            let mut sum = 0u32;
+            if mby > 0 {
+                sum += above.iter().map(|&v| u32::from(v)).sum::<u32>();
+                shift += 1;
+            }
+            if mbx > 0 {
+                sum += left.iter().map(|&v| u32::from(v)).sum::<u32>();
+                shift += 1;
+            }
+            let dc = if mbx == 0 && mby == 0 { 128 } else { (sum + (1 << (shift - 1))) >> shift };
+            vec![dc as u8; size * size]
+        }
+        Mode::V => (0..size * size).map(|i| above[i % size]).collect(),
+        Mode::H => (0..size * size).map(|i| left[i / size]).collect(),
+        Mode::Tm => (0..size * size)
+            .map(|i| (i32::from(left[i / size]) + i32::from(above[i % size]) - i32::from(*corner)).clamp(0, 255) as u8)
+            .collect(),
+    }
+}
+
+fn sse(a: &[u8], b: &[u8]) -> u64 {
+    a.iter().zip(b).map(|(&a, &b)| (i64::from(a) - i64::from(b)).pow(2) as u64).sum()
+}
+
+/// 大小为 `size` 的块中第 `i` 个 4x4 子块的残差
+fn residual(src: &[u8], pred: &[u8], size: usize, i: usize) -> [i32; 16] {
+    let (bx, by) = (i % (size / 4) * 4, i / (size / 4) * 4);
+    std::array::from_fn(|k| {
+        let index = (by + k / 4) * size + bx + k % 4;
+        i32::from(src[index]) - i32::from(pred[index])
+    })
+}
+
+/// 把反变换后的残差加到预测上，写入重建平面
+fn add_residual(recon: &mut Plane, pred: &[u8], size: usize, mbx: usize, mby: usize, i: usize, res: &[i32; 16]) {
+    let (bx, by) = (i % (size / 4) * 4, i / (size / 4) * 4);
+    for (k, r) in res.iter().enumerate() {
+        let (x, y) = (bx + k % 4, by + k / 4);
+        let value = (i32::from(pred[y * size + x]) + r).clamp(0, 255) as u8;
+        recon.data[(mby * size + y) * recon.stride + mbx * size + x] = value;
+    }
+}
+
+fn encode_luma(src: &Plane, recon: &mut Plane, mbx: usize, mby: usize, quant: &Quant) -> Luma {
+    let source = src.block(mbx, mby, 16);
+    let border = borders(recon, mbx, mby, 16);
+    let (mode, pred) = MODES
+        .iter()
+        .map(|&mode| (mode, predict(mode, 16, &border, mbx, mby)))
+        .min_by_key(|(_, pred)| sse(&source, pred))
+        .unwrap();
+
+    let coeffs: [[i32; 16]; 16] = std::array::from_fn(|i| fdct(&residual(&source, &pred, 16, i)));
+    let mut y2 = fwht(&std::array::from_fn(|i| coeffs[i][0]));
+    quantize(&mut y2, 0, quant.y2_dc, quant.y2_ac);
+    let mut dc = dequantize(&y2, quant.y2_dc, quant.y2_ac);
+    iwht4x4(&mut dc);
+
+    let mut y = coeffs;
+    for (i, block) in y.iter_mut().enumerate() {
+        quantize(block, 1, quant.y_dc, quant.y_ac);
+        let mut res = dequantize(block, quant.y_dc, quant.y_ac);
+        res[0] = dc[i];
+        idct4x4(&mut res);
+        add_residual(recon, &pred, 16, mbx, mby, i, &res);
+    }
+    Luma { mode, y2, y }
+}
+
+/// 一个色度平面的原始像素和四种模式的预测
+fn encode_chroma_planes(src: &Plane, recon: &Plane, mbx: usize, mby: usize) -> (Vec<u8>, [Vec<u8>; 4]) {
+    let border = borders(recon, mbx, mby, 8);
+    (src.block(mbx, mby, 8), MODES.map(|mode| predict(mode, 8, &border, mbx, mby)))
+}
+
+/// U 和 V 共用一个模式，取两者误差之和最小的
+fn best_chroma_mode(planes: &[(Vec<u8>, [Vec<u8>; 4]); 2]) -> Mode {
+    MODES
+        .into_iter()
+        .min_by_key(|&mode| planes.iter().map(|(src, preds)| sse(src, &preds[mode as usize])).sum::<u64>())
+        .unwrap()
+}
+
+fn reconstruct_chroma(src: &[u8], pred: &[u8], recon: &mut Plane, mbx: usize, mby: usize, quant: &Quant) -> [[i32; 16]; 4] {
+    std::array::from_fn(|i| {
+        let mut block = fdct(&residual(src, pred, 8, i));
+        quantize(&mut block, 0, quant.uv_dc, quant.uv_ac);
+        let mut res = dequantize(&block, quant.uv_dc, quant.uv_ac);
+        idct4x4(&mut res);
+        add_residual(recon, pred, 8, mbx, mby, i, &res);
+        block
+    })
+}
+
+/// 按步长四舍五入为量化级别，从 `first` 开始，之前的系数置 0
+fn quantize(block: &mut [i32; 16], first: usize, dc: i32, ac: i32) {
+    for (i, c) in block.iter_mut().enumerate() {
+        let step = if i == 0 { dc } else { ac };
+        *c = if i < first { 0 } else { c.signum() * ((c.abs() + step / 2) / step).min(2048) };
+    }
+}
+
+fn dequantize(block: &[i32; 16], dc: i32, ac: i32) -> [i32; 16] {
+    std::array::from_fn(|i| block[i] * if i == 0 { dc } else { ac })
+}
+
+/// 写出一个宏块的全部系数，并更新上方和左侧的非零标记
+fn write_macroblock(
+    tokens: &mut BoolEncoder,
+    y2: &[i32; 16],
+    y: &[[i32; 16]; 16],
+    uv: &[[[i32; 16]; 4]; 2],
+    (top, left): (&mut [bool; 9], &mut [bool; 9]),
+) {
+    let nz = tokens.put_block(&COEFF_PROBS[1], y2, 0, usize::from(top[0]) + usize::from(left[0]));
+    top[0] = nz;
+    left[0] = nz;
+    for (i, block) in y.iter().enumerate() {
+        let (x, row) = (1 + i % 4, 1 + i / 4);
+        let nz = tokens.put_block(&COEFF_PROBS[0], block, 1, usize::from(top[x]) + usize::from(left[row]));
+        top[x] = nz;
+        left[row] = nz;
+    }
+    for (plane, blocks) in uv.iter().enumerate() {
+        for (i, block) in blocks.iter().enumerate() {
+            let (x, row) = (5 + plane * 2 + i % 2, 5 + plane * 2 + i / 2);
+            let nz = tokens.put_block(&COEFF_PROBS[2], block, 0, usize::from(top[x]) + usize::from(left[row]));
+            top[x] = nz;
+            left[row] = nz;
+        }
+    }
+}
+
+/// VP8 的布尔算术编码器（RFC 6386 7.3）
+struct BoolEncoder {
+    output: Vec<u8>,
+    range: u32,
+    bottom: u32,
+    bit_count: i32,
+}
+
+impl BoolEncoder {
+    fn new() -> Self {
+        Self {
+            output: Vec::new(),
+            range: 255,
+            bottom: 0,
+            bit_count: 24,
+        }
+    }
+
+    /// 进位传到已经输出的字节
+    fn carry(&mut self) {
+        for byte in self.output.iter_mut().rev() {
+            if *byte == 255 {
+                *byte = 0;
+            } else {
+                *byte += 1;
+                break;
+            }
+        }
+    }
+
+    /// 以 `prob`/256 为取 false 的概率写一位
+    fn put(&mut self, prob: u8, value: bool) {
+        let split = 1 + (((self.range - 1) * u32::from(prob)) >> 8);
+        if value {
+            self.bottom += split;
+            self.range -= split;
+        } else {
+            self.range = split;
+        }
+        while self.range < 128 {
+            self.range <<= 1;
+            if self.bottom & (1 << 31) != 0 {
+                self.carry();
+            }
+            self.bottom <<= 1;
+            self.bit_count -= 1;
+            if self.bit_count == 0 {
+                self.output.push((self.bottom >> 24) as u8);
+                self.bottom &= (1 << 24) - 1;
+                self.bit_count = 8;
+            }
+        }
+    }
+
+    fn put_flag(&mut self, value: bool) {
+        self.put(128, value);
+    }
+
+    fn put_literal(&mut self, value: u32, bits: u32) {
+        for bit in (0..bits).rev() {
+            self.put_flag((value >> bit) & 1 != 0);
+        }
+    }
+
+    /// 关键帧 16x16 亮度模式树，第一位为 1 表示不是 B_PRED
+    fn put_luma_mode(&mut self, mode: Mode) {
+        self.put(145, true);
+        match mode {
+            Mode::Dc | Mode::V => {
+                self.put(156, false);
+                self.put(163, mode == Mode::V);
+            }
+            Mode::H | Mode::Tm => {
+                self.put(156, true);
+                self.put(128, mode == Mode::Tm);
+            }
+        }
+    }
+
+    fn put_chroma_mode(&mut self, mode: Mode) {
+        self.put(142, mode != Mode::Dc);
+        if mode != Mode::Dc {
+            self.put(114, mode != Mode::V);
+            if mode != Mode::V {
+                self.put(183, mode == Mode::Tm);
+            }
+        }
+    }
+
+    /// 按 zigzag 顺序写出一个块的系数，返回是否有非零系数
+    fn put_block(&mut self, probs: &[[[u8; 11]; 3]; 8], block: &[i32; 16], first: usize, context: usize) -> bool {
+        let Some(last) = (first..16).rev().find(|&i| block[ZIGZAG[i]] != 0) else {
+            self.put(probs[COEFF_BANDS[first]][context][0], false);
+            return false;
+        };
+        let mut context = context;
+        let mut after_zero = false;
+        for i in first..=last {
+            let p = &probs[COEFF_BANDS[i]][context];
+            let value = block[ZIGZAG[i]];
+            // 0 之后不会紧跟块结束，省去 EOB 分支
+            if !after_zero {
+                self.put(p[0], true);
+            }
+            if value == 0 {
+                self.put(p[1], false);
+                context = 0;
+                after_zero = true;
+                continue;
+            }
+            self.put(p[1], true);
+            let abs = value.unsigned_abs();
+            self.put_token(p, abs);
+            self.put_flag(value < 0);
+            context = if abs == 1 { 1 } else { 2 };
+            after_zero = false;
+        }
+        if last < 15 {
+            self.put(probs[COEFF_BANDS[last + 1]][context][0], false);
+        }
+        true
+    }
+
+    /// 非零系数的绝对值：1-4 直接编码，更大的值为类别加额外位
+    fn put_token(&mut self, p: &[u8; 11], abs: u32) {
+        self.put(p[2], abs > 1);
+        if abs == 1 {
+            return;
+        }
+        self.put(p[3], abs > 4);
+        if abs <= 4 {
+            self.put(p[4], abs > 2);
+            if abs > 2 {
+                self.put(p[5], abs == 4);
+            }
+            return;
+        }
+        let category = DCT_CAT_BASE.iter().rposition(|&base| abs >= base).unwrap_or_default();
+        self.put(p[6], category >= 2);
+        if category < 2 {
+            self.put(p[7], category == 1);
+        } else {
+            self.put(p[8], category >= 4);
+            self.put(p[9 + usize::from(category >= 4)], category % 2 == 1);
+        }
+        let extra = abs - DCT_CAT_BASE[category];
+        let probs = &PROB_DCT_CAT[category];
+        let bits = probs.iter().take_while(|&&p| p != 0).count();
+        for (i, &prob) in probs[..bits].iter().enumerate() {
+            self.put(prob, (extra >> (bits - 1 - i)) & 1 != 0);
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        for _ in 0..32 {
+            self.put_flag(false);
+        }
+        self.output
+    }
+}
+
+/// 4x4 前向 DCT（libvpx `vp8_short_fdct4x4_c`）
+fn fdct(input: &[i32; 16]) -> [i32; 16] {
+    let mut out = [0; 16];
+    for i in 0..4 {
+        let ip = &input[i * 4..i * 4 + 4];
+        let a1 = (ip[0] + ip[3]) * 8;
+        let b1 = (ip[1] + ip[2]) * 8;
+        let c1 = (ip[1] - ip[2]) * 8;
+        let d1 = (ip[0] - ip[3]) * 8;
+        out[i * 4] = a1 + b1;
+        out[i * 4 + 2] = a1 - b1;
+        out[i * 4 + 1] = (c1 * 2217 + d1 * 5352 + 14500) >> 12;
+        out[i * 4 + 3] = (d1 * 2217 - c1 * 5352 + 7500) >> 12;
+    }
+    for i in 0..4 {
+        let a1 = out[i] + out[12 + i];
+        let b1 = out[4 + i] + out[8 + i];
+        let c1 = out[4 + i] - out[8 + i];
+        let d1 = out[i] - out[12 + i];
+        out[i] = (a1 + b1 + 7) >> 4;
+        out[8 + i] = (a1 - b1 + 7) >> 4;
+        out[4 + i] = ((c1 * 2217 + d1 * 5352 + 12000) >> 16) + i32::from(d1 != 0);
+        out[12 + i] = (d1 * 2217 - c1 * 5352 + 51000) >> 16;
+    }
+    out
+}
+
+/// 16 个亮度块直流分量的前向 Walsh-Hadamard 变换（libvpx `vp8_short_walsh4x4_c`）
+fn fwht(input: &[i32; 16]) -> [i32; 16] {
+    let mut out = [0; 16];
+    for i in 0..4 {
+        let ip = &input[i * 4..i * 4 + 4];
+        let a1 = (ip[0] + ip[2]) * 4;
+        let d1 = (ip[1] + ip[3]) * 4;
+        let c1 = (ip[1] - ip[3]) * 4;
+        let b1 = (ip[0] - ip[2]) * 4;
+        out[i * 4] = a1 + d1 + i32::from(a1 != 0);
+        out[i * 4 + 1] = b1 + c1;
+        out[i * 4 + 2] = b1 - c1;
+        out[i * 4 + 3] = a1 - d1;
+    }
+    for i in 0..4 {
+        let a1 = out[i] + out[8 + i];
+        let d1 = out[4 + i] + out[12 + i];
+        let c1 = out[4 + i] - out[12 + i];
+        let b1 = out[i] - out[8 + i];
+        let round = |v: i32| (v + i32::from(v < 0) + 3) >> 3;
+        out[i] = round(a1 + d1);
+        out[4 + i] = round(b1 + c1);
+        out[8 + i] = round(b1 - c1);
+        out[12 + i] = round(a1 - d1);
+    }
+    out
+}
+
+/// 4x4 反向 DCT，与解码器逐位一致，重建结果才能作为后续宏块的预测
+fn idct4x4(block: &mut [i32; 16]) {
+    const C1: i64 = 20091;
+    const C2: i64 = 35468;
+    let mut tmp = [0i64; 16];
+    for i in 0..4 {
+        let [b0, b4, b8, b12] = [block[i], block[4 + i], block[8 + i], block[12 + i]].map(i64::from);
+        let a1 = b0 + b8;
+        let b1 = b0 - b8;
+        let c1 = ((b4 * C2) >> 16) - (b12 + ((b12 * C1) >> 16));
+        let d1 = (b4 + ((b4 * C1) >> 16)) + ((b12 * C2) >> 16);
+        tmp[i] = a1 + d1;
+        tmp[4 + i] = b1 + c1;
+        tmp[12 + i] = a1 - d1;
+        tmp[8 + i] = b1 - c1;
+    }
+    for i in 0..4 {
+        let [t0, t1, t2, t3] = [tmp[4 * i], tmp[4 * i + 1], tmp[4 * i + 2], tmp[4 * i + 3]];
+        let a1 = t0 + t2;
+        let b1 = t0 - t2;
+        let c1 = ((t1 * C2) >> 16) - (t3 + ((t3 * C1) >> 16));
+        let d1 = (t1 + ((t1 * C1) >> 16)) + ((t3 * C2) >> 16);
+        block[4 * i] = ((a1 + d1 + 4) >> 3) as i32;
+        block[4 * i + 3] = ((a1 - d1 + 4) >> 3) as i32;
+        block[4 * i + 1] = ((b1 + c1 + 4) >> 3) as i32;
+        block[4 * i + 2] = ((b1 - c1 + 4) >> 3) as i32;
+    }
+}
+
+/// 反向 Walsh-Hadamard 变换，与解码器一致
+fn iwht4x4(block: &mut [i32; 16]) {
+    for i in 0..4 {
+        let a1 = block[i] + block[12 + i];
+        let b1 = block[4 + i] + block[8 + i];
+        let c1 = block[4 + i] - block[8 + i];
+        let d1 = block[i] - block[12 + i];
+        block[i] = a1 + b1;
+        block[4 + i] = c1 + d1;
+        block[8 + i] = a1 - b1;
+        block[12 + i] = d1 - c1;
+    }
+    for row in block.chunks_exact_mut(4) {
+        let a1 = row[0] + row[3];
+        let b1 = row[1] + row[2];
+        let c1 = row[1] - row[2];
+        let d1 = row[0] - row[3];
+        row[0] = (a1 + b1 + 3) >> 3;
+        row[1] = (c1 + d1 + 3) >> 3;
+        row[2] = (a1 - b1 + 3) >> 3;
+        row[3] = (d1 - c1 + 3) >> 3;
+    }
+}
+
+const ZIGZAG: [usize; 16] = [0, 1, 4, 8, 5, 2, 3, 6, 9, 12, 13, 10, 7, 11, 14, 15];
+const COEFF_BANDS: [usize; 16] = [0, 1, 2, 3, 6, 4, 5, 6, 6, 6, 6, 6, 6, 6, 6, 7];
+const DCT_CAT_BASE: [u32; 6] = [5, 7, 11, 19, 35, 67];
+
+const PROB_DCT_CAT: [[u8; 12]; 6] = [
+    [159, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+    [165, 145, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+    [173, 148, 140, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+    [176, 155, 140, 135, 0, 0, 0, 0, 0, 0, 0, 0],
+    [180, 157, 141, 134, 130, 0, 0, 0, 0, 0, 0, 0],
+    [254, 254, 243, 230, 196, 177, 153, 140, 133, 130, 129, 0],
+];
+
+#[rustfmt::skip]
+const DC_QUANT: [i16; 128] = [
+      4,   5,   6,   7,   8,   9,  10,  10,  11,  12,  13,  14,  15,  16,  17,  17,
+     18,  19,  20,  20,  21,  21,  22,  22,  23,  23,  24,  25,  25,  26,  27,  28,
+     29,  30,  31,  32,  33,  34,  35,  36,  37,  37,  38,  39,  40,  41,  42,  43,
+     44,  45,  46,  46,  47,  48,  49,  50,  51,  52,  53,  54,  55,  56,  57,  58,
+     59,  60,  61,  62,  63,  64,  65,  66,  67,  68,  69,  70,  71,  72,  73,  74,
+     75,  76,  76,  77,  78,  79,  80,  81,  82,  83,  84,  85,  86,  87,  88,  89,
+     91,  93,  95,  96,  98, 100, 101, 102, 104, 106, 108, 110, 112, 114, 116, 118,
+    122, 124, 126, 128, 130, 132, 134, 136, 138, 140, 143, 145, 148, 151, 154, 157,
+];
+
+#[rustfmt::skip]
+const AC_QUANT: [i16; 128] = [
+      4,   5,   6,   7,   8,   9,  10,  11,  12,  13,  14,  15,  16,  17,  18,  19,
+     20,  21,  22,  23,  24,  25,  26,  27,  28,  29,  30,  31,  32,  33,  34,  35,
+     36,  37,  38,  39,  40,  41,  42,  43,  44,  45,  46,  47,  48,  49,  50,  51,
+     52,  53,  54,  55,  56,  57,  58,  60,  62,  64,  66,  68,  70,  72,  74,  76,
+     78,  80,  82,  84,  86,  88,  90,  92,  94,  96,  98, 100, 102, 104, 106, 108,
+    110, 112, 114, 116, 119, 122, 125, 128, 131, 134, 137, 140, 143, 146, 149, 152,
+    155, 158, 161, 164, 167, 170, 173, 177, 181, 185, 189, 193, 197, 201, 205, 209,
+    213, 217, 221, 225, 229, 234, 239, 245, 249, 254, 259, 264, 269, 274, 279, 284,
+];
+
+/// 系数概率：[块类型][频带][上下文][树节点]
+type TokenProbs = [[[[u8; 11]; 3]; 8]; 4];
+
+#[rustfmt::skip]
+const COEFF_PROBS: TokenProbs = [
+    [
+        [[128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128], [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128], [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128]],
+        [[253, 136, 254, 255, 228, 219, 128, 128, 128, 128, 128], [189, 129, 242, 255, 227, 213, 255, 219, 128, 128, 128], [106, 126, 227, 252, 214, 209, 255, 255, 128, 128, 128]],
+        [[1, 98, 248, 255, 236, 226, 255, 255, 128, 128, 128], [181, 133, 238, 254, 221, 234, 255, 154, 128, 128, 128], [78, 134, 202, 247, 198, 180, 255, 219, 128, 128, 128]],
+        [[1, 185, 249, 255, 243, 255, 128, 128, 128, 128, 128], [184, 150, 247, 255, 236, 224, 128, 128, 128, 128, 128], [77, 110, 216, 255, 236, 230, 128, 128, 128, 128, 128]],
+        [[1, 101, 251, 255, 241, 255, 128, 128, 128, 128, 128], [170, 139, 241, 252, 236, 209, 255, 255, 128, 128, 128], [37, 116, 196, 243, 228, 255, 255, 255, 128, 128, 128]],
+        [[1, 204, 254, 255, 245, 255, 128, 128, 128, 128, 128], [207, 160, 250, 255, 238, 128, 128, 128, 128, 128, 128], [102, 103, 231, 255, 211, 171, 128, 128, 128, 128, 128]],
+        [[1, 152, 252, 255, 240, 255, 128, 128, 128, 128, 128], [177, 135, 243, 255, 234, 225, 128, 128, 128, 128, 128], [80, 129, 211, 255, 194, 224, 128, 128, 128, 128, 128]],
+        [[1, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128], [246, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128], [255, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128]],
+    ],
+    [
+        [[198, 35, 237, 223, 193, 187, 162, 160, 145, 155, 62], [131, 45, 198, 221, 172, 176, 220, 157, 252, 221, 1], [68, 47, 146, 208, 149, 167, 221, 162, 255, 223, 128]],
+        [[1, 149, 241, 255, 221, 224, 255, 255, 128, 128, 128], [184, 141, 234, 253, 222, 220, 255, 199, 128, 128, 128], [81, 99, 181, 242, 176, 190, 249, 202, 255, 255, 128]],
+        [[1, 129, 232, 253, 214, 197, 242, 196, 255, 255, 128], [99, 121, 210, 250, 201, 198, 255, 202, 128, 128, 128], [23, 91, 163, 242, 170, 187, 247, 210, 255, 255, 128]],
+        [[1, 200, 246, 255, 234, 255, 128, 128, 128, 128, 128], [109, 178, 241, 255, 231, 245, 255, 255, 128, 128, 128], [44, 130, 201, 253, 205, 192, 255, 255, 128, 128, 128]],
+        [[1, 132, 239, 251, 219, 209, 255, 165, 128, 128, 128], [94, 136, 225, 251, 218, 190, 255, 255, 128, 128, 128], [22, 100, 174, 245, 186, 161, 255, 199, 128, 128, 128]],
+        [[1, 182, 249, 255, 232, 235, 128, 128, 128, 128, 128], [124, 143, 241, 255, 227, 234, 128, 128, 128, 128, 128], [35, 77, 181, 251, 193, 211, 255, 205, 128, 128, 128]],
+        [[1, 157, 247, 255, 236, 231, 255, 255, 128, 128, 128], [121, 141, 235, 255, 225, 227, 255, 255, 128, 128, 128], [45, 99, 188, 251, 195, 217, 255, 224, 128, 128, 128]],
+        [[1, 1, 251, 255, 213, 255, 128, 128, 128, 128, 128], [203, 1, 248, 255, 255, 128, 128, 128, 128, 128, 128], [137, 1, 177, 255, 224, 255, 128, 128, 128, 128, 128]],
+    ],
+    [
+        [[253, 9, 248, 251, 207, 208, 255, 192, 128, 128, 128], [175, 13, 224, 243, 193, 185, 249, 198, 255, 255, 128], [73, 17, 171, 221, 161, 179, 236, 167, 255, 234, 128]],
+        [[1, 95, 247, 253, 212, 183, 255, 255, 128, 128, 128], [239, 90, 244, 250, 211, 209, 255, 255, 128, 128, 128], [155, 77, 195, 248, 188, 195, 255, 255, 128, 128, 128]],
+        [[1, 24, 239, 251, 218, 219, 255, 205, 128, 128, 128], [201, 51, 219, 255, 196, 186, 128, 128, 128, 128, 128], [69, 46, 190, 239, 201, 218, 255, 228, 128, 128, 128]],
+        [[1, 191, 251, 255, 255, 128, 128, 128, 128, 128, 128], [223, 165, 249, 255, 213, 255, 128, 128, 128, 128, 128], [141, 124, 248, 255, 255, 128, 128, 128, 128, 128, 128]],
+        [[1, 16, 248, 255, 255, 128, 128, 128, 128, 128, 128], [190, 36, 230, 255, 236, 255, 128, 128, 128, 128, 128], [149, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128]],
+        [[1, 226, 255, 128, 128, 128, 128, 128, 128, 128, 128], [247, 192, 255, 128, 128, 128, 128, 128, 128, 128, 128], [240, 128, 255, 128, 128, 128, 128, 128, 128, 128, 128]],
+        [[1, 134, 252, 255, 255, 128, 128, 128, 128, 128, 128], [213, 62, 250, 255, 255, 128, 128, 128, 128, 128, 128], [55, 93, 255, 128, 128, 128, 128, 128, 128, 128, 128]],
+        [[128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128], [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128], [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128]],
+    ],
+    [
+        [[202, 24, 213, 235, 186, 191, 220, 160, 240, 175, 255], [126, 38, 182, 232, 169, 184, 228, 174, 255, 187, 128], [61, 46, 138, 219, 151, 178, 240, 170, 255, 216, 128]],
+        [[1, 112, 230, 250, 199, 191, 247, 159, 255, 255, 128], [166, 109, 228, 252, 211, 215, 255, 174, 128, 128, 128], [39, 77, 162, 232, 172, 180, 245, 178, 255, 255, 128]],
+        [[1, 52, 220, 246, 198, 199, 249, 220, 255, 255, 128], [124, 74, 191, 243, 183, 193, 250, 221, 255, 255, 128], [24, 71, 130, 219, 154, 170, 243, 182, 255, 255, 128]],
+        [[1, 182, 225, 249, 219, 240, 255, 224, 128, 128, 128], [149, 150, 226, 252, 216, 205, 255, 171, 128, 128, 128], [28, 108, 170, 242, 183, 194, 254, 223, 255, 255, 128]],
+        [[1, 81, 230, 252, 204, 203, 255, 192, 128, 128, 128], [123, 102, 209, 247, 188, 196, 255, 233, 128, 128, 128], [20, 95, 153, 243, 164, 173, 255, 203, 128, 128, 128]],
+        [[1, 222, 248, 255, 216, 213, 128, 128, 128, 128, 128], [168, 175, 246, 252, 235, 205, 255, 255, 128, 128, 128], [47, 116, 215, 255, 211, 212, 255, 255, 128, 128, 128]],
+        [[1, 121, 236, 253, 212, 214, 255, 255, 128, 128, 128], [141, 84, 213, 252, 201, 202, 255, 219, 128, 128, 128], [42, 80, 160, 240, 162, 185, 255, 205, 128, 128, 128]],
+        [[1, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128], [244, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128], [238, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128]],
+    ],
+];
+
+#[rustfmt::skip]
+const COEFF_UPDATE_PROBS: TokenProbs = [
+    [
+        [[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[176, 246, 255, 255, 255, 255, 255, 255, 255, 255, 255], [223, 241, 252, 255, 255, 255, 255, 255, 255, 255, 255], [249, 253, 253, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 244, 252, 255, 255, 255, 255, 255, 255, 255, 255], [234, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255], [253, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 246, 254, 255, 255, 255, 255, 255, 255, 255, 255], [239, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255], [254, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 248, 254, 255, 255, 255, 255, 255, 255, 255, 255], [251, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255], [251, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255], [254, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 254, 253, 255, 254, 255, 255, 255, 255, 255, 255], [250, 255, 254, 255, 254, 255, 255, 255, 255, 255, 255], [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+    ],
+    [
+        [[217, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [225, 252, 241, 253, 255, 255, 254, 255, 255, 255, 255], [234, 250, 241, 250, 253, 255, 253, 254, 255, 255, 255]],
+        [[255, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255], [223, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255], [238, 253, 254, 254, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 248, 254, 255, 255, 255, 255, 255, 255, 255, 255], [249, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 253, 255, 255, 255, 255, 255, 255, 255, 255, 255], [247, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255], [252, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255], [253, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 254, 253, 255, 255, 255, 255, 255, 255, 255, 255], [250, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+    ],
+    [
+        [[186, 251, 250, 255, 255, 255, 255, 255, 255, 255, 255], [234, 251, 244, 254, 255, 255, 255, 255, 255, 255, 255], [251, 251, 243, 253, 254, 255, 254, 255, 255, 255, 255]],
+        [[255, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255], [236, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255], [251, 253, 253, 254, 254, 255, 255, 255, 255, 255, 255]],
+        [[255, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255], [254, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255], [254, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255], [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+    ],
+    [
+        [[248, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [250, 254, 252, 254, 255, 255, 255, 255, 255, 255, 255], [248, 254, 249, 253, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 253, 253, 255, 255, 255, 255, 255, 255, 255, 255], [246, 253, 253, 255, 255, 255, 255, 255, 255, 255, 255], [252, 254, 251, 254, 254, 255, 255, 255, 255, 255, 255]],
+        [[255, 254, 252, 255, 255, 255, 255, 255, 255, 255, 255], [248, 254, 253, 255, 255, 255, 255, 255, 255, 255, 255], [253, 255, 254, 254, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 251, 254, 255, 255, 255, 255, 255, 255, 255, 255], [245, 251, 254, 255, 255, 255, 255, 255, 255, 255, 255], [253, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 251, 253, 255, 255, 255, 255, 255, 255, 255, 255], [252, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255], [255, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 252, 255, 255, 255, 255, 255, 255, 255, 255, 255], [249, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 255, 253, 255, 255, 255, 255, 255, 255, 255, 255], [250, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+        [[255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255], [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]],
+    ],
+];
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageFormat, Rgba};
+
+    use super::*;
+
+    /// 平滑的彩色渐变加上亮度上的细条纹，宽高不是 16 的倍数
+    ///
+    /// 细节只放在亮度上：色度按 4:2:0 采样，细的彩色条纹本来就保留不了
+    fn sample(width: u32, height: u32, alpha: impl Fn(u32, u32) -> u8) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let stripe = if (x / 3 + y / 5) % 2 == 0 { 60 } else { 0 };
+            Rgba([(x * 2 + stripe) as u8, (y * 3 + stripe) as u8, (100 + stripe) as u8, alpha(x, y)])
+        })
+    }
+
+    fn decode(data: &[u8]) -> RgbaImage {
+        image::load_from_memory_with_format(data, ImageFormat::WebP).unwrap().to_rgba8()
+    }
+
+    fn psnr(a: &RgbaImage, b: &RgbaImage) -> f64 {
+        let mse = a
+            .pixels()
+            .zip(b.pixels())
+            .flat_map(|(p, q)| (0..3).map(move |i| (f64::from(p[i]) - f64::from(q[i])).powi(2)))
+            .sum::<f64>()
+            / (a.width() * a.height() * 3) as f64;
+        10.0 * (255.0 * 255.0 / mse.max(1e-9)).log10()
+    }
+
+    #[test]
+    fn decodes_close_to_source() {
+        let img = sample(61, 45, |_, _| 255);
+        let data = encode_lossy(&img, 90).unwrap();
+        assert_eq!(&data[12..16], b"VP8 ", "opaque images use the simple format");
+        let decoded = decode(&data);
+        assert_eq!(decoded.dimensions(), img.dimensions());
+        assert!(psnr(&img, &decoded) > 32.0, "psnr {}", psnr(&img, &decoded));
+        assert!(decoded.pixels().all(|p| p[3] == 255));
+    }
+
+    #[test]
+    fn lower_quality_is_smaller() {
+        let img = sample(64, 48, |_, _| 255);
+        let high = encode_lossy(&img, 95).unwrap();
+        let low = encode_lossy(&img, 20).unwrap();
+        assert!(low.len() < high.len(), "{} >= {}", low.len(), high.len());
+        assert!(psnr(&img, &decode(&low)) > 18.0);
+        assert!(psnr(&img, &decode(&high)) > psnr(&img, &decode(&low)));
+    }
+
+    #[test]
+    fn keeps_flat_colors() {
+        let white = RgbaImage::from_pixel(20, 20, Rgba([255, 255, 255, 255]));
+        let decoded = decode(&encode_lossy(&white, 50).unwrap());
+        assert!(decoded.pixels().all(|p| p.0.iter().all(|&c| c >= 254)), "{:?}", decoded.get_pixel(0, 0));
+    }
+
+    #[test]
+    fn alpha_is_lossless() {
+        let img = sample(37, 29, |x, y| ((x * 7 + y * 3) % 256) as u8);
+        let data = encode_lossy(&img, 80).unwrap();
+        assert_eq!(&data[12..16], b"VP8X");
+        let decoded = decode(&data);
+        for (p, q) in img.pixels().zip(decoded.pixels()) {
+            assert_eq!(p[3], q[3]);
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_images() {
+        assert!(encode_lossy(&RgbaImage::new(MAX_SIZE + 1, 1), 80).is_err());
+    }
+}